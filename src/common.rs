@@ -1,3 +1,5 @@
+pub mod checkpoint;
 pub mod db;
 pub mod lmdb_utils;
 pub mod progress;
+pub mod trie_walk;
@@ -0,0 +1,128 @@
+use std::{
+    fs::{self, File},
+    io::{BufReader, BufWriter, Error as IoError, Write},
+    path::{Path, PathBuf},
+    result::Result,
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Error as JsonError;
+use thiserror::Error;
+
+/// Suffix appended to the checkpoint path to get the temporary file path.
+const TMP_SUFFIX: &str = ".tmp";
+
+/// Errors encountered when loading or storing a checkpoint file.
+#[derive(Debug, Error)]
+pub enum Error {
+    /// Error reading or writing the checkpoint file.
+    #[error("Error accessing checkpoint file {0}: {1}")]
+    Io(PathBuf, IoError),
+    /// Error (de)serializing the checkpoint contents.
+    #[error("Error (de)serializing checkpoint file {0}: {1}")]
+    Json(PathBuf, JsonError),
+}
+
+/// Returns the path of the temporary file a checkpoint at `path` is first
+/// written to. The suffix is appended rather than replacing the extension,
+/// so it differs from `path` even if that ends in `.tmp`.
+fn tmp_path(path: &Path) -> PathBuf {
+    let mut tmp_path = path.as_os_str().to_os_string();
+    tmp_path.push(TMP_SUFFIX);
+    PathBuf::from(tmp_path)
+}
+
+/// Loads a checkpoint previously saved at `path`.
+pub fn load<T: DeserializeOwned, P: AsRef<Path>>(path: P) -> Result<T, Error> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|io_err| Error::Io(path.to_path_buf(), io_err))?;
+    serde_json::from_reader(BufReader::new(file))
+        .map_err(|json_err| Error::Json(path.to_path_buf(), json_err))
+}
+
+/// Saves a checkpoint at `path`, replacing any previous one.
+///
+/// The contents are first written to a temporary file next to `path` which
+/// is then renamed over it, so an interruption never leaves a truncated
+/// checkpoint behind.
+pub fn store<T: Serialize, P: AsRef<Path>>(path: P, checkpoint: &T) -> Result<(), Error> {
+    let path = path.as_ref();
+    let tmp_path = tmp_path(path);
+    {
+        let file = File::create(&tmp_path).map_err(|io_err| Error::Io(tmp_path.clone(), io_err))?;
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer(&mut writer, checkpoint)
+            .map_err(|json_err| Error::Json(tmp_path.clone(), json_err))?;
+        writer
+            .flush()
+            .map_err(|io_err| Error::Io(tmp_path.clone(), io_err))?;
+    }
+    fs::rename(&tmp_path, path).map_err(|io_err| Error::Io(path.to_path_buf(), io_err))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::{load, store, tmp_path, Error};
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct MockCheckpoint {
+        name: String,
+        position: u64,
+    }
+
+    #[test]
+    fn checkpoint_roundtrip() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let path = tmp_dir.path().join("checkpoint.json");
+
+        let first = MockCheckpoint {
+            name: "first".to_string(),
+            position: 1,
+        };
+        store(&path, &first).unwrap();
+        assert_eq!(load::<MockCheckpoint, _>(&path).unwrap(), first);
+
+        // Storing again should replace the previous checkpoint.
+        let second = MockCheckpoint {
+            name: "second".to_string(),
+            position: 2,
+        };
+        store(&path, &second).unwrap();
+        assert_eq!(load::<MockCheckpoint, _>(&path).unwrap(), second);
+        // No temporary file should be left behind.
+        assert_eq!(std::fs::read_dir(tmp_dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn checkpoint_with_tmp_extension() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let path = tmp_dir.path().join("checkpoint.tmp");
+
+        let checkpoint = MockCheckpoint {
+            name: "tmp".to_string(),
+            position: 3,
+        };
+        assert_eq!(tmp_path(&path), tmp_dir.path().join("checkpoint.tmp.tmp"));
+        store(&path, &checkpoint).unwrap();
+        assert_eq!(load::<MockCheckpoint, _>(&path).unwrap(), checkpoint);
+        assert_eq!(std::fs::read_dir(tmp_dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn missing_or_invalid_checkpoint() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let path = tmp_dir.path().join("checkpoint.json");
+        assert!(matches!(
+            load::<MockCheckpoint, _>(&path),
+            Err(Error::Io(..))
+        ));
+
+        std::fs::write(&path, b"not a checkpoint").unwrap();
+        assert!(matches!(
+            load::<MockCheckpoint, _>(&path),
+            Err(Error::Json(..))
+        ));
+    }
+}
@@ -27,21 +27,33 @@ pub use transfer_db::TransferDatabase;
 pub use transfer_hashes_db::TransferHashesDatabase;
 
 use std::{
+    cell::RefCell,
+    collections::{hash_map::Entry, HashMap},
     fmt::{Display, Formatter, Result as FormatterResult},
+    iter,
     path::Path,
     result::Result,
 };
 
 use bincode::Error as BincodeError;
-use lmdb::{Cursor, Environment, EnvironmentFlags, Error as LmdbError, RoCursor, Transaction};
+use lmdb::{
+    Cursor, Database as LmdbDatabase, Environment, EnvironmentFlags, Error as LmdbError, RoCursor,
+    RoTransaction, Transaction,
+};
+use lmdb_sys::MDB_SET_RANGE;
 use log::info;
 use thiserror::Error;
 
+use casper_hashing::Digest;
+use casper_node::types::{DeployConfigurationFailure, DeployHash};
 use casper_types::bytesrepr::Error as BytesreprError;
 
+use super::checkpoint::Error as CheckpointError;
+
 pub const STORAGE_FILE_NAME: &str = "storage.lmdb";
 pub const TRIE_STORE_FILE_NAME: &str = "data.lmdb";
 const ENTRY_LOG_INTERVAL: usize = 100_000;
+const CHECKPOINT_INTERVAL: usize = ENTRY_LOG_INTERVAL;
 const MAX_DB_READERS: u32 = 100;
 
 #[derive(Debug, Error)]
@@ -50,6 +62,8 @@ pub enum DeserializationError {
     BincodeError(#[from] BincodeError),
     #[error("failed parsing struct with bytesrepr")]
     BytesreprError(String),
+    #[error("key has {0} bytes instead of {1}")]
+    InvalidKeyLength(usize, usize),
 }
 
 impl From<BytesreprError> for DeserializationError {
@@ -58,22 +72,63 @@ impl From<BytesreprError> for DeserializationError {
     }
 }
 
+/// Inconsistencies found in entries which parsed successfully.
+#[derive(Debug, Error)]
+pub enum ValidationError {
+    #[error("database error: {0}")]
+    Database(#[from] LmdbError),
+    #[error("invalid approval at index {0}: {1}")]
+    InvalidApproval(usize, String),
+    #[error("invalid deploy: {0}")]
+    InvalidDeploy(Box<DeployConfigurationFailure>),
+    #[error("key {0} doesn't match deploy hash {1}")]
+    KeyMismatch(String, DeployHash),
+    #[error("no deploy with hash {0} in the deploys database")]
+    MissingDeploy(String),
+}
+
+impl From<DeployConfigurationFailure> for ValidationError {
+    fn from(failure: DeployConfigurationFailure) -> Self {
+        Self::InvalidDeploy(Box::new(failure))
+    }
+}
+
+/// Error found when checking a single entry of a database.
+#[derive(Debug, Error)]
+pub enum EntryError {
+    #[error(transparent)]
+    Parsing(#[from] DeserializationError),
+    #[error(transparent)]
+    Validation(#[from] ValidationError),
+}
+
 /// Errors encountered when operating on the storage database.
 #[derive(Debug, Error)]
 pub enum Error {
     /// Errors accumulated when parsing a database with "--no-failfast".
     Accumulated(Vec<Self>),
+    /// Parsing error on the key of the entry at index in the database.
+    KeyParsing(usize, DeserializationError),
     /// Parsing error on entry at index in the database.
     Parsing(usize, DeserializationError),
+    /// Validation error on entry at index in the database.
+    Validation(usize, ValidationError),
     /// Database operation error.
     Database(#[from] LmdbError),
+    /// Error saving the progress of the check.
+    Checkpoint(#[from] CheckpointError),
 }
 
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
         match self {
             Self::Database(e) => write!(f, "Error operating the database: {e}"),
+            Self::Checkpoint(e) => write!(f, "Error saving checkpoint: {e}"),
+            Self::KeyParsing(idx, inner) => {
+                write!(f, "Error parsing key of element {idx}: {inner}")
+            }
             Self::Parsing(idx, inner) => write!(f, "Error parsing element {idx}: {inner}"),
+            Self::Validation(idx, inner) => write!(f, "Error validating element {idx}: {inner}"),
             Self::Accumulated(accumulated_errors) => {
                 writeln!(f, "Errors caught:")?;
                 for error in accumulated_errors {
@@ -97,22 +152,188 @@ pub fn db_env<P: AsRef<Path>>(path: P) -> Result<Environment, LmdbError> {
     Ok(env)
 }
 
+/// Same as [`db_env`], opening the environment read-only.
+pub fn read_only_db_env<P: AsRef<Path>>(path: P) -> Result<Environment, LmdbError> {
+    let env = Environment::new()
+        .set_flags(
+            EnvironmentFlags::NO_SUB_DIR
+                | EnvironmentFlags::NO_TLS
+                | EnvironmentFlags::NO_READAHEAD
+                | EnvironmentFlags::READ_ONLY,
+        )
+        .set_max_dbs(MAX_DB_READERS)
+        .open(path.as_ref())?;
+    Ok(env)
+}
+
+/// Names of all the databases in the storage, in the order in which they
+/// are checked.
+pub const DB_NAMES: [&str; 12] = [
+    "block_body",
+    "block_body_merkle",
+    "block_header",
+    "block_metadata",
+    "deploy_hashes",
+    "deploy_metadata",
+    "deploys",
+    "finalized_approvals",
+    "proposers",
+    "state_store",
+    "transfer",
+    "transfer_hashes",
+];
+
+/// Other databases read while validating the entries of a database. Each
+/// of them is opened at most once per scan.
+pub struct ValidationDbs<'a> {
+    txn: &'a RoTransaction<'a>,
+    opened: RefCell<HashMap<&'static str, LmdbDatabase>>,
+}
+
+impl<'a> ValidationDbs<'a> {
+    pub fn new(txn: &'a RoTransaction<'a>) -> Self {
+        Self {
+            txn,
+            opened: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the value under `key` in the database named `db_name`, if
+    /// any.
+    pub fn get(&self, db_name: &'static str, key: &[u8]) -> Result<Option<&'a [u8]>, LmdbError> {
+        let db = match self.opened.borrow_mut().entry(db_name) {
+            Entry::Occupied(entry) => *entry.get(),
+            Entry::Vacant(entry) => *entry.insert(unsafe { self.txn.open_db(Some(db_name))? }),
+        };
+        match self.txn.get(db, &key) {
+            Ok(value) => Ok(Some(value)),
+            Err(LmdbError::NotFound) => Ok(None),
+            Err(lmdb_err) => Err(lmdb_err),
+        }
+    }
+}
+
+/// Function parsing the value of an entry in a database.
+pub type ParseFn = fn(&[u8]) -> Result<(), DeserializationError>;
+
+/// Returns the function parsing the values of the storage database with
+/// the given name.
+pub fn parse_fn(db_name: &str) -> Option<ParseFn> {
+    let parse_fn: ParseFn = match db_name {
+        "block_body" => BlockBodyDatabase::parse_element,
+        "block_body_merkle" => BlockBodyMerkleDatabase::parse_element,
+        "block_header" => BlockHeaderDatabase::parse_element,
+        "block_metadata" => BlockMetadataDatabase::parse_element,
+        "deploy_hashes" => DeployHashesDatabase::parse_element,
+        "deploy_metadata" => DeployMetadataDatabase::parse_element,
+        "deploys" => DeployDatabase::parse_element,
+        "finalized_approvals" => FinalizedApprovalsDatabase::parse_element,
+        "proposers" => ProposerDatabase::parse_element,
+        "state_store" => StateStoreDatabase::parse_element,
+        "transfer" => TransferDatabase::parse_element,
+        "transfer_hashes" => TransferHashesDatabase::parse_element,
+        _ => return None,
+    };
+    Some(parse_fn)
+}
+
+/// Parses a key made of a digest, such as a block or deploy hash.
+pub fn parse_digest_key(bytes: &[u8]) -> Result<(), DeserializationError> {
+    if bytes.len() != Digest::LENGTH {
+        return Err(DeserializationError::InvalidKeyLength(
+            bytes.len(),
+            Digest::LENGTH,
+        ));
+    }
+    Ok(())
+}
+
+/// Position in a database from which parsing starts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StartPosition<'a> {
+    /// Skip the given number of entries from the start of the database.
+    Index(usize),
+    /// Seek to the first entry with a key greater than the given one.
+    AfterKey(&'a [u8]),
+}
+
+/// Callback receiving the key of the last entry parsed in a database,
+/// periodically and once parsing completes.
+pub type CheckpointFn<'a> = dyn FnMut(&[u8]) -> Result<(), Error> + 'a;
+
 pub trait Database {
     fn db_name() -> &'static str;
 
+    /// Parses a key of an entry in a database.
+    fn parse_key(bytes: &[u8]) -> Result<(), DeserializationError>;
+
     /// Parses a value of an entry in a database.
     fn parse_element(bytes: &[u8]) -> Result<(), DeserializationError>;
 
-    /// Parses all elements of a database by trying to deserialize them sequentially.
-    fn parse_elements(mut cursor: RoCursor, failfast: bool, start_at: usize) -> Result<(), Error> {
-        if start_at > 0 {
-            info!("Skipping {} entries.", start_at);
-        }
+    /// Parses the value of an entry whose key parsed successfully and
+    /// validates the consistency of the entry, possibly against other
+    /// databases read through `dbs`. Databases with validation override
+    /// this so that each value is deserialized only once.
+    fn check_entry(_dbs: &ValidationDbs, _key: &[u8], value: &[u8]) -> Result<(), EntryError> {
+        Self::parse_element(value)?;
+        Ok(())
+    }
+
+    /// Parses all elements of a database by trying to deserialize their keys
+    /// and values sequentially, validating the entries which parse.
+    ///
+    /// Every `CHECKPOINT_INTERVAL` entries, and after the last one,
+    /// `on_checkpoint` is called with the key of the last parsed entry. Once
+    /// an entry failed, it's called with the key of the last entry before it.
+    fn parse_elements(
+        txn: &RoTransaction,
+        mut cursor: RoCursor,
+        failfast: bool,
+        start: StartPosition,
+        on_checkpoint: &mut CheckpointFn,
+    ) -> Result<(), Error> {
+        let entries: Box<dyn Iterator<Item = (&[u8], &[u8])>> = match start {
+            StartPosition::Index(start_at) => {
+                if start_at > 0 {
+                    info!("Skipping {} entries.", start_at);
+                }
+                Box::new(cursor.iter().skip(start_at))
+            }
+            StartPosition::AfterKey(last_key) => {
+                info!("Resuming after key {}.", hex::encode(last_key));
+                // Position the cursor on the first key greater than or equal
+                // to `last_key`, then continue from there. If `last_key` is
+                // no longer in the database, the entry the cursor landed on
+                // hasn't been parsed yet.
+                match cursor.get(Some(last_key), None, MDB_SET_RANGE) {
+                    Ok((maybe_key, value)) => {
+                        let first_entry = maybe_key
+                            .filter(|key| *key != last_key)
+                            .map(|key| (key, value));
+                        Box::new(first_entry.into_iter().chain(cursor.iter()))
+                    }
+                    Err(LmdbError::NotFound) => Box::new(iter::empty()),
+                    Err(lmdb_err) => return Err(lmdb_err.into()),
+                }
+            }
+        };
+        let validation_dbs = ValidationDbs::new(txn);
         let mut error_buffer = vec![];
-        for (idx, (_raw_key, raw_val)) in cursor.iter().skip(start_at).enumerate() {
-            if let Err(e) =
-                Self::parse_element(raw_val).map_err(|parsing_err| Error::Parsing(idx, parsing_err))
-            {
+        let mut last_key = None;
+        for (idx, (raw_key, raw_val)) in entries.enumerate() {
+            let key_result =
+                Self::parse_key(raw_key).map_err(|parsing_err| Error::KeyParsing(idx, parsing_err));
+            // Only entries with a key in the expected format can be validated.
+            let entry_result = if key_result.is_ok() {
+                Self::check_entry(&validation_dbs, raw_key, raw_val)
+            } else {
+                Self::parse_element(raw_val).map_err(EntryError::from)
+            }
+            .map_err(|entry_err| match entry_err {
+                EntryError::Parsing(parsing_err) => Error::Parsing(idx, parsing_err),
+                EntryError::Validation(validation_err) => Error::Validation(idx, validation_err),
+            });
+            for e in [key_result.err(), entry_result.err()].into_iter().flatten() {
                 if failfast {
                     return Err(e);
                 } else {
@@ -122,6 +343,18 @@ pub trait Database {
             if idx % ENTRY_LOG_INTERVAL == 0 {
                 info!("Parsed {} entries...", idx);
             }
+            // The checkpoint never moves past a failed entry, so that a
+            // resumed check reports it again.
+            if !error_buffer.is_empty() {
+                continue;
+            }
+            if (idx + 1) % CHECKPOINT_INTERVAL == 0 {
+                on_checkpoint(raw_key)?;
+            }
+            last_key = Some(raw_key);
+        }
+        if let Some(last_key) = last_key {
+            on_checkpoint(last_key)?;
         }
         info!("Parsing complete.");
         if !failfast && !error_buffer.is_empty() {
@@ -130,14 +363,20 @@ pub trait Database {
         Ok(())
     }
 
-    /// Validates the database by ensuring every value of an entry can be parsed.
-    fn check_db(env: &Environment, failfast: bool, start_at: usize) -> Result<(), Error> {
+    /// Validates the database by ensuring every entry can be parsed and is
+    /// consistent.
+    fn check_db(
+        env: &Environment,
+        failfast: bool,
+        start: StartPosition,
+        on_checkpoint: &mut CheckpointFn,
+    ) -> Result<(), Error> {
         info!("Checking {} database.", Self::db_name());
         let txn = env.begin_ro_txn()?;
         let db = unsafe { txn.open_db(Some(Self::db_name()))? };
 
         if let Ok(cursor) = txn.open_ro_cursor(db) {
-            Self::parse_elements(cursor, failfast, start_at)?;
+            Self::parse_elements(&txn, cursor, failfast, start, on_checkpoint)?;
         }
         Ok(())
     }
@@ -5,7 +5,7 @@ use std::{
 
 use casper_node::types::BlockBody;
 
-use super::{Database, DeserializationError};
+use super::{parse_digest_key, Database, DeserializationError};
 
 pub struct BlockBodyDatabase;
 
@@ -20,6 +20,10 @@ impl Database for BlockBodyDatabase {
         "block_body"
     }
 
+    fn parse_key(bytes: &[u8]) -> Result<(), DeserializationError> {
+        parse_digest_key(bytes)
+    }
+
     fn parse_element(bytes: &[u8]) -> Result<(), DeserializationError> {
         let _: BlockBody = bincode::deserialize(bytes)?;
         Ok(())
@@ -6,7 +6,7 @@ use std::{
 use casper_hashing::Digest;
 use casper_types::bytesrepr::FromBytes;
 
-use super::{Database, DeserializationError};
+use super::{parse_digest_key, Database, DeserializationError};
 
 pub struct BlockBodyMerkleDatabase;
 
@@ -21,6 +21,10 @@ impl Database for BlockBodyMerkleDatabase {
         "block_body_merkle"
     }
 
+    fn parse_key(bytes: &[u8]) -> Result<(), DeserializationError> {
+        parse_digest_key(bytes)
+    }
+
     fn parse_element(bytes: &[u8]) -> Result<(), DeserializationError> {
         let _: (Digest, Digest) = FromBytes::from_bytes(bytes)?.0;
         Ok(())
@@ -5,7 +5,7 @@ use std::{
 
 use casper_node::types::BlockHeader;
 
-use super::{Database, DeserializationError};
+use super::{parse_digest_key, Database, DeserializationError};
 
 pub struct BlockHeaderDatabase;
 
@@ -20,6 +20,10 @@ impl Database for BlockHeaderDatabase {
         "block_header"
     }
 
+    fn parse_key(bytes: &[u8]) -> Result<(), DeserializationError> {
+        parse_digest_key(bytes)
+    }
+
     fn parse_element(bytes: &[u8]) -> Result<(), DeserializationError> {
         let _: BlockHeader = bincode::deserialize(bytes)?;
         Ok(())
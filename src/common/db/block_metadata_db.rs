@@ -5,7 +5,7 @@ use std::{
 
 use casper_node::types::BlockSignatures;
 
-use super::{Database, DeserializationError};
+use super::{parse_digest_key, Database, DeserializationError};
 
 pub struct BlockMetadataDatabase;
 
@@ -20,6 +20,10 @@ impl Database for BlockMetadataDatabase {
         "block_metadata"
     }
 
+    fn parse_key(bytes: &[u8]) -> Result<(), DeserializationError> {
+        parse_digest_key(bytes)
+    }
+
     fn parse_element(bytes: &[u8]) -> Result<(), DeserializationError> {
         let _: BlockSignatures = bincode::deserialize(bytes)?;
         Ok(())
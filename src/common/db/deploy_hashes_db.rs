@@ -5,7 +5,7 @@ use std::{
 
 use casper_types::{bytesrepr::FromBytes, DeployHash};
 
-use super::{Database, DeserializationError};
+use super::{parse_digest_key, Database, DeserializationError};
 
 pub struct DeployHashesDatabase;
 
@@ -20,6 +20,10 @@ impl Database for DeployHashesDatabase {
         "deploy_hashes"
     }
 
+    fn parse_key(bytes: &[u8]) -> Result<(), DeserializationError> {
+        parse_digest_key(bytes)
+    }
+
     fn parse_element(bytes: &[u8]) -> Result<(), DeserializationError> {
         let _: Vec<DeployHash> = FromBytes::from_bytes(bytes)?.0;
         Ok(())
@@ -4,7 +4,7 @@ use std::{
     result::Result,
 };
 
-use super::{Database, DeserializationError};
+use super::{parse_digest_key, Database, DeserializationError};
 
 pub struct DeployMetadataDatabase;
 
@@ -19,6 +19,10 @@ impl Database for DeployMetadataDatabase {
         "deploy_metadata"
     }
 
+    fn parse_key(bytes: &[u8]) -> Result<(), DeserializationError> {
+        parse_digest_key(bytes)
+    }
+
     fn parse_element(bytes: &[u8]) -> Result<(), DeserializationError> {
         let _: DeployMetadata = bincode::deserialize(bytes)?;
         Ok(())
@@ -3,10 +3,11 @@ use std::{
     result::Result,
 };
 
+use super::{
+    parse_digest_key, Database, DeserializationError, EntryError, ValidationDbs, ValidationError,
+};
 use casper_node::types::Deploy;
 
-use super::{Database, DeserializationError};
-
 pub struct DeployDatabase;
 
 impl Display for DeployDatabase {
@@ -20,8 +21,23 @@ impl Database for DeployDatabase {
         "deploys"
     }
 
+    fn parse_key(bytes: &[u8]) -> Result<(), DeserializationError> {
+        parse_digest_key(bytes)
+    }
+
     fn parse_element(bytes: &[u8]) -> Result<(), DeserializationError> {
         let _: Deploy = bincode::deserialize(bytes)?;
         Ok(())
     }
+
+    fn check_entry(_dbs: &ValidationDbs, key: &[u8], value: &[u8]) -> Result<(), EntryError> {
+        let mut deploy: Deploy = bincode::deserialize(value).map_err(DeserializationError::from)?;
+        // Checks the body and deploy hashes against the header, as well as
+        // the approval signatures.
+        deploy.is_valid().map_err(ValidationError::from)?;
+        if deploy.id().as_ref() != key {
+            return Err(ValidationError::KeyMismatch(hex::encode(key), *deploy.id()).into());
+        }
+        Ok(())
+    }
 }
@@ -3,9 +3,12 @@ use std::{
     result::Result,
 };
 
+use super::{
+    parse_digest_key, Database, DeployDatabase, DeserializationError, EntryError, ValidationDbs,
+    ValidationError,
+};
 use casper_node::types::FinalizedApprovals;
-
-use super::{Database, DeserializationError};
+use casper_types::crypto;
 
 pub struct FinalizedApprovalsDatabase;
 
@@ -20,8 +23,31 @@ impl Database for FinalizedApprovalsDatabase {
         "finalized_approvals"
     }
 
+    fn parse_key(bytes: &[u8]) -> Result<(), DeserializationError> {
+        parse_digest_key(bytes)
+    }
+
     fn parse_element(bytes: &[u8]) -> Result<(), DeserializationError> {
         let _: FinalizedApprovals = bincode::deserialize(bytes)?;
         Ok(())
     }
+
+    fn check_entry(dbs: &ValidationDbs, key: &[u8], value: &[u8]) -> Result<(), EntryError> {
+        let approvals: FinalizedApprovals =
+            bincode::deserialize(value).map_err(DeserializationError::from)?;
+        // The key is the hash of the deploy the approvals were finalized for.
+        if dbs
+            .get(DeployDatabase::db_name(), key)
+            .map_err(ValidationError::from)?
+            .is_none()
+        {
+            return Err(ValidationError::MissingDeploy(hex::encode(key)).into());
+        }
+        for (idx, approval) in approvals.as_ref().iter().enumerate() {
+            crypto::verify(key, approval.signature(), approval.signer()).map_err(|crypto_err| {
+                ValidationError::InvalidApproval(idx, crypto_err.to_string())
+            })?;
+        }
+        Ok(())
+    }
 }
@@ -5,7 +5,7 @@ use std::{
 
 use casper_types::{bytesrepr::FromBytes, PublicKey};
 
-use super::{Database, DeserializationError};
+use super::{parse_digest_key, Database, DeserializationError};
 
 pub struct ProposerDatabase;
 
@@ -20,6 +20,10 @@ impl Database for ProposerDatabase {
         "proposers"
     }
 
+    fn parse_key(bytes: &[u8]) -> Result<(), DeserializationError> {
+        parse_digest_key(bytes)
+    }
+
     fn parse_element(bytes: &[u8]) -> Result<(), DeserializationError> {
         let _: PublicKey = FromBytes::from_bytes(bytes)?.0;
         Ok(())
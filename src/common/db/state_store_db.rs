@@ -20,6 +20,12 @@ impl Database for StateStoreDatabase {
         "state_store"
     }
 
+    fn parse_key(_bytes: &[u8]) -> Result<(), DeserializationError> {
+        // Keys are arbitrary byte strings chosen by the components storing
+        // their state.
+        Ok(())
+    }
+
     fn parse_element(bytes: &[u8]) -> Result<(), DeserializationError> {
         let _: u64 = FromBytes::from_bytes(bytes)?.0;
         Ok(())
@@ -1,8 +1,14 @@
+use casper_execution_engine::core::engine_state::executable_deploy_item::ExecutableDeployItem;
+use casper_node::types::{Deploy, DeployConfigurationFailure, FinalizedApprovals};
+use casper_types::{bytesrepr::Bytes, RuntimeArgs, SecretKey, TimeDiff, Timestamp};
 use lmdb::{Database as LmdbDatabase, Environment, Transaction, WriteFlags};
 use rand::{self, prelude::ThreadRng, Rng, RngCore};
 use serde::{Deserialize, Serialize};
 
-use super::{Database, DeserializationError};
+use super::{
+    parse_digest_key, Database, DeployDatabase, DeserializationError, Error,
+    FinalizedApprovalsDatabase, StartPosition, ValidationDbs, ValidationError,
+};
 use crate::test_utils::LmdbTestFixture;
 
 fn gen_bytes(rng: &mut ThreadRng) -> Vec<u8> {
@@ -118,6 +124,13 @@ impl Database for MockDb {
         "test_db"
     }
 
+    fn parse_key(bytes: &[u8]) -> Result<(), DeserializationError> {
+        if bytes.len() != 4 {
+            return Err(DeserializationError::InvalidKeyLength(bytes.len(), 4));
+        }
+        Ok(())
+    }
+
     fn parse_element(bytes: &[u8]) -> Result<(), DeserializationError> {
         bincode::deserialize::<MockStruct>(bytes)?;
         Ok(())
@@ -144,10 +157,22 @@ fn good_db_should_pass_check() {
     let fixture = LmdbTestFixture::new(vec![MockDb::db_name()], None);
     populate_db(&fixture.env, fixture.db(Some(MockDb::db_name())).unwrap());
 
-    assert!(MockDb::check_db(&fixture.env, true, 0).is_ok());
-    assert!(MockDb::check_db(&fixture.env, false, 0).is_ok());
-    assert!(MockDb::check_db(&fixture.env, true, 4).is_ok());
-    assert!(MockDb::check_db(&fixture.env, false, 4).is_ok());
+    assert!(MockDb::check_db(&fixture.env, true, StartPosition::Index(0), &mut |_| Ok(())).is_ok());
+    assert!(MockDb::check_db(
+        &fixture.env,
+        false,
+        StartPosition::Index(0),
+        &mut |_| Ok(())
+    )
+    .is_ok());
+    assert!(MockDb::check_db(&fixture.env, true, StartPosition::Index(4), &mut |_| Ok(())).is_ok());
+    assert!(MockDb::check_db(
+        &fixture.env,
+        false,
+        StartPosition::Index(4),
+        &mut |_| Ok(())
+    )
+    .is_ok());
 }
 
 #[test]
@@ -155,8 +180,363 @@ fn bad_db_should_fail_check() {
     let fixture = LmdbTestFixture::new(vec![MockDb::db_name()], None);
     populate_faulty_db(&fixture.env, fixture.db(Some(MockDb::db_name())).unwrap());
 
-    assert!(MockDb::check_db(&fixture.env, true, 0).is_err());
-    assert!(MockDb::check_db(&fixture.env, false, 0).is_err());
-    assert!(MockDb::check_db(&fixture.env, true, 4).is_err());
-    assert!(MockDb::check_db(&fixture.env, false, 4).is_err());
+    assert!(
+        MockDb::check_db(&fixture.env, true, StartPosition::Index(0), &mut |_| Ok(())).is_err()
+    );
+    assert!(MockDb::check_db(
+        &fixture.env,
+        false,
+        StartPosition::Index(0),
+        &mut |_| Ok(())
+    )
+    .is_err());
+    assert!(
+        MockDb::check_db(&fixture.env, true, StartPosition::Index(4), &mut |_| Ok(())).is_err()
+    );
+    assert!(MockDb::check_db(
+        &fixture.env,
+        false,
+        StartPosition::Index(4),
+        &mut |_| Ok(())
+    )
+    .is_err());
+}
+
+#[test]
+fn check_should_resume_after_key() {
+    let fixture = LmdbTestFixture::new(vec![MockDb::db_name()], None);
+    let db = fixture.db(Some(MockDb::db_name())).unwrap();
+    let mut rng = rand::thread_rng();
+    // Big endian keys so that the faulty entries come first in key order.
+    let mut rw_tx = fixture.env.begin_rw_txn().unwrap();
+    for i in 0u32..20 {
+        let bytes = if i < 10 {
+            gen_faulty_bytes(&mut rng)
+        } else {
+            gen_bytes(&mut rng)
+        };
+        rw_tx
+            .put(*db, &i.to_be_bytes(), &bytes, WriteFlags::empty())
+            .unwrap();
+    }
+    rw_tx.commit().unwrap();
+
+    let mut checkpoints: Vec<Vec<u8>> = vec![];
+    assert!(MockDb::check_db(
+        &fixture.env,
+        true,
+        StartPosition::AfterKey(&9u32.to_be_bytes()),
+        &mut |key| {
+            checkpoints.push(key.to_vec());
+            Ok(())
+        }
+    )
+    .is_ok());
+    // The last checked key is always reported.
+    assert_eq!(checkpoints, vec![19u32.to_be_bytes().to_vec()]);
+
+    // Resuming from a key which is not in the database should continue with
+    // the next greater key.
+    assert!(MockDb::check_db(
+        &fixture.env,
+        true,
+        StartPosition::AfterKey(&[0, 0, 0, 9, 0]),
+        &mut |_| Ok(())
+    )
+    .is_ok());
+    assert!(MockDb::check_db(
+        &fixture.env,
+        true,
+        StartPosition::AfterKey(&8u32.to_be_bytes()),
+        &mut |_| Ok(())
+    )
+    .is_err());
+    // Resuming after the last key leaves nothing to check.
+    assert!(MockDb::check_db(
+        &fixture.env,
+        true,
+        StartPosition::AfterKey(&[0xff; 4]),
+        &mut |_| Ok(())
+    )
+    .is_ok());
+}
+
+#[test]
+fn checkpoint_should_not_pass_failed_entries() {
+    let fixture = LmdbTestFixture::new(vec![MockDb::db_name()], None);
+    let db = fixture.db(Some(MockDb::db_name())).unwrap();
+    let mut rng = rand::thread_rng();
+    let mut rw_tx = fixture.env.begin_rw_txn().unwrap();
+    for i in 0u32..20 {
+        let bytes = if i == 5 || i == 12 {
+            gen_faulty_bytes(&mut rng)
+        } else {
+            gen_bytes(&mut rng)
+        };
+        rw_tx
+            .put(*db, &i.to_be_bytes(), &bytes, WriteFlags::empty())
+            .unwrap();
+    }
+    rw_tx.commit().unwrap();
+
+    let mut checkpoints: Vec<Vec<u8>> = vec![];
+    match MockDb::check_db(&fixture.env, false, StartPosition::Index(0), &mut |key| {
+        checkpoints.push(key.to_vec());
+        Ok(())
+    }) {
+        Err(Error::Accumulated(errors)) => assert_eq!(errors.len(), 2),
+        Err(err) => panic!("Unexpected error: {err}"),
+        Ok(_) => panic!("Unexpected successful check"),
+    }
+    // The checkpoint stays on the last entry before the first failure, so
+    // resuming reports both failures again.
+    assert_eq!(checkpoints, vec![4u32.to_be_bytes().to_vec()]);
+    assert!(matches!(
+        MockDb::check_db(
+            &fixture.env,
+            false,
+            StartPosition::AfterKey(&checkpoints[0]),
+            &mut |_| Ok(())
+        ),
+        Err(Error::Accumulated(errors)) if errors.len() == 2
+    ));
+}
+
+#[test]
+fn bad_keys_should_fail_check() {
+    let fixture = LmdbTestFixture::new(vec![MockDb::db_name()], None);
+    let db = fixture.db(Some(MockDb::db_name())).unwrap();
+    populate_db(&fixture.env, db);
+    let mut rng = rand::thread_rng();
+    let mut rw_tx = fixture.env.begin_rw_txn().unwrap();
+    // A truncated key with a valid value and a garbage key with an invalid one.
+    rw_tx
+        .put(*db, &[0u8; 3], &gen_bytes(&mut rng), WriteFlags::empty())
+        .unwrap();
+    rw_tx
+        .put(
+            *db,
+            &[0xffu8; 7],
+            &gen_faulty_bytes(&mut rng),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+    rw_tx.commit().unwrap();
+
+    assert!(matches!(
+        MockDb::check_db(&fixture.env, true, StartPosition::Index(0), &mut |_| Ok(())),
+        Err(Error::KeyParsing(
+            0,
+            DeserializationError::InvalidKeyLength(3, 4)
+        ))
+    ));
+    match MockDb::check_db(
+        &fixture.env,
+        false,
+        StartPosition::Index(0),
+        &mut |_| Ok(()),
+    ) {
+        Err(Error::Accumulated(errors)) => {
+            assert_eq!(errors.len(), 3);
+            assert!(matches!(errors[0], Error::KeyParsing(0, _)));
+            assert!(matches!(errors[1], Error::KeyParsing(_, _)));
+            assert!(matches!(errors[2], Error::Parsing(_, _)));
+        }
+        other => panic!("unexpected check result {other:?}"),
+    }
+}
+
+#[test]
+fn digest_keys_should_be_validated() {
+    assert!(parse_digest_key(&[0u8; 32]).is_ok());
+    assert!(matches!(
+        parse_digest_key(&[0u8; 31]),
+        Err(DeserializationError::InvalidKeyLength(31, 32))
+    ));
+    assert!(matches!(
+        parse_digest_key(&[]),
+        Err(DeserializationError::InvalidKeyLength(0, 32))
+    ));
+}
+
+fn mock_deploy(key_idx: usize, chain_name: &str) -> Deploy {
+    let secret_key = SecretKey::ed25519_from_bytes([key_idx as u8 + 1; 32]).unwrap();
+    let module = ExecutableDeployItem::ModuleBytes {
+        module_bytes: Bytes::new(),
+        args: RuntimeArgs::new(),
+    };
+    Deploy::new(
+        Timestamp::now(),
+        TimeDiff::from_seconds(60),
+        1,
+        vec![],
+        chain_name.to_string(),
+        module.clone(),
+        module,
+        &secret_key,
+        None,
+    )
+}
+
+#[test]
+fn deploys_should_be_validated() {
+    let fixture = LmdbTestFixture::new(vec![DeployDatabase::db_name()], None);
+    let db = fixture.db(Some(DeployDatabase::db_name())).unwrap();
+    let check = || {
+        DeployDatabase::check_db(
+            &fixture.env,
+            false,
+            StartPosition::Index(0),
+            &mut |_| Ok(()),
+        )
+    };
+
+    let valid_deploy = mock_deploy(0, "test");
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    txn.put(
+        *db,
+        valid_deploy.id(),
+        &bincode::serialize(&valid_deploy).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.commit().unwrap();
+    assert!(check().is_ok());
+
+    // A valid deploy stored under the wrong key.
+    let misplaced_deploy = mock_deploy(1, "test");
+    // A deploy whose header was tampered with after signing.
+    let mut tampered_json = serde_json::to_value(mock_deploy(2, "test")).unwrap();
+    tampered_json["header"]["chain_name"] = "tampered".into();
+    let tampered_deploy: Deploy = serde_json::from_value(tampered_json).unwrap();
+
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    txn.put(
+        *db,
+        &[0u8; 32],
+        &bincode::serialize(&misplaced_deploy).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.put(
+        *db,
+        tampered_deploy.id(),
+        &bincode::serialize(&tampered_deploy).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.commit().unwrap();
+
+    match check() {
+        Err(Error::Accumulated(errors)) => {
+            assert_eq!(errors.len(), 2);
+            assert!(errors.iter().any(|error| matches!(
+                error,
+                Error::Validation(_, ValidationError::KeyMismatch(_, deploy_hash))
+                    if deploy_hash == misplaced_deploy.id()
+            )));
+            assert!(errors.iter().any(|error| matches!(
+                error,
+                Error::Validation(_, ValidationError::InvalidDeploy(failure))
+                    if **failure == DeployConfigurationFailure::InvalidDeployHash
+            )));
+        }
+        other => panic!("unexpected check result {other:?}"),
+    }
+}
+
+#[test]
+fn finalized_approvals_should_be_validated() {
+    let fixture = LmdbTestFixture::new(
+        vec![
+            DeployDatabase::db_name(),
+            FinalizedApprovalsDatabase::db_name(),
+        ],
+        None,
+    );
+    let deploy_db = fixture.db(Some(DeployDatabase::db_name())).unwrap();
+    let approvals_db = fixture
+        .db(Some(FinalizedApprovalsDatabase::db_name()))
+        .unwrap();
+    let check = || {
+        FinalizedApprovalsDatabase::check_db(
+            &fixture.env,
+            false,
+            StartPosition::Index(0),
+            &mut |_| Ok(()),
+        )
+    };
+
+    let deploys: Vec<Deploy> = (0..3).map(|idx| mock_deploy(idx, "test")).collect();
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    for deploy in deploys.iter().take(2) {
+        txn.put(
+            *deploy_db,
+            deploy.id(),
+            &bincode::serialize(deploy).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+    }
+    let approvals = FinalizedApprovals::new(deploys[0].approvals().clone());
+    txn.put(
+        *approvals_db,
+        deploys[0].id(),
+        &bincode::serialize(&approvals).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.commit().unwrap();
+    assert!(check().is_ok());
+
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    // Approvals of the first deploy stored for the second one.
+    txn.put(
+        *approvals_db,
+        deploys[1].id(),
+        &bincode::serialize(&approvals).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    // Approvals for a deploy missing from the deploys database.
+    let missing_approvals = FinalizedApprovals::new(deploys[2].approvals().clone());
+    txn.put(
+        *approvals_db,
+        deploys[2].id(),
+        &bincode::serialize(&missing_approvals).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.commit().unwrap();
+
+    match check() {
+        Err(Error::Accumulated(errors)) => {
+            assert_eq!(errors.len(), 2);
+            assert!(errors.iter().any(|error| matches!(
+                error,
+                Error::Validation(_, ValidationError::InvalidApproval(0, _))
+            )));
+            assert!(errors.iter().any(|error| matches!(
+                error,
+                Error::Validation(_, ValidationError::MissingDeploy(_))
+            )));
+        }
+        other => panic!("unexpected check result {other:?}"),
+    }
+}
+
+#[test]
+fn validation_dbs_should_read_other_dbs() {
+    let fixture = LmdbTestFixture::new(vec!["a", "b"], None);
+    let db = fixture.db(Some("b")).unwrap();
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    txn.put(*db, &[1u8], &[2u8], WriteFlags::empty()).unwrap();
+    txn.commit().unwrap();
+
+    let txn = fixture.env.begin_ro_txn().unwrap();
+    let dbs = ValidationDbs::new(&txn);
+    assert_eq!(dbs.get("b", &[1u8]).unwrap(), Some([2u8].as_slice()));
+    assert_eq!(dbs.get("b", &[2u8]).unwrap(), None);
+    assert_eq!(dbs.get("a", &[1u8]).unwrap(), None);
+    assert!(dbs.get("c", &[1u8]).is_err());
 }
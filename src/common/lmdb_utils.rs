@@ -1,7 +1,15 @@
-use std::result::Result;
+use std::{
+    fs::OpenOptions,
+    io::{self, ErrorKind},
+    mem,
+    os::unix::io::AsRawFd,
+    path::Path,
+    result::Result,
+};
 
-use lmdb::{Database, Error, Transaction};
-use lmdb_sys::{mdb_stat, MDB_stat};
+use lmdb::{Cursor, Database, Environment, Error, Transaction};
+use lmdb_sys::{mdb_stat, MDB_stat, MDB_NEXT};
+use log::warn;
 
 /// Retrieves the number of entries in a database.
 pub fn entry_count<T: Transaction>(txn: &'_ T, database: Database) -> Result<usize, Error> {
@@ -21,13 +29,95 @@ pub fn entry_count<T: Transaction>(txn: &'_ T, database: Database) -> Result<usi
     }
 }
 
+/// Lists the names of all the named databases in an environment by
+/// iterating the keys of its main database.
+pub fn db_names(env: &Environment) -> Result<Vec<String>, Error> {
+    let main_db = env.open_db(None)?;
+    let mut candidates = vec![];
+    {
+        let txn = env.begin_ro_txn()?;
+        {
+            let mut cursor = txn.open_ro_cursor(main_db)?;
+            for (raw_key, _) in cursor.iter() {
+                match std::str::from_utf8(raw_key) {
+                    Ok(name) => candidates.push(name.to_string()),
+                    Err(_) => warn!(
+                        "Skipping key {} of the main database, not a database name",
+                        hex::encode(raw_key)
+                    ),
+                }
+            }
+        }
+        txn.commit()?;
+    }
+    // Plain entries stored in the main database can't be opened as
+    // databases.
+    let mut names = vec![];
+    for name in candidates {
+        match env.open_db(Some(&name)) {
+            Ok(_) => names.push(name),
+            Err(Error::Incompatible) => {
+                warn!("Skipping entry {name} of the main database, not a database")
+            }
+            Err(lmdb_err) => return Err(lmdb_err),
+        }
+    }
+    Ok(names)
+}
+
+/// Reads every entry of a database without interpreting it, returning the
+/// number of entries and their total size in bytes.
+///
+/// Unlike iterating the cursor, this surfaces the errors encountered by
+/// lmdb while reading the pages of the database.
+pub fn scan_db<T: Transaction>(txn: &'_ T, database: Database) -> Result<(usize, usize), Error> {
+    let cursor = txn.open_ro_cursor(database)?;
+    let mut entry_count = 0;
+    let mut byte_count = 0;
+    loop {
+        match cursor.get(None, None, MDB_NEXT) {
+            Ok((maybe_key, value)) => {
+                entry_count += 1;
+                byte_count += maybe_key.map(<[u8]>::len).unwrap_or_default() + value.len();
+            }
+            Err(Error::NotFound) => break,
+            Err(lmdb_err) => return Err(lmdb_err),
+        }
+    }
+    Ok((entry_count, byte_count))
+}
+
+/// Returns whether another process has the LMDB environment with the lock
+/// file at `lock_path` open.
+///
+/// Every process with the environment open holds a shared lock on the first
+/// byte of the lock file, which is only queried here. Closing the file drops
+/// the locks this process holds on it, so this must be called before the
+/// environment is opened.
+pub fn is_open_elsewhere<P: AsRef<Path>>(lock_path: P) -> io::Result<bool> {
+    let lock_file = match OpenOptions::new().read(true).write(true).open(lock_path) {
+        Ok(lock_file) => lock_file,
+        Err(io_err) if io_err.kind() == ErrorKind::NotFound => return Ok(false),
+        Err(io_err) => return Err(io_err),
+    };
+    let mut lock: libc::flock = unsafe { mem::zeroed() };
+    lock.l_type = libc::F_WRLCK as libc::c_short;
+    lock.l_whence = libc::SEEK_SET as libc::c_short;
+    lock.l_start = 0;
+    lock.l_len = 1;
+    if unsafe { libc::fcntl(lock_file.as_raw_fd(), libc::F_GETLK, &mut lock) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(lock.l_type != libc::F_UNLCK as libc::c_short)
+}
+
 #[cfg(test)]
 mod tests {
     use lmdb::{Transaction, WriteFlags};
 
     use crate::test_utils::LmdbTestFixture;
 
-    use super::entry_count;
+    use super::{db_names, entry_count, is_open_elsewhere, scan_db};
 
     #[test]
     fn db_entry_count() {
@@ -87,4 +177,41 @@ mod tests {
             txn.commit().unwrap();
         };
     }
+
+    #[test]
+    fn list_and_scan_dbs() {
+        let fixture = LmdbTestFixture::new(vec!["first", "second"], None);
+        let env = &fixture.env;
+        assert_eq!(
+            db_names(env).unwrap(),
+            vec!["first".to_string(), "second".to_string()]
+        );
+
+        let db = fixture.db(Some("second")).unwrap();
+        {
+            let txn = env.begin_ro_txn().unwrap();
+            assert_eq!(scan_db(&txn, *db).unwrap(), (0, 0));
+            txn.commit().unwrap();
+        }
+        {
+            let mut txn = env.begin_rw_txn().unwrap();
+            txn.put(*db, &[0u8, 1], &[2u8, 3, 4], WriteFlags::empty())
+                .unwrap();
+            txn.put(*db, &[5u8], &[], WriteFlags::empty()).unwrap();
+            txn.commit().unwrap();
+        }
+        let txn = env.begin_ro_txn().unwrap();
+        assert_eq!(scan_db(&txn, *db).unwrap(), (2, 6));
+        txn.commit().unwrap();
+    }
+
+    #[test]
+    fn open_elsewhere_should_ignore_this_process() {
+        let fixture = LmdbTestFixture::new(vec![], None);
+        let mut lock_path = fixture.file_path.clone().into_os_string();
+        lock_path.push("-lock");
+        // Only this process has the environment open.
+        assert!(!is_open_elsewhere(&lock_path).unwrap());
+        assert!(!is_open_elsewhere(fixture.tmp_dir.path().join("missing-lock")).unwrap());
+    }
 }
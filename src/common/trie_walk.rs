@@ -0,0 +1,104 @@
+use std::result::Result;
+
+use lmdb::{Database, Error as LmdbError, RoTransaction};
+use thiserror::Error;
+
+use casper_execution_engine::{
+    core::engine_state::EngineState,
+    storage::{
+        global_state::lmdb::LmdbGlobalState,
+        transaction_source::{Readable, Transaction, TransactionSource},
+        trie::Trie,
+    },
+};
+use casper_hashing::Digest;
+use casper_types::{
+    bytesrepr::{self, Error as BytesreprError},
+    Key, StoredValue,
+};
+
+/// Errors encountered when walking the tries under a state root.
+#[derive(Debug, Error)]
+pub enum Error {
+    /// Error reading the trie store.
+    #[error("Error reading the trie store: {0}")]
+    Lmdb(#[from] LmdbError),
+    /// A trie under the state root isn't in the trie store.
+    #[error("Missing trie {0}")]
+    MissingTrie(Digest),
+    /// Parsing error on the trie with the given key.
+    #[error("Error parsing trie {0}: {1}")]
+    Parsing(Digest, BytesreprError),
+}
+
+/// Reads the trie with the given key from the trie store `db`.
+pub fn read_trie(
+    txn: &RoTransaction,
+    db: Database,
+    trie_key: Digest,
+) -> Result<Trie<Key, StoredValue>, Error> {
+    let value_bytes = txn
+        .read(db, trie_key.as_ref())?
+        .ok_or(Error::MissingTrie(trie_key))?;
+    bytesrepr::deserialize(value_bytes.to_vec())
+        .map_err(|bytesrepr_err| Error::Parsing(trie_key, bytesrepr_err))
+}
+
+/// Walks every trie under `state_root_hash` depth first, calling `visit`
+/// with each trie and its depth, i.e. the number of tries between it and the
+/// state root.
+///
+/// A trie is only reachable through a single path under a state root, so
+/// the visited tries aren't remembered.
+pub fn walk_tries<E, F>(
+    engine_state: &EngineState<LmdbGlobalState>,
+    state_root_hash: Digest,
+    visit: F,
+) -> Result<(), E>
+where
+    E: From<Error>,
+    F: FnMut(&Trie<Key, StoredValue>, usize) -> Result<(), E>,
+{
+    let txn = engine_state
+        .get_state()
+        .environment()
+        .create_read_txn()
+        .map_err(Error::from)?;
+    let db = engine_state.get_state().trie_store().get_db();
+    walk_tries_in_txn(&txn, db, state_root_hash, visit)?;
+    txn.commit().map_err(Error::from)?;
+    Ok(())
+}
+
+/// Same as [`walk_tries`], reading the trie store `db` in an existing
+/// transaction.
+pub fn walk_tries_in_txn<E, F>(
+    txn: &RoTransaction,
+    db: Database,
+    state_root_hash: Digest,
+    mut visit: F,
+) -> Result<(), E>
+where
+    E: From<Error>,
+    F: FnMut(&Trie<Key, StoredValue>, usize) -> Result<(), E>,
+{
+    let mut pending_tries = vec![(state_root_hash, 0usize)];
+    while let Some((trie_key, depth)) = pending_tries.pop() {
+        let trie = read_trie(txn, db, trie_key)?;
+        match &trie {
+            Trie::Leaf { .. } => {}
+            // Push the children in reverse so they are visited in key order.
+            Trie::Node { pointer_block } => pending_tries.extend(
+                pointer_block
+                    .as_indexed_pointers()
+                    .collect::<Vec<_>>()
+                    .into_iter()
+                    .rev()
+                    .map(|(_index, pointer)| (*pointer.hash(), depth + 1)),
+            ),
+            Trie::Extension { pointer, .. } => pending_tries.push((*pointer.hash(), depth + 1)),
+        }
+        visit(&trie, depth)?;
+    }
+    Ok(())
+}
@@ -10,21 +10,35 @@ use clap::{crate_description, crate_name, crate_version, Arg, Command};
 use log::error;
 
 use subcommands::{
-    archive, check, execution_results_summary, extract_slice, latest_block_summary,
-    purge_signatures, remove_block, trie_compact, unsparse, Error,
+    archive, auction_state, balance, check, dump_state, execution_results_summary, export_contract,
+    extract_slice, latest_block_summary, list_dbs, purge_signatures, query_state, remove_block,
+    repair, state_diff, state_root_coverage, supply_report, trie_compact, trie_gc, trie_stats,
+    unsparse, Error,
 };
 
 const LOGGING: &str = "logging";
 
 enum DisplayOrder {
     Archive,
+    AuctionState,
+    Balance,
     Check,
+    DumpState,
     ExecutionResults,
+    ExportContract,
     ExtractSlice,
     LatestBlock,
+    ListDbs,
     PurgeSignatures,
+    QueryState,
     RemoveBlock,
+    Repair,
+    StateDiff,
+    StateRootCoverage,
+    SupplyReport,
     TrieCompact,
+    TrieGc,
+    TrieStats,
     Unsparse,
 }
 
@@ -43,19 +57,35 @@ fn cli() -> Command<'static> {
         .about(crate_description!())
         .arg_required_else_help(true)
         .subcommand(archive::command(DisplayOrder::Archive as usize))
+        .subcommand(auction_state::command(DisplayOrder::AuctionState as usize))
+        .subcommand(balance::command(DisplayOrder::Balance as usize))
         .subcommand(check::command(DisplayOrder::Check as usize))
+        .subcommand(dump_state::command(DisplayOrder::DumpState as usize))
         .subcommand(execution_results_summary::command(
             DisplayOrder::ExecutionResults as usize,
         ))
+        .subcommand(export_contract::command(
+            DisplayOrder::ExportContract as usize,
+        ))
         .subcommand(extract_slice::command(DisplayOrder::ExtractSlice as usize))
         .subcommand(latest_block_summary::command(
             DisplayOrder::LatestBlock as usize,
         ))
+        .subcommand(list_dbs::command(DisplayOrder::ListDbs as usize))
         .subcommand(purge_signatures::command(
             DisplayOrder::PurgeSignatures as usize,
         ))
+        .subcommand(query_state::command(DisplayOrder::QueryState as usize))
         .subcommand(remove_block::command(DisplayOrder::RemoveBlock as usize))
+        .subcommand(repair::command(DisplayOrder::Repair as usize))
+        .subcommand(state_diff::command(DisplayOrder::StateDiff as usize))
+        .subcommand(state_root_coverage::command(
+            DisplayOrder::StateRootCoverage as usize,
+        ))
+        .subcommand(supply_report::command(DisplayOrder::SupplyReport as usize))
         .subcommand(trie_compact::command(DisplayOrder::TrieCompact as usize))
+        .subcommand(trie_gc::command(DisplayOrder::TrieGc as usize))
+        .subcommand(trie_stats::command(DisplayOrder::TrieStats as usize))
         .subcommand(unsparse::command(DisplayOrder::Unsparse as usize))
         .arg(
             Arg::new(LOGGING)
@@ -94,17 +124,29 @@ fn main() {
 
     let result: Result<(), Error> = match subcommand_name {
         archive::COMMAND_NAME => archive::run(matches).map_err(Error::from),
+        auction_state::COMMAND_NAME => auction_state::run(matches).map_err(Error::from),
+        balance::COMMAND_NAME => balance::run(matches).map_err(Error::from),
         check::COMMAND_NAME => check::run(matches).map_err(Error::from),
+        dump_state::COMMAND_NAME => dump_state::run(matches).map_err(Error::from),
         execution_results_summary::COMMAND_NAME => {
             execution_results_summary::run(matches).map_err(Error::from)
         }
+        export_contract::COMMAND_NAME => export_contract::run(matches).map_err(Error::from),
         extract_slice::COMMAND_NAME => extract_slice::run(matches).map_err(Error::from),
         latest_block_summary::COMMAND_NAME => {
             latest_block_summary::run(matches).map_err(Error::from)
         }
+        list_dbs::COMMAND_NAME => list_dbs::run(matches).map_err(Error::from),
         purge_signatures::COMMAND_NAME => purge_signatures::run(matches).map_err(Error::from),
+        query_state::COMMAND_NAME => query_state::run(matches).map_err(Error::from),
         remove_block::COMMAND_NAME => remove_block::run(matches).map_err(Error::from),
+        repair::COMMAND_NAME => repair::run(matches).map_err(Error::from),
+        state_diff::COMMAND_NAME => state_diff::run(matches).map_err(Error::from),
+        state_root_coverage::COMMAND_NAME => state_root_coverage::run(matches).map_err(Error::from),
+        supply_report::COMMAND_NAME => supply_report::run(matches).map_err(Error::from),
         trie_compact::COMMAND_NAME => trie_compact::run(matches).map_err(Error::from),
+        trie_gc::COMMAND_NAME => trie_gc::run(matches).map_err(Error::from),
+        trie_stats::COMMAND_NAME => trie_stats::run(matches).map_err(Error::from),
         unsparse::COMMAND_NAME => unsparse::run(matches).map_err(Error::from),
         _ => unreachable!("{} should be handled above", subcommand_name),
     };
@@ -1,23 +1,47 @@
 pub mod archive;
+pub mod auction_state;
+pub mod balance;
 pub mod check;
+pub mod dump_state;
 pub mod execution_results_summary;
+pub mod export_contract;
 pub mod extract_slice;
 pub mod latest_block_summary;
+pub mod list_dbs;
 pub mod purge_signatures;
+pub mod query_state;
 pub mod remove_block;
+pub mod repair;
+pub mod state_diff;
+pub mod state_root_coverage;
+pub mod supply_report;
 pub mod trie_compact;
+pub mod trie_gc;
+pub mod trie_stats;
 pub mod unsparse;
 
 use thiserror::Error as ThisError;
 
 use archive::{CreateError, UnpackError};
+use auction_state::Error as AuctionStateError;
+use balance::Error as BalanceError;
 use check::Error as CheckError;
+use dump_state::Error as DumpStateError;
 use execution_results_summary::Error as ExecutionResultsSummaryError;
+use export_contract::Error as ExportContractError;
 use extract_slice::Error as ExtractSliceError;
 use latest_block_summary::Error as LatestBlockSummaryError;
+use list_dbs::Error as ListDbsError;
 use purge_signatures::Error as PurgeSignaturesError;
+use query_state::Error as QueryStateError;
 use remove_block::Error as RemoveBlockError;
+use repair::Error as RepairError;
+use state_diff::Error as StateDiffError;
+use state_root_coverage::Error as StateRootCoverageError;
+use supply_report::Error as SupplyReportError;
 use trie_compact::Error as TrieCompactError;
+use trie_gc::Error as TrieGcError;
+use trie_stats::Error as TrieStatsError;
 use unsparse::Error as UnsparseError;
 
 #[derive(ThisError, Debug)]
@@ -26,20 +50,44 @@ pub enum Error {
     ArchiveCreate(#[from] CreateError),
     #[error("Archive unpack failed: {0}")]
     ArchiveUnpack(#[from] UnpackError),
+    #[error("Auction state command failed: {0}")]
+    AuctionState(#[from] AuctionStateError),
+    #[error("Balance command failed: {0}")]
+    Balance(#[from] BalanceError),
     #[error("Check command failed: {0}")]
     Check(#[from] CheckError),
+    #[error("Dump state command failed: {0}")]
+    DumpState(#[from] DumpStateError),
     #[error("Execution results summary command failed: {0}")]
     ExecutionResultsSummary(#[from] ExecutionResultsSummaryError),
+    #[error("Export contract command failed: {0}")]
+    ExportContract(#[from] ExportContractError),
     #[error("Extract slice command failed: {0}")]
     ExtractSlice(#[from] ExtractSliceError),
     #[error("Latest block summary command failed: {0}")]
     LatestBlockSummary(#[from] LatestBlockSummaryError),
+    #[error("List databases command failed: {0}")]
+    ListDbs(#[from] ListDbsError),
     #[error("Purge signatures failed: {0}")]
     PurgeSignatures(#[from] PurgeSignaturesError),
+    #[error("Query state command failed: {0}")]
+    QueryState(#[from] QueryStateError),
     #[error("Remove block failed: {0}")]
     RemoveBlock(#[from] RemoveBlockError),
+    #[error("Repair failed: {0}")]
+    Repair(#[from] RepairError),
+    #[error("State diff command failed: {0}")]
+    StateDiff(#[from] StateDiffError),
+    #[error("State root coverage command failed: {0}")]
+    StateRootCoverage(#[from] StateRootCoverageError),
+    #[error("Supply report command failed: {0}")]
+    SupplyReport(#[from] SupplyReportError),
     #[error("Trie compact failed: {0}")]
     TrieCompact(#[from] TrieCompactError),
+    #[error("Trie garbage collection failed: {0}")]
+    TrieGc(#[from] TrieGcError),
+    #[error("Trie stats command failed: {0}")]
+    TrieStats(#[from] TrieStatsError),
     #[error("Unsparse failed: {0}")]
     Unsparse(#[from] UnsparseError),
 }
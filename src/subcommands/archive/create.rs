@@ -5,7 +5,6 @@ mod tests;
 use std::io::Error as IoError;
 
 use clap::{Arg, ArgMatches, Command};
-use log::error;
 use thiserror::Error as ThisError;
 
 use super::zstd_utils::Error as ZstdError;
@@ -10,7 +10,6 @@ use std::{
 };
 
 use clap::{Arg, ArgGroup, ArgMatches, Command};
-use log::error;
 use reqwest::Error as ReqwestError;
 use thiserror::Error as ThisError;
 
@@ -30,12 +30,7 @@ impl HttpStream {
                         info!("Download size: {} bytes.", len);
                         len.try_into().ok()
                     });
-                    Ok((
-                        response.bytes_stream().map_err(|reqwest_err| {
-                            io::Error::new(io::ErrorKind::Other, reqwest_err)
-                        }),
-                        maybe_len,
-                    ))
+                    Ok((response.bytes_stream().map_err(io::Error::other), maybe_len))
                 }
                 Err(request_err) => Err(Error::Request(request_err)),
             }
@@ -0,0 +1,152 @@
+mod read_auction;
+#[cfg(test)]
+mod tests;
+mod weights_check;
+
+use std::{io::Error as IoError, path::Path};
+
+use anyhow::Error as AnyError;
+use bincode::Error as BincodeError;
+use clap::{Arg, ArgMatches, Command};
+use lmdb::Error as LmdbError;
+use serde_json::Error as JsonSerializationError;
+use thiserror::Error as ThisError;
+
+use casper_execution_engine::core::engine_state::{
+    Error as EngineStateError, GetEraValidatorsError,
+};
+use casper_hashing::Digest;
+use casper_node::types::BlockHash;
+
+use super::{
+    dump_state::{self, StateRootError},
+    purge_signatures::Error as PurgeSignaturesError,
+    trie_compact::DEFAULT_MAX_DB_SIZE,
+};
+
+pub const COMMAND_NAME: &str = "auction-state";
+const MAX_DB_SIZE: &str = "max-db-size";
+const OUTPUT: &str = "output";
+const OVERWRITE: &str = "overwrite";
+const TRIE_STORE_PATH: &str = "trie";
+
+/// Errors encountered when reading the auction state.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Database operation error on the storage database.
+    #[error("Error operating the database: {0}")]
+    Database(#[from] LmdbError),
+    /// Error reading the era validators from the auction contract.
+    #[error("Error reading the era validators: {0}")]
+    EraValidators(#[from] GetEraValidatorsError),
+    /// Parsing error on entry in the block header database.
+    #[error("Error parsing block header with hash {0}: {1}")]
+    HeaderParsing(BlockHash, BincodeError),
+    /// Error indexing the switch blocks in the storage database.
+    #[error("Error indexing the switch blocks: {0}")]
+    Indices(#[from] PurgeSignaturesError),
+    #[error("Error serializing output: {0}")]
+    JsonSerialize(#[from] JsonSerializationError),
+    /// Error opening the trie store.
+    #[error("Error opening the trie store: {0}")]
+    OpenTrie(AnyError),
+    #[error("Error writing output: {0}")]
+    Output(#[from] IoError),
+    /// Error reading the bids from the global state.
+    #[error("Error reading the bids: {0}")]
+    Query(#[from] EngineStateError),
+    /// The state root isn't in the trie store.
+    #[error("State root {0} not found in the trie store")]
+    RootNotFound(Digest),
+    /// Error finding the state root to read the auction state at.
+    #[error("Error finding the state root: {0}")]
+    StateRoot(#[from] StateRootError),
+}
+
+enum DisplayOrder {
+    TriePath,
+    StateRoot,
+    MaxDbSize,
+    Output,
+    Overwrite,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Outputs the era validators and bids of the auction contract under a state root in \
+            JSON format. If a storage path is given, the era validators are checked against \
+            the validator weights of the switch blocks in storage.",
+        )
+        .arg(
+            Arg::new(TRIE_STORE_PATH)
+                .display_order(DisplayOrder::TriePath as usize)
+                .required(true)
+                .short('t')
+                .long(TRIE_STORE_PATH)
+                .takes_value(true)
+                .value_name("TRIE_STORE_DIR_PATH")
+                .help("Path of the directory with the `data.lmdb` file."),
+        )
+        .args(dump_state::state_root_args(
+            DisplayOrder::StateRoot as usize,
+        ))
+        .arg(
+            Arg::new(MAX_DB_SIZE)
+                .display_order(DisplayOrder::MaxDbSize as usize)
+                .required(false)
+                .short('m')
+                .long(MAX_DB_SIZE)
+                .takes_value(true)
+                .default_value(DEFAULT_MAX_DB_SIZE)
+                .value_name("MAX_DB_SIZE")
+                .help("Maximum size the DB files are allowed to be, in bytes."),
+        )
+        .arg(
+            Arg::new(OUTPUT)
+                .display_order(DisplayOrder::Output as usize)
+                .short('o')
+                .long(OUTPUT)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help(
+                    "Path to where the program will output the auction state. \
+                    If unspecified, defaults to standard output.",
+                ),
+        )
+        .arg(
+            Arg::new(OVERWRITE)
+                .display_order(DisplayOrder::Overwrite as usize)
+                .required(false)
+                .short('w')
+                .long(OVERWRITE)
+                .takes_value(false)
+                .requires(OUTPUT)
+                .help(
+                    "Overwrite an already existing output file in destination \
+                    directory.",
+                ),
+        )
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let trie_path = Path::new(matches.value_of(TRIE_STORE_PATH).unwrap());
+    let state_root_hash = dump_state::resolve_state_root(matches)?;
+    let storage_path = matches.value_of(dump_state::STORAGE_PATH).map(Path::new);
+    let max_db_size = matches
+        .value_of(MAX_DB_SIZE)
+        .unwrap()
+        .parse()
+        .expect("Value of \"--max-db-size\" must be an integer.");
+    let output = matches.value_of(OUTPUT).map(Path::new);
+    let overwrite = matches.is_present(OVERWRITE);
+    read_auction::auction_state(
+        trie_path,
+        max_db_size,
+        state_root_hash,
+        storage_path,
+        output,
+        overwrite,
+    )
+}
@@ -0,0 +1,123 @@
+use std::{
+    fs::OpenOptions,
+    io::{self, Write},
+    path::Path,
+};
+
+use serde::Serialize;
+
+use casper_execution_engine::{
+    core::engine_state::{
+        EngineState, Error as EngineStateError, GetBidsRequest, GetBidsResult,
+        GetEraValidatorsError, GetEraValidatorsRequest,
+    },
+    shared::newtypes::CorrelationId,
+    storage::global_state::lmdb::LmdbGlobalState,
+};
+use casper_hashing::Digest;
+use casper_types::{
+    system::auction::{Bids, EraValidators},
+    ProtocolVersion,
+};
+
+use crate::{
+    common::db::{self, STORAGE_FILE_NAME},
+    subcommands::trie_compact::load_execution_engine,
+};
+
+use super::{
+    weights_check::{self, EraWeightsCheck},
+    Error,
+};
+
+/// Data of the auction contract under a state root.
+#[derive(Debug, Serialize)]
+pub(crate) struct AuctionState {
+    pub(crate) state_root_hash: Digest,
+    pub(crate) era_validators: EraValidators,
+    pub(crate) bids: Bids,
+    /// Outcome of checking the era validators against the switch blocks in
+    /// storage, if a storage was given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) weights_check: Option<Vec<EraWeightsCheck>>,
+}
+
+/// Returns the era validators held in the seigniorage recipients snapshot of
+/// the auction contract in the global state at `state_root_hash`.
+fn era_validators(
+    engine_state: &EngineState<LmdbGlobalState>,
+    state_root_hash: Digest,
+) -> Result<EraValidators, Error> {
+    // The protocol version isn't used when reading the snapshot.
+    let request = GetEraValidatorsRequest::new(state_root_hash, ProtocolVersion::V1_0_0);
+    match engine_state.get_era_validators(CorrelationId::new(), None, request) {
+        Ok(era_validators) => Ok(era_validators),
+        Err(GetEraValidatorsError::RootNotFound)
+        | Err(GetEraValidatorsError::Other(EngineStateError::RootNotFound(_))) => {
+            Err(Error::RootNotFound(state_root_hash))
+        }
+        Err(err) => Err(Error::EraValidators(err)),
+    }
+}
+
+/// Returns all the bids in the global state at `state_root_hash`.
+fn bids(
+    engine_state: &EngineState<LmdbGlobalState>,
+    state_root_hash: Digest,
+) -> Result<Bids, Error> {
+    match engine_state.get_bids(CorrelationId::new(), GetBidsRequest::new(state_root_hash))? {
+        GetBidsResult::Success { bids } => Ok(bids),
+        GetBidsResult::RootNotFound => Err(Error::RootNotFound(state_root_hash)),
+    }
+}
+
+/// Reads the era validators and the bids of the auction contract in the
+/// global state at `state_root_hash`.
+pub(crate) fn read_auction_state<P: AsRef<Path>>(
+    trie_path: P,
+    max_db_size: usize,
+    state_root_hash: Digest,
+) -> Result<AuctionState, Error> {
+    let (engine_state, _env) =
+        load_execution_engine(trie_path, max_db_size, Digest::default(), true)
+            .map_err(Error::OpenTrie)?;
+    Ok(AuctionState {
+        state_root_hash,
+        era_validators: era_validators(&engine_state, state_root_hash)?,
+        bids: bids(&engine_state, state_root_hash)?,
+        weights_check: None,
+    })
+}
+
+pub fn auction_state<P1: AsRef<Path>, P2: AsRef<Path>, P3: AsRef<Path>>(
+    trie_path: P1,
+    max_db_size: usize,
+    state_root_hash: Digest,
+    storage_path: Option<P2>,
+    output: Option<P3>,
+    overwrite: bool,
+) -> Result<(), Error> {
+    // Validate the output file early so that, in case this fails
+    // we don't unnecessarily read the global state.
+    let out_writer: Box<dyn Write> = if let Some(out_path) = output {
+        let file = OpenOptions::new()
+            .create_new(!overwrite)
+            .write(true)
+            .truncate(true)
+            .open(out_path)?;
+        Box::new(file)
+    } else {
+        Box::new(io::stdout())
+    };
+
+    let mut auction_state = read_auction_state(trie_path, max_db_size, state_root_hash)?;
+    if let Some(storage_path) = storage_path {
+        let env = db::read_only_db_env(storage_path.as_ref().join(STORAGE_FILE_NAME))?;
+        auction_state.weights_check = Some(weights_check::check_era_weights(
+            &env,
+            &auction_state.era_validators,
+        )?);
+    }
+    serde_json::to_writer_pretty(out_writer, &auction_state)?;
+    Ok(())
+}
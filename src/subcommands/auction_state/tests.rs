@@ -0,0 +1,224 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use casper_node::types::BlockHash;
+use casper_types::{
+    contracts::{ContractPackageHash, EntryPoints},
+    system::{
+        auction::{
+            Bid, EraValidators, SeigniorageRecipient, SeigniorageRecipientsSnapshot,
+            SEIGNIORAGE_RECIPIENTS_SNAPSHOT_KEY,
+        },
+        AUCTION,
+    },
+    AccessRights, CLValue, Contract, ContractHash, ContractWasmHash, EraId, Key, ProtocolVersion,
+    StoredValue, URef, U512,
+};
+use lmdb::{Transaction, WriteFlags};
+
+use crate::{
+    subcommands::trie_compact::tests::{create_test_global_state, DEFAULT_MAX_DB_SIZE},
+    test_utils::{self, LmdbTestFixture, MockSwitchBlockHeader, KEYS},
+};
+
+use super::{
+    read_auction,
+    weights_check::{self, WeightsCheckStatus},
+    Error,
+};
+
+const AUCTION_HASH: ContractHash = ContractHash::new([1u8; 32]);
+
+/// Creates the global state of an auction contract with a snapshot of
+/// `snapshot` and a bid for each of `bids`.
+fn auction_stored_values(
+    snapshot: SeigniorageRecipientsSnapshot,
+    bids: &[Bid],
+) -> HashMap<Key, StoredValue> {
+    let snapshot_uref = URef::new([2u8; 32], AccessRights::READ_ADD_WRITE);
+    let mut named_keys = BTreeMap::new();
+    named_keys.insert(
+        SEIGNIORAGE_RECIPIENTS_SNAPSHOT_KEY.to_string(),
+        Key::URef(snapshot_uref),
+    );
+    let auction_contract = Contract::new(
+        ContractPackageHash::new([3u8; 32]),
+        ContractWasmHash::new([4u8; 32]),
+        named_keys,
+        EntryPoints::new(),
+        ProtocolVersion::V1_0_0,
+    );
+    let mut registry = BTreeMap::new();
+    registry.insert(AUCTION.to_string(), AUCTION_HASH);
+
+    let mut stored_values = HashMap::new();
+    stored_values.insert(
+        Key::SystemContractRegistry,
+        StoredValue::CLValue(CLValue::from_t(registry).unwrap()),
+    );
+    stored_values.insert(
+        Key::Hash(AUCTION_HASH.value()),
+        StoredValue::Contract(auction_contract),
+    );
+    // URefs are stored in the global state without their access rights.
+    stored_values.insert(
+        Key::URef(snapshot_uref).normalize(),
+        StoredValue::CLValue(CLValue::from_t(snapshot).unwrap()),
+    );
+    for bid in bids {
+        stored_values.insert(
+            Key::Bid(bid.validator_public_key().to_account_hash()),
+            StoredValue::Bid(Box::new(bid.clone())),
+        );
+    }
+    stored_values
+}
+
+fn test_snapshot() -> SeigniorageRecipientsSnapshot {
+    let mut snapshot = BTreeMap::new();
+    for era in 10..12u64 {
+        let mut recipients = BTreeMap::new();
+        recipients.insert(
+            KEYS[0].clone(),
+            SeigniorageRecipient::new(U512::from(100 * era), 10, BTreeMap::new()),
+        );
+        let mut delegator_stake = BTreeMap::new();
+        delegator_stake.insert(KEYS[2].clone(), U512::from(50));
+        recipients.insert(
+            KEYS[1].clone(),
+            SeigniorageRecipient::new(U512::from(200), 5, delegator_stake),
+        );
+        snapshot.insert(EraId::new(era), recipients);
+    }
+    snapshot
+}
+
+#[test]
+fn auction_state_should_hold_era_validators_and_bids() {
+    let bids = vec![
+        Bid::unlocked(
+            KEYS[0].clone(),
+            URef::new([5u8; 32], AccessRights::READ_ADD_WRITE),
+            U512::from(1_000),
+            10,
+        ),
+        Bid::unlocked(
+            KEYS[1].clone(),
+            URef::new([6u8; 32], AccessRights::READ_ADD_WRITE),
+            U512::from(200),
+            5,
+        ),
+    ];
+    let (trie_dir, state_root_hash) =
+        create_test_global_state(auction_stored_values(test_snapshot(), &bids));
+
+    let auction_state =
+        read_auction::read_auction_state(trie_dir.path(), *DEFAULT_MAX_DB_SIZE, state_root_hash)
+            .unwrap();
+    assert_eq!(auction_state.state_root_hash, state_root_hash);
+    assert_eq!(auction_state.era_validators.len(), 2);
+    for era in 10..12u64 {
+        let weights = auction_state.era_validators.get(&EraId::new(era)).unwrap();
+        assert_eq!(weights.get(&KEYS[0]), Some(&U512::from(100 * era)));
+        // The delegated stake is part of the validator weight.
+        assert_eq!(weights.get(&KEYS[1]), Some(&U512::from(250)));
+    }
+    assert_eq!(auction_state.bids.len(), 2);
+    for bid in bids {
+        assert_eq!(
+            auction_state.bids.get(bid.validator_public_key()),
+            Some(&bid)
+        );
+    }
+    assert!(auction_state.weights_check.is_none());
+
+    // Make sure the maps keyed by eras and public keys serialize to JSON.
+    let json = serde_json::to_value(&auction_state).unwrap();
+    assert!(json["era_validators"]["10"].is_object());
+    assert!(json.get("weights_check").is_none());
+}
+
+#[test]
+fn auction_state_should_fail_on_missing_root() {
+    let (trie_dir, _) = create_test_global_state(auction_stored_values(test_snapshot(), &[]));
+
+    let missing_root = [9u8; 32].into();
+    match read_auction::read_auction_state(trie_dir.path(), *DEFAULT_MAX_DB_SIZE, missing_root) {
+        Err(Error::RootNotFound(root)) => assert_eq!(root, missing_root),
+        Err(err) => panic!("Unexpected error: {err}"),
+        Ok(_) => panic!("Unexpected success"),
+    }
+}
+
+#[test]
+fn era_weights_should_be_checked_against_switch_blocks() {
+    let fixture = LmdbTestFixture::new(vec!["block_header"], None);
+
+    let mut era_validators = EraValidators::new();
+    for era in 10..13u64 {
+        let mut weights = BTreeMap::new();
+        weights.insert(KEYS[0].clone(), U512::from(100));
+        weights.insert(KEYS[1].clone(), U512::from(200));
+        era_validators.insert(EraId::new(era), weights);
+    }
+
+    // The switch block of era 9 holds the same weights as the auction for
+    // era 10, the one of era 10 holds different weights for era 11 and there
+    // is no switch block for era 11.
+    let mut switch_block_headers: Vec<(BlockHash, MockSwitchBlockHeader)> =
+        (0..2u8).map(test_utils::mock_switch_block_header).collect();
+    switch_block_headers[0].1.era_id = EraId::new(9);
+    switch_block_headers[0].1.height = 90;
+    switch_block_headers[0]
+        .1
+        .insert_key_weight(KEYS[0].clone(), U512::from(100));
+    switch_block_headers[0]
+        .1
+        .insert_key_weight(KEYS[1].clone(), U512::from(200));
+    switch_block_headers[1].1.era_id = EraId::new(10);
+    switch_block_headers[1].1.height = 100;
+    switch_block_headers[1]
+        .1
+        .insert_key_weight(KEYS[0].clone(), U512::from(100));
+    switch_block_headers[1]
+        .1
+        .insert_key_weight(KEYS[1].clone(), U512::from(150));
+    switch_block_headers[1]
+        .1
+        .insert_key_weight(KEYS[2].clone(), U512::from(50));
+
+    let env = &fixture.env;
+    if let Ok(mut txn) = env.begin_rw_txn() {
+        for (block_hash, block_header) in switch_block_headers.iter() {
+            txn.put(
+                *fixture.db(Some("block_header")).unwrap(),
+                block_hash,
+                &bincode::serialize(block_header).unwrap(),
+                WriteFlags::empty(),
+            )
+            .unwrap();
+        }
+        txn.commit().unwrap();
+    };
+
+    let checks = weights_check::check_era_weights(env, &era_validators).unwrap();
+    assert_eq!(checks.len(), 3);
+
+    assert_eq!(checks[0].era_id, EraId::new(10));
+    assert_eq!(checks[0].switch_block_hash, Some(switch_block_headers[0].0));
+    assert_eq!(checks[0].status, WeightsCheckStatus::Match);
+    assert!(checks[0].mismatched_validators.is_empty());
+
+    assert_eq!(checks[1].era_id, EraId::new(11));
+    assert_eq!(checks[1].switch_block_hash, Some(switch_block_headers[1].0));
+    assert_eq!(checks[1].status, WeightsCheckStatus::Mismatch);
+    assert_eq!(
+        checks[1].mismatched_validators,
+        BTreeSet::from([KEYS[1].clone(), KEYS[2].clone()])
+            .into_iter()
+            .collect::<Vec<_>>()
+    );
+
+    assert_eq!(checks[2].era_id, EraId::new(12));
+    assert_eq!(checks[2].switch_block_hash, None);
+    assert_eq!(checks[2].status, WeightsCheckStatus::MissingSwitchBlock);
+}
@@ -0,0 +1,115 @@
+use std::collections::BTreeSet;
+
+use lmdb::{Environment, Transaction};
+use log::warn;
+use serde::Serialize;
+
+use casper_node::types::{BlockHash, BlockHeader};
+use casper_types::{
+    system::auction::{EraValidators, ValidatorWeights},
+    EraId, PublicKey,
+};
+
+use crate::{
+    common::db::{BlockHeaderDatabase, Database},
+    subcommands::purge_signatures,
+};
+
+use super::Error;
+
+/// Outcome of checking the era validators of an era against its switch
+/// block.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum WeightsCheckStatus {
+    /// The switch block holds the same validator weights.
+    Match,
+    /// The switch block holds different validator weights.
+    Mismatch,
+    /// There is no switch block holding the weights of the era in storage.
+    MissingSwitchBlock,
+}
+
+/// Result of checking the era validators of an era against the
+/// `next_era_validator_weights` of the switch block of the previous era.
+#[derive(Debug, Serialize)]
+pub(crate) struct EraWeightsCheck {
+    pub(crate) era_id: EraId,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) switch_block_hash: Option<BlockHash>,
+    pub(crate) status: WeightsCheckStatus,
+    /// Validators whose weight differs between the auction contract and the
+    /// switch block.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(crate) mismatched_validators: Vec<PublicKey>,
+}
+
+/// Returns the validators with a different weight, or present in only one of
+/// the two sets of weights.
+fn mismatched_validators(
+    auction_weights: &ValidatorWeights,
+    block_weights: &ValidatorWeights,
+) -> Vec<PublicKey> {
+    auction_weights
+        .keys()
+        .chain(block_weights.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter(|public_key| auction_weights.get(public_key) != block_weights.get(public_key))
+        .cloned()
+        .collect()
+}
+
+/// Checks the weights of every era in `era_validators` against the
+/// `next_era_validator_weights` of the matching switch block in the storage
+/// database opened in `env`.
+pub(crate) fn check_era_weights(
+    env: &Environment,
+    era_validators: &EraValidators,
+) -> Result<Vec<EraWeightsCheck>, Error> {
+    let indices = purge_signatures::initialize_indices(env, &BTreeSet::new())?;
+    let txn = env.begin_ro_txn()?;
+    let header_db = unsafe { txn.open_db(Some(BlockHeaderDatabase::db_name()))? };
+
+    let mut checks = Vec::with_capacity(era_validators.len());
+    for (era_id, auction_weights) in era_validators {
+        let switch_block_hash = match indices.switch_blocks.get(era_id) {
+            Some(switch_block_hash) => *switch_block_hash,
+            None => {
+                checks.push(EraWeightsCheck {
+                    era_id: *era_id,
+                    switch_block_hash: None,
+                    status: WeightsCheckStatus::MissingSwitchBlock,
+                    mismatched_validators: vec![],
+                });
+                continue;
+            }
+        };
+        let switch_block_header: BlockHeader =
+            bincode::deserialize(txn.get(header_db, &switch_block_hash)?)
+                .map_err(|bincode_err| Error::HeaderParsing(switch_block_hash, bincode_err))?;
+        let block_weights = switch_block_header
+            .next_era_validator_weights()
+            .cloned()
+            .unwrap_or_default();
+        let mismatched_validators = mismatched_validators(auction_weights, &block_weights);
+        let status = if mismatched_validators.is_empty() {
+            WeightsCheckStatus::Match
+        } else {
+            warn!(
+                "Era validators of era {era_id} differ from the weights in switch block \
+                {switch_block_hash} for {} validators",
+                mismatched_validators.len()
+            );
+            WeightsCheckStatus::Mismatch
+        };
+        checks.push(EraWeightsCheck {
+            era_id: *era_id,
+            switch_block_hash: Some(switch_block_hash),
+            status,
+            mismatched_validators,
+        });
+    }
+    txn.commit()?;
+    Ok(checks)
+}
@@ -0,0 +1,139 @@
+mod read_balance;
+#[cfg(test)]
+mod tests;
+
+use std::{
+    io::{self, Error as IoError, Write},
+    path::Path,
+};
+
+use anyhow::Error as AnyError;
+use clap::{Arg, ArgMatches, Command};
+use thiserror::Error as ThisError;
+
+use casper_types::{account::AccountHash, AsymmetricType, CLValueError, PublicKey, URef};
+
+use super::{
+    dump_state::{self, StateRootError},
+    query_state::Error as QueryStateError,
+    trie_compact::DEFAULT_MAX_DB_SIZE,
+};
+
+pub const COMMAND_NAME: &str = "balance";
+const ACCOUNT_HASH: &str = "account-hash";
+const MAX_DB_SIZE: &str = "max-db-size";
+const PUBLIC_KEY: &str = "public-key";
+const TRIE_STORE_PATH: &str = "trie";
+
+/// Errors encountered when looking up a balance.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// The value under the balance key isn't a `U512`.
+    #[error("Invalid balance of purse {0}: {1}")]
+    InvalidBalance(URef, CLValueError),
+    /// The value under the account key isn't an `Account`.
+    #[error("No account record under {}", .0.to_formatted_string())]
+    NotAnAccount(AccountHash),
+    /// The value under the balance key isn't a `CLValue`.
+    #[error("No balance record for purse {0}")]
+    NotABalance(URef),
+    /// Error opening the trie store.
+    #[error("Error opening the trie store: {0}")]
+    OpenTrie(AnyError),
+    #[error("Error writing output: {0}")]
+    Output(#[from] IoError),
+    /// Error querying the global state.
+    #[error("Error querying the global state: {0}")]
+    Query(#[from] QueryStateError),
+    /// Error finding the state root to read the balance at.
+    #[error("Error finding the state root: {0}")]
+    StateRoot(#[from] StateRootError),
+}
+
+enum DisplayOrder {
+    TriePath,
+    StateRoot,
+    AccountHash,
+    PublicKey,
+    MaxDbSize,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about("Prints the balance of the main purse of an account under a state root, in motes.")
+        .arg(
+            Arg::new(TRIE_STORE_PATH)
+                .display_order(DisplayOrder::TriePath as usize)
+                .required(true)
+                .short('t')
+                .long(TRIE_STORE_PATH)
+                .takes_value(true)
+                .value_name("TRIE_STORE_DIR_PATH")
+                .help("Path of the directory with the `data.lmdb` file."),
+        )
+        .args(dump_state::state_root_args(
+            DisplayOrder::StateRoot as usize,
+        ))
+        .arg(
+            Arg::new(ACCOUNT_HASH)
+                .display_order(DisplayOrder::AccountHash as usize)
+                .required_unless_present(PUBLIC_KEY)
+                .conflicts_with(PUBLIC_KEY)
+                .short('a')
+                .long(ACCOUNT_HASH)
+                .takes_value(true)
+                .value_name("FORMATTED_ACCOUNT_HASH")
+                .help("Account hash of the account, formatted as `account-hash-<HEX>`."),
+        )
+        .arg(
+            Arg::new(PUBLIC_KEY)
+                .display_order(DisplayOrder::PublicKey as usize)
+                .short('p')
+                .long(PUBLIC_KEY)
+                .takes_value(true)
+                .value_name("PUBLIC_KEY")
+                .help("Hex encoded public key of the account."),
+        )
+        .arg(
+            Arg::new(MAX_DB_SIZE)
+                .display_order(DisplayOrder::MaxDbSize as usize)
+                .required(false)
+                .short('m')
+                .long(MAX_DB_SIZE)
+                .takes_value(true)
+                .default_value(DEFAULT_MAX_DB_SIZE)
+                .value_name("MAX_DB_SIZE")
+                .help("Maximum size the DB files are allowed to be, in bytes."),
+        )
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let trie_path = Path::new(matches.value_of(TRIE_STORE_PATH).unwrap());
+    let state_root_hash = dump_state::resolve_state_root(matches)?;
+    let account_hash = matches
+        .value_of(ACCOUNT_HASH)
+        .map(|account_hash_str| {
+            AccountHash::from_formatted_str(account_hash_str)
+                .expect("should parse account hash from formatted string")
+        })
+        .unwrap_or_else(|| {
+            matches
+                .value_of(PUBLIC_KEY)
+                .map(|public_key_str| {
+                    PublicKey::from_hex(public_key_str)
+                        .expect("should parse public key from hex format")
+                        .to_account_hash()
+                })
+                .expect("should have either account-hash or public-key arg")
+        });
+    let max_db_size = matches
+        .value_of(MAX_DB_SIZE)
+        .unwrap()
+        .parse()
+        .expect("Value of \"--max-db-size\" must be an integer.");
+    let balance =
+        read_balance::account_balance(trie_path, max_db_size, state_root_hash, account_hash)?;
+    writeln!(io::stdout(), "{balance}")?;
+    Ok(())
+}
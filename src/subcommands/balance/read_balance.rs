@@ -0,0 +1,53 @@
+use std::path::Path;
+
+use casper_execution_engine::{
+    core::engine_state::EngineState, storage::global_state::lmdb::LmdbGlobalState,
+};
+use casper_hashing::Digest;
+use casper_types::{account::AccountHash, Key, StoredValue, URef, U512};
+
+use crate::subcommands::{query_state, trie_compact::load_execution_engine};
+
+use super::Error;
+
+/// Returns the main purse of the account with the given hash.
+fn main_purse(
+    engine_state: &EngineState<LmdbGlobalState>,
+    state_root_hash: Digest,
+    account_hash: AccountHash,
+) -> Result<URef, Error> {
+    match query_state::run_query(
+        engine_state,
+        state_root_hash,
+        Key::Account(account_hash),
+        vec![],
+    )? {
+        StoredValue::Account(account) => Ok(account.main_purse()),
+        _ => Err(Error::NotAnAccount(account_hash)),
+    }
+}
+
+/// Returns the balance of the main purse of the account with the given hash
+/// in the global state at `state_root_hash`, in motes.
+pub(crate) fn account_balance<P: AsRef<Path>>(
+    trie_path: P,
+    max_db_size: usize,
+    state_root_hash: Digest,
+    account_hash: AccountHash,
+) -> Result<U512, Error> {
+    let (engine_state, _env) =
+        load_execution_engine(trie_path, max_db_size, Digest::default(), true)
+            .map_err(Error::OpenTrie)?;
+    let purse = main_purse(&engine_state, state_root_hash, account_hash)?;
+    match query_state::run_query(
+        &engine_state,
+        state_root_hash,
+        Key::Balance(purse.addr()),
+        vec![],
+    )? {
+        StoredValue::CLValue(cl_value) => cl_value
+            .into_t()
+            .map_err(|cl_value_err| Error::InvalidBalance(purse, cl_value_err)),
+        _ => Err(Error::NotABalance(purse)),
+    }
+}
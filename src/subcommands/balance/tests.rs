@@ -0,0 +1,93 @@
+use std::collections::{BTreeMap, HashMap};
+
+use casper_types::{
+    account::{Account, AccountHash},
+    AccessRights, CLValue, Key, StoredValue, URef, U512,
+};
+
+use crate::subcommands::{
+    query_state::Error as QueryStateError,
+    trie_compact::tests::{create_test_global_state, DEFAULT_MAX_DB_SIZE},
+};
+
+use super::{read_balance, Error};
+
+const ACCOUNT_HASH: AccountHash = AccountHash::new([1u8; 32]);
+const PURSELESS_ACCOUNT_HASH: AccountHash = AccountHash::new([2u8; 32]);
+const NOT_AN_ACCOUNT_HASH: AccountHash = AccountHash::new([3u8; 32]);
+
+fn test_stored_values() -> HashMap<Key, StoredValue> {
+    let main_purse = URef::new([4u8; 32], AccessRights::READ_ADD_WRITE);
+    let missing_purse = URef::new([5u8; 32], AccessRights::READ_ADD_WRITE);
+
+    let mut stored_values = HashMap::new();
+    stored_values.insert(
+        Key::Account(ACCOUNT_HASH),
+        StoredValue::Account(Account::create(ACCOUNT_HASH, BTreeMap::new(), main_purse)),
+    );
+    stored_values.insert(
+        Key::Balance(main_purse.addr()),
+        StoredValue::CLValue(CLValue::from_t(U512::from(1_234_567_890u64)).unwrap()),
+    );
+    stored_values.insert(
+        Key::Account(PURSELESS_ACCOUNT_HASH),
+        StoredValue::Account(Account::create(
+            PURSELESS_ACCOUNT_HASH,
+            BTreeMap::new(),
+            missing_purse,
+        )),
+    );
+    stored_values.insert(
+        Key::Account(NOT_AN_ACCOUNT_HASH),
+        StoredValue::CLValue(CLValue::from_t(1u64).unwrap()),
+    );
+    stored_values
+}
+
+#[test]
+fn balance_should_be_read_from_main_purse() {
+    let (trie_dir, state_root_hash) = create_test_global_state(test_stored_values());
+
+    let balance = read_balance::account_balance(
+        trie_dir.path(),
+        *DEFAULT_MAX_DB_SIZE,
+        state_root_hash,
+        ACCOUNT_HASH,
+    )
+    .unwrap();
+    assert_eq!(balance, U512::from(1_234_567_890u64));
+}
+
+#[test]
+fn balance_should_fail_on_missing_records() {
+    let (trie_dir, state_root_hash) = create_test_global_state(test_stored_values());
+
+    for account_hash in [AccountHash::new([6u8; 32]), PURSELESS_ACCOUNT_HASH] {
+        match read_balance::account_balance(
+            trie_dir.path(),
+            *DEFAULT_MAX_DB_SIZE,
+            state_root_hash,
+            account_hash,
+        ) {
+            Err(Error::Query(QueryStateError::ValueNotFound(_))) => {}
+            Err(err) => panic!("Unexpected error: {err}"),
+            Ok(balance) => panic!("Unexpected balance: {balance}"),
+        }
+    }
+}
+
+#[test]
+fn balance_should_fail_on_invalid_account() {
+    let (trie_dir, state_root_hash) = create_test_global_state(test_stored_values());
+
+    match read_balance::account_balance(
+        trie_dir.path(),
+        *DEFAULT_MAX_DB_SIZE,
+        state_root_hash,
+        NOT_AN_ACCOUNT_HASH,
+    ) {
+        Err(Error::NotAnAccount(account_hash)) => assert_eq!(account_hash, NOT_AN_ACCOUNT_HASH),
+        Err(err) => panic!("Unexpected error: {err}"),
+        Ok(balance) => panic!("Unexpected balance: {balance}"),
+    }
+}
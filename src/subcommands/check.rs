@@ -1,19 +1,48 @@
-use std::path::{Path, PathBuf};
+mod chain;
+mod indices;
+#[cfg(test)]
+mod tests;
 
-use clap::{Arg, ArgMatches, Command};
-use lmdb::Error as LmdbError;
+use std::{
+    fs::OpenOptions,
+    io::{self, Error as IoError, Write},
+    path::{Path, PathBuf},
+};
+
+use bincode::Error as BincodeError;
+use casper_hashing::Digest;
+use casper_node::types::BlockHash;
+use clap::{Arg, ArgGroup, ArgMatches, Command};
+use lmdb::{Environment, Error as LmdbError, Transaction};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::Error as JsonError;
 use thiserror::Error as ThisError;
 
-use crate::common::db::{
-    db_env, BlockBodyDatabase, BlockBodyMerkleDatabase, BlockHeaderDatabase, BlockMetadataDatabase,
-    Database, DeployDatabase, DeployHashesDatabase, DeployMetadataDatabase, Error as DbError,
-    FinalizedApprovalsDatabase, ProposerDatabase, StateStoreDatabase, TransferDatabase,
-    TransferHashesDatabase, STORAGE_FILE_NAME,
+use crate::common::{
+    checkpoint::{self, Error as CheckpointError},
+    db::{
+        db_env, BlockBodyDatabase, BlockBodyMerkleDatabase, BlockHeaderDatabase,
+        BlockMetadataDatabase, CheckpointFn, Database, DeployDatabase, DeployHashesDatabase,
+        DeployMetadataDatabase, Error as DbError, FinalizedApprovalsDatabase, ProposerDatabase,
+        StartPosition, StateStoreDatabase, TransferDatabase, TransferHashesDatabase, DB_NAMES,
+        STORAGE_FILE_NAME,
+    },
+    lmdb_utils,
 };
 
 pub const COMMAND_NAME: &str = "check";
+const CHAIN: &str = "chain";
+const CHECKPOINT: &str = "checkpoint";
 const DB_PATH: &str = "db-path";
+const INDICES: &str = "indices";
 const NO_FAILFAST: &str = "no-failfast";
+const OUTPUT: &str = "output";
+const OVERWRITE: &str = "overwrite";
+const REBUILD_INDICES: &str = "rebuild-indices";
+const REPORT: &str = "report";
+const RESUME: &str = "resume";
+const SCAN_UNKNOWN: &str = "scan-unknown";
 const SPECIFIC: &str = "specific";
 const START_AT: &str = "start-at";
 
@@ -22,18 +51,60 @@ enum DisplayOrder {
     DbPath,
     Specific,
     StartAt,
+    Checkpoint,
+    Resume,
+    ScanUnknown,
+    Chain,
+    Indices,
+    RebuildIndices,
+    Output,
+    Overwrite,
 }
 
 #[derive(ThisError, Debug)]
 pub enum Error {
+    #[error("Error parsing block body {0}: {1}")]
+    BodyParsing(Digest, BincodeError),
+    #[error("Error reading block headers: {0}")]
+    ChainDatabase(LmdbError),
+    #[error("Found {0} chain continuity issues")]
+    ChainDiscontinuity(usize),
+    #[error("Error loading checkpoint: {0}")]
+    Checkpoint(#[from] CheckpointError),
+    #[error("Checkpoint was saved while checking database {0}, which is not being checked")]
+    CheckpointMismatch(String),
     #[error("Error checking the database: {0}")]
     Database(#[from] DbError),
+    #[error("Error parsing block header {0}: {1}")]
+    HeaderParsing(BlockHash, BincodeError),
+    #[error("Error operating the block body index databases: {0}")]
+    IndexDatabase(LmdbError),
+    #[error("Found {0} unrepaired divergences between the block bodies and their index entries")]
+    IndexDivergence(usize),
+    #[error("Error serializing block body part: {0}")]
+    IndexSerialize(String),
+    #[error("Error deserializing raw key of block header DB element: {0}")]
+    InvalidHeaderKey(usize),
+    #[error("Error writing output: {0}")]
+    Output(#[from] IoError),
     #[error("Error initializing lmdb environment at {0}: {1}")]
     Path(PathBuf, LmdbError),
+    #[error("Error serializing report: {0}")]
+    ReportSerialize(JsonError),
     #[error("Unknown database {0}")]
     UnknownDb(String),
 }
 
+/// Progress of a check, periodically saved to the checkpoint file.
+#[derive(Debug, Deserialize, PartialEq, Eq, Serialize)]
+struct Checkpoint {
+    /// Name of the database being checked.
+    db_name: String,
+    /// Key of the last checked entry in that database.
+    #[serde(with = "hex")]
+    last_key: Vec<u8>,
+}
+
 pub fn command(display_order: usize) -> Command<'static> {
     Command::new(COMMAND_NAME)
         .about(
@@ -84,10 +155,117 @@ pub fn command(display_order: usize) -> Command<'static> {
                     to be set.",
                 ),
         )
+        .arg(
+            Arg::new(CHECKPOINT)
+                .display_order(DisplayOrder::Checkpoint as usize)
+                .short('c')
+                .long(CHECKPOINT)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help(
+                    "Path of a file where the progress of the check is periodically saved, so \
+                    that it can be continued later with \"--resume\".",
+                ),
+        )
+        .arg(
+            Arg::new(RESUME)
+                .display_order(DisplayOrder::Resume as usize)
+                .short('r')
+                .long(RESUME)
+                .takes_value(false)
+                .requires(CHECKPOINT)
+                .conflicts_with(START_AT)
+                .help(
+                    "Resume the check right after the last entry recorded in the file given \
+                    by \"--checkpoint\".",
+                ),
+        )
+        .arg(
+            Arg::new(SCAN_UNKNOWN)
+                .display_order(DisplayOrder::ScanUnknown as usize)
+                .long(SCAN_UNKNOWN)
+                .takes_value(false)
+                .help(
+                    "Read all entries of the databases in the environment which have no known \
+                    format, without parsing them. By default, such databases are skipped.",
+                ),
+        )
+        .arg(
+            Arg::new(CHAIN)
+                .display_order(DisplayOrder::Chain as usize)
+                .long(CHAIN)
+                .takes_value(false)
+                .conflicts_with_all(&[SPECIFIC, CHECKPOINT, SCAN_UNKNOWN])
+                .help(
+                    "Instead of parsing the databases, verify the continuity of the chain formed \
+                    by the block headers and output a report of the issues found in JSON format.",
+                ),
+        )
+        .arg(
+            Arg::new(INDICES)
+                .display_order(DisplayOrder::Indices as usize)
+                .long(INDICES)
+                .takes_value(false)
+                .conflicts_with_all(&[SPECIFIC, CHECKPOINT, SCAN_UNKNOWN])
+                .help(
+                    "Instead of parsing the databases, follow the merklized block bodies through \
+                    the \"block_body_merkle\", \"deploy_hashes\", \"transfer_hashes\" and \
+                    \"proposers\" databases from the body hash of every block header, and output \
+                    a report of the missing, corrupt or divergent entries found in JSON format.",
+                ),
+        )
+        .arg(
+            Arg::new(REBUILD_INDICES)
+                .display_order(DisplayOrder::RebuildIndices as usize)
+                .long(REBUILD_INDICES)
+                .takes_value(false)
+                .requires(INDICES)
+                .help(
+                    "Overwrite the divergent index entries with the ones derived from the bodies \
+                    in the \"block_body\" database, in batches of bounded write transactions.",
+                ),
+        )
+        .group(ArgGroup::new(REPORT).args(&[CHAIN, INDICES]))
+        .arg(
+            Arg::new(OUTPUT)
+                .display_order(DisplayOrder::Output as usize)
+                .short('o')
+                .long(OUTPUT)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .requires(REPORT)
+                .help(
+                    "Path to where the program will output the chain or index report. \
+                    If unspecified, defaults to standard output.",
+                ),
+        )
+        .arg(
+            Arg::new(OVERWRITE)
+                .display_order(DisplayOrder::Overwrite as usize)
+                .short('w')
+                .long(OVERWRITE)
+                .takes_value(false)
+                .requires(OUTPUT)
+                .help(
+                    "Overwrite an already existing output file in destination \
+                    directory.",
+                ),
+        )
 }
 
 pub fn run(matches: &ArgMatches) -> Result<(), Error> {
     let path = matches.value_of(DB_PATH).unwrap();
+    if matches.is_present(CHAIN) {
+        let output = matches.value_of(OUTPUT).map(Path::new);
+        let overwrite = matches.is_present(OVERWRITE);
+        return check_chain(path, output, overwrite);
+    }
+    if matches.is_present(INDICES) {
+        let output = matches.value_of(OUTPUT).map(Path::new);
+        let overwrite = matches.is_present(OVERWRITE);
+        let rebuild = matches.is_present(REBUILD_INDICES);
+        return check_indices(path, output, overwrite, rebuild);
+    }
     let failfast = !matches.is_present(NO_FAILFAST);
     let specific = matches.value_of(SPECIFIC);
     let start_at: usize = matches
@@ -95,8 +273,48 @@ pub fn run(matches: &ArgMatches) -> Result<(), Error> {
         .expect("should have a default")
         .parse()
         .unwrap_or_else(|_| panic!("Value of \"--{START_AT}\" must be an integer."));
+    let checkpoint_path = matches.value_of(CHECKPOINT).map(Path::new);
+    let resume = matches.is_present(RESUME);
+    let scan_unknown = matches.is_present(SCAN_UNKNOWN);
+
+    check_db(
+        path,
+        failfast,
+        specific,
+        start_at,
+        checkpoint_path,
+        resume,
+        scan_unknown,
+    )
+}
 
-    check_db(path, failfast, specific, start_at)
+fn check_specific_db(
+    env: &Environment,
+    db_name: &str,
+    failfast: bool,
+    start: StartPosition,
+    on_checkpoint: &mut CheckpointFn,
+) -> Result<(), Error> {
+    match db_name {
+        "block_body" => BlockBodyDatabase::check_db(env, failfast, start, on_checkpoint)?,
+        "block_body_merkle" => {
+            BlockBodyMerkleDatabase::check_db(env, failfast, start, on_checkpoint)?
+        }
+        "block_header" => BlockHeaderDatabase::check_db(env, failfast, start, on_checkpoint)?,
+        "block_metadata" => BlockMetadataDatabase::check_db(env, failfast, start, on_checkpoint)?,
+        "deploy_hashes" => DeployHashesDatabase::check_db(env, failfast, start, on_checkpoint)?,
+        "deploy_metadata" => DeployMetadataDatabase::check_db(env, failfast, start, on_checkpoint)?,
+        "deploys" => DeployDatabase::check_db(env, failfast, start, on_checkpoint)?,
+        "finalized_approvals" => {
+            FinalizedApprovalsDatabase::check_db(env, failfast, start, on_checkpoint)?
+        }
+        "proposers" => ProposerDatabase::check_db(env, failfast, start, on_checkpoint)?,
+        "state_store" => StateStoreDatabase::check_db(env, failfast, start, on_checkpoint)?,
+        "transfer" => TransferDatabase::check_db(env, failfast, start, on_checkpoint)?,
+        "transfer_hashes" => TransferHashesDatabase::check_db(env, failfast, start, on_checkpoint)?,
+        _ => return Err(Error::UnknownDb(db_name.to_string())),
+    }
+    Ok(())
 }
 
 fn check_db<P: AsRef<Path>>(
@@ -104,43 +322,152 @@ fn check_db<P: AsRef<Path>>(
     failfast: bool,
     specific: Option<&str>,
     start_at: usize,
+    checkpoint_path: Option<&Path>,
+    resume: bool,
+    scan_unknown: bool,
 ) -> Result<(), Error> {
     let storage_path = path.as_ref().join(STORAGE_FILE_NAME);
     let env = db_env(storage_path)
         .map_err(|lmdb_err| Error::Path(path.as_ref().to_path_buf(), lmdb_err))?;
-    if let Some(db_name) = specific {
-        match db_name.trim() {
-            "block_body" => BlockBodyDatabase::check_db(&env, failfast, start_at)?,
-            "block_body_merkle" => BlockBodyMerkleDatabase::check_db(&env, failfast, start_at)?,
-            "block_header" => BlockHeaderDatabase::check_db(&env, failfast, start_at)?,
-            "block_metadata" => BlockMetadataDatabase::check_db(&env, failfast, start_at)?,
-            "deploy_hashes" => DeployHashesDatabase::check_db(&env, failfast, start_at)?,
-            "deploy_metadata" => DeployMetadataDatabase::check_db(&env, failfast, start_at)?,
-            "deploys" => DeployDatabase::check_db(&env, failfast, start_at)?,
-            "finalized_approvals" => {
-                FinalizedApprovalsDatabase::check_db(&env, failfast, start_at)?
+    let discovered_names = lmdb_utils::db_names(&env).map_err(DbError::from)?;
+    let (db_names, unknown_names): (Vec<&str>, Vec<&str>) = match specific {
+        Some(db_name) => {
+            let db_name = db_name.trim();
+            if DB_NAMES.contains(&db_name) {
+                (vec![db_name], vec![])
+            } else if discovered_names.iter().any(|name| name == db_name) {
+                (vec![], vec![db_name])
+            } else {
+                return Err(Error::UnknownDb(db_name.to_string()));
             }
-            "proposers" => ProposerDatabase::check_db(&env, failfast, start_at)?,
-            "state_store" => StateStoreDatabase::check_db(&env, failfast, start_at)?,
-            "transfer" => TransferDatabase::check_db(&env, failfast, start_at)?,
-            "transfer_hashes" => TransferHashesDatabase::check_db(&env, failfast, start_at)?,
-            _ => return Err(Error::UnknownDb(db_name.to_string())),
         }
+        None => {
+            // Sanity check for `start_at`, already validated in arg parser.
+            assert_eq!(start_at, 0);
+            let unknown_names = discovered_names
+                .iter()
+                .map(String::as_str)
+                .filter(|name| !DB_NAMES.contains(name))
+                .collect();
+            (DB_NAMES.to_vec(), unknown_names)
+        }
+    };
+
+    let mut maybe_checkpoint: Option<Checkpoint> = if resume {
+        let checkpoint: Checkpoint =
+            checkpoint::load(checkpoint_path.expect("resume requires a checkpoint path"))?;
+        if !db_names.contains(&checkpoint.db_name.as_str()) {
+            return Err(Error::CheckpointMismatch(checkpoint.db_name));
+        }
+        Some(checkpoint)
     } else {
-        // Sanity check for `start_at`, already validated in arg parser.
-        assert_eq!(start_at, 0);
-        BlockBodyDatabase::check_db(&env, failfast, start_at)?;
-        BlockBodyMerkleDatabase::check_db(&env, failfast, start_at)?;
-        BlockHeaderDatabase::check_db(&env, failfast, start_at)?;
-        BlockMetadataDatabase::check_db(&env, failfast, start_at)?;
-        DeployHashesDatabase::check_db(&env, failfast, start_at)?;
-        DeployMetadataDatabase::check_db(&env, failfast, start_at)?;
-        DeployDatabase::check_db(&env, failfast, start_at)?;
-        FinalizedApprovalsDatabase::check_db(&env, failfast, start_at)?;
-        ProposerDatabase::check_db(&env, failfast, start_at)?;
-        StateStoreDatabase::check_db(&env, failfast, start_at)?;
-        TransferDatabase::check_db(&env, failfast, start_at)?;
-        TransferHashesDatabase::check_db(&env, failfast, start_at)?;
+        None
     };
+
+    for db_name in db_names {
+        let start = match maybe_checkpoint.as_ref() {
+            // Databases before the one in the checkpoint were already checked.
+            Some(checkpoint) if checkpoint.db_name != db_name => {
+                info!("Skipping {db_name} database, already checked.");
+                continue;
+            }
+            Some(checkpoint) => StartPosition::AfterKey(&checkpoint.last_key),
+            None => StartPosition::Index(start_at),
+        };
+        let mut on_checkpoint = |last_key: &[u8]| match checkpoint_path {
+            Some(path) => {
+                let checkpoint = Checkpoint {
+                    db_name: db_name.to_string(),
+                    last_key: last_key.to_vec(),
+                };
+                checkpoint::store(path, &checkpoint).map_err(DbError::from)
+            }
+            None => Ok(()),
+        };
+        check_specific_db(&env, db_name, failfast, start, &mut on_checkpoint)?;
+        maybe_checkpoint = None;
+    }
+
+    for db_name in unknown_names {
+        if scan_unknown {
+            scan_unknown_db(&env, db_name)?;
+        } else {
+            warn!("No known format for {db_name} database, skipping it.");
+        }
+    }
+    Ok(())
+}
+
+/// Reads all the entries of a database with no known format, surfacing
+/// the errors lmdb encounters along the way.
+fn scan_unknown_db(env: &Environment, db_name: &str) -> Result<(), Error> {
+    warn!("No known format for {db_name} database, scanning it without parsing.");
+    let txn = env.begin_ro_txn().map_err(DbError::from)?;
+    let db = unsafe { txn.open_db(Some(db_name)).map_err(DbError::from)? };
+    let (entry_count, byte_count) = lmdb_utils::scan_db(&txn, db).map_err(DbError::from)?;
+    info!("Read {entry_count} entries totalling {byte_count} bytes from {db_name} database.");
     Ok(())
 }
+
+fn check_chain<P1: AsRef<Path>, P2: AsRef<Path>>(
+    path: P1,
+    output: Option<P2>,
+    overwrite: bool,
+) -> Result<(), Error> {
+    let storage_path = path.as_ref().join(STORAGE_FILE_NAME);
+    let env = db_env(storage_path)
+        .map_err(|lmdb_err| Error::Path(path.as_ref().to_path_buf(), lmdb_err))?;
+    // Validate the output file early so that, in case this fails
+    // we don't unnecessarily read the whole database.
+    let out_writer = report_writer(output, overwrite)?;
+
+    info!("Checking chain continuity.");
+    let report = chain::check_chain(&env)?;
+    chain::dump_chain_report(&report, out_writer)?;
+    match report.issue_count() {
+        0 => Ok(()),
+        issue_count => Err(Error::ChainDiscontinuity(issue_count)),
+    }
+}
+
+fn check_indices<P1: AsRef<Path>, P2: AsRef<Path>>(
+    path: P1,
+    output: Option<P2>,
+    overwrite: bool,
+    rebuild: bool,
+) -> Result<(), Error> {
+    let storage_path = path.as_ref().join(STORAGE_FILE_NAME);
+    let env = db_env(storage_path)
+        .map_err(|lmdb_err| Error::Path(path.as_ref().to_path_buf(), lmdb_err))?;
+    let out_writer = report_writer(output, overwrite)?;
+
+    info!("Checking block body indices.");
+    let report = indices::check_indices(&env, rebuild)?;
+    indices::dump_index_report(&report, out_writer)?;
+    if rebuild {
+        info!("Rebuilt {} index entries.", report.rebuilt_entries);
+    }
+    match report.unrepaired_divergences {
+        0 => Ok(()),
+        unrepaired => Err(Error::IndexDivergence(unrepaired)),
+    }
+}
+
+/// Opens the file the report is written to, or standard output if no path
+/// is given.
+fn report_writer<P: AsRef<Path>>(
+    output: Option<P>,
+    overwrite: bool,
+) -> Result<Box<dyn Write>, Error> {
+    let out_writer: Box<dyn Write> = if let Some(out_path) = output {
+        let file = OpenOptions::new()
+            .create_new(!overwrite)
+            .write(true)
+            .truncate(true)
+            .open(out_path)?;
+        Box::new(file)
+    } else {
+        Box::new(io::stdout())
+    };
+    Ok(out_writer)
+}
@@ -0,0 +1,273 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::Write,
+};
+
+use casper_hashing::Digest;
+use casper_node::types::{BlockHash, BlockHeader};
+use casper_types::EraId;
+use lmdb::{Cursor, Environment, Transaction};
+use log::{info, warn};
+use serde::Serialize;
+
+use crate::common::{
+    db::{BlockHeaderDatabase, Database},
+    lmdb_utils,
+    progress::ProgressTracker,
+};
+
+use super::Error;
+
+/// The information about a block header needed to verify the chain.
+struct HeaderLink {
+    block_hash: BlockHash,
+    parent_hash: BlockHash,
+    era_id: EraId,
+    /// Whether the header is a switch block holding the validator weights
+    /// for the next era.
+    closes_era: bool,
+}
+
+/// Range of consecutive heights for which no block header was found.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct Gap {
+    pub(crate) first_missing_height: u64,
+    pub(crate) last_missing_height: u64,
+}
+
+/// Several block headers found at the same height.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct Fork {
+    pub(crate) height: u64,
+    pub(crate) block_hashes: Vec<BlockHash>,
+}
+
+/// Block header whose parent hash doesn't match any block at the previous
+/// height.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct BrokenLink {
+    pub(crate) height: u64,
+    pub(crate) block_hash: BlockHash,
+    pub(crate) parent_hash: BlockHash,
+}
+
+/// Block header with a lower era id than its parent.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct EraRegression {
+    pub(crate) height: u64,
+    pub(crate) block_hash: BlockHash,
+    pub(crate) era_id: EraId,
+    pub(crate) parent_era_id: EraId,
+}
+
+/// Block header following a switch block which isn't in the next era.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct MissedEraTransition {
+    pub(crate) height: u64,
+    pub(crate) block_hash: BlockHash,
+    pub(crate) era_id: EraId,
+    pub(crate) expected_era_id: EraId,
+}
+
+/// Structure of the chain as found in the block header database, along
+/// with all the continuity issues encountered.
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub(crate) struct ChainReport {
+    pub(crate) block_count: usize,
+    pub(crate) lowest_height: Option<u64>,
+    pub(crate) highest_height: Option<u64>,
+    pub(crate) gaps: Vec<Gap>,
+    pub(crate) forks: Vec<Fork>,
+    pub(crate) broken_links: Vec<BrokenLink>,
+    pub(crate) era_regressions: Vec<EraRegression>,
+    pub(crate) missed_era_transitions: Vec<MissedEraTransition>,
+    /// Eras before the highest one which have no switch block holding the
+    /// validator weights of the next era.
+    pub(crate) unclosed_eras: Vec<EraId>,
+}
+
+impl ChainReport {
+    /// Returns the total number of continuity issues in the report.
+    pub(crate) fn issue_count(&self) -> usize {
+        self.gaps.len()
+            + self.forks.len()
+            + self.broken_links.len()
+            + self.era_regressions.len()
+            + self.missed_era_transitions.len()
+            + self.unclosed_eras.len()
+    }
+
+    fn from_links(heights: BTreeMap<u64, Vec<HeaderLink>>) -> Self {
+        let mut report = ChainReport {
+            block_count: heights.values().map(Vec::len).sum(),
+            lowest_height: heights.keys().next().copied(),
+            highest_height: heights.keys().next_back().copied(),
+            ..Default::default()
+        };
+
+        let mut eras: BTreeSet<EraId> = BTreeSet::new();
+        let mut closed_eras: BTreeSet<EraId> = BTreeSet::new();
+        let mut previous: Option<(u64, &Vec<HeaderLink>)> = None;
+        for (&height, links) in heights.iter() {
+            if links.len() > 1 {
+                report.forks.push(Fork {
+                    height,
+                    block_hashes: links.iter().map(|link| link.block_hash).collect(),
+                });
+            }
+            for link in links {
+                let _ = eras.insert(link.era_id);
+                if link.closes_era {
+                    let _ = closed_eras.insert(link.era_id);
+                }
+            }
+
+            match previous {
+                Some((previous_height, _)) if previous_height + 1 != height => {
+                    report.gaps.push(Gap {
+                        first_missing_height: previous_height + 1,
+                        last_missing_height: height - 1,
+                    });
+                }
+                Some((_, parents)) => {
+                    for link in links {
+                        match parents
+                            .iter()
+                            .find(|parent| parent.block_hash == link.parent_hash)
+                        {
+                            Some(parent) if parent.era_id > link.era_id => {
+                                report.era_regressions.push(EraRegression {
+                                    height,
+                                    block_hash: link.block_hash,
+                                    era_id: link.era_id,
+                                    parent_era_id: parent.era_id,
+                                })
+                            }
+                            Some(parent)
+                                if parent.closes_era
+                                    && link.era_id != parent.era_id.successor() =>
+                            {
+                                report.missed_era_transitions.push(MissedEraTransition {
+                                    height,
+                                    block_hash: link.block_hash,
+                                    era_id: link.era_id,
+                                    expected_era_id: parent.era_id.successor(),
+                                })
+                            }
+                            Some(_) => {}
+                            None => report.broken_links.push(BrokenLink {
+                                height,
+                                block_hash: link.block_hash,
+                                parent_hash: link.parent_hash,
+                            }),
+                        }
+                    }
+                }
+                None => {}
+            }
+            previous = Some((height, links));
+        }
+
+        // The highest era may still be in progress, so it isn't required to
+        // have a switch block.
+        let _ = eras.pop_last();
+        report.unclosed_eras = eras.difference(&closed_eras).copied().collect();
+        report
+    }
+}
+
+/// Reads all the block headers in the database and verifies the chain
+/// they form is continuous.
+pub(crate) fn check_chain(env: &Environment) -> Result<ChainReport, Error> {
+    let txn = env.begin_ro_txn().map_err(Error::ChainDatabase)?;
+    let header_db = unsafe {
+        txn.open_db(Some(BlockHeaderDatabase::db_name()))
+            .map_err(Error::ChainDatabase)?
+    };
+
+    let mut maybe_progress_tracker = match lmdb_utils::entry_count(&txn, header_db).ok() {
+        Some(entry_count) => ProgressTracker::new(
+            entry_count,
+            Box::new(|completion| info!("Header database parsing {}% complete...", completion)),
+        )
+        .ok(),
+        None => {
+            info!("Skipping progress tracking for header database parsing");
+            None
+        }
+    };
+
+    let mut heights: BTreeMap<u64, Vec<HeaderLink>> = BTreeMap::new();
+    {
+        let mut cursor = txn
+            .open_ro_cursor(header_db)
+            .map_err(Error::ChainDatabase)?;
+        for (idx, (raw_key, raw_value)) in cursor.iter().enumerate() {
+            if let Some(progress_tracker) = maybe_progress_tracker.as_mut() {
+                progress_tracker.advance_by(1);
+            }
+            let block_hash: BlockHash = Digest::try_from(raw_key)
+                .map_err(|_| Error::InvalidHeaderKey(idx))?
+                .into();
+            let block_header: BlockHeader = bincode::deserialize(raw_value)
+                .map_err(|bincode_err| Error::HeaderParsing(block_hash, bincode_err))?;
+            heights
+                .entry(block_header.height())
+                .or_default()
+                .push(HeaderLink {
+                    block_hash,
+                    parent_hash: *block_header.parent_hash(),
+                    era_id: block_header.era_id(),
+                    closes_era: block_header.next_era_validator_weights().is_some(),
+                });
+        }
+    }
+    txn.commit().map_err(Error::ChainDatabase)?;
+
+    let report = ChainReport::from_links(heights);
+    for gap in report.gaps.iter() {
+        warn!(
+            "Missing block headers for heights {} to {}",
+            gap.first_missing_height, gap.last_missing_height
+        );
+    }
+    for fork in report.forks.iter() {
+        warn!(
+            "Found {} block headers at height {}",
+            fork.block_hashes.len(),
+            fork.height
+        );
+    }
+    for link in report.broken_links.iter() {
+        warn!(
+            "Parent hash {} of block {} at height {} doesn't match any block at the previous \
+            height",
+            link.parent_hash, link.block_hash, link.height
+        );
+    }
+    for regression in report.era_regressions.iter() {
+        warn!(
+            "Block {} at height {} is in era {} while its parent is in era {}",
+            regression.block_hash, regression.height, regression.era_id, regression.parent_era_id
+        );
+    }
+    for transition in report.missed_era_transitions.iter() {
+        warn!(
+            "Block {} at height {} is in era {} while its parent is a switch block, so it \
+            should be in era {}",
+            transition.block_hash, transition.height, transition.era_id, transition.expected_era_id
+        );
+    }
+    for era_id in report.unclosed_eras.iter() {
+        warn!("No switch block found for era {era_id}");
+    }
+    Ok(report)
+}
+
+/// Writes the chain report in JSON format.
+pub(crate) fn dump_chain_report<W: Write + ?Sized>(
+    report: &ChainReport,
+    out_writer: Box<W>,
+) -> Result<(), Error> {
+    serde_json::to_writer_pretty(out_writer, report).map_err(Error::ReportSerialize)
+}
@@ -0,0 +1,485 @@
+use std::io::Write;
+
+use casper_hashing::Digest;
+use casper_node::types::{
+    BlockBody, BlockHash, BlockHeader, DeployHash, HashingAlgorithmVersion, MerkleBlockBodyPart,
+};
+use casper_types::{
+    bytesrepr::{self, ToBytes},
+    PublicKey,
+};
+use lmdb::{
+    Cursor, Database as LmdbDatabase, Environment, Error as LmdbError, RoTransaction, Transaction,
+    WriteFlags,
+};
+use log::{info, warn};
+use serde::Serialize;
+
+use crate::common::{
+    db::{
+        BlockBodyDatabase, BlockBodyMerkleDatabase, BlockHeaderDatabase, Database,
+        DeployHashesDatabase, ProposerDatabase, TransferHashesDatabase,
+    },
+    lmdb_utils,
+    progress::ProgressTracker,
+};
+
+use super::Error;
+
+/// Maximum number of index entries written in a single transaction when
+/// rebuilding the indices.
+const REBUILD_BATCH_SIZE: usize = 10_000;
+
+/// How an index entry differs from what the block dictates.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum DivergenceKind {
+    /// No entry under the expected key.
+    Missing,
+    /// An entry with a different value than the one derived from the block
+    /// body under the expected key.
+    Mismatch,
+    /// An entry which doesn't parse or doesn't hash to the key it's stored
+    /// under.
+    Corrupt,
+}
+
+/// Index entry which doesn't match the block it belongs to.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct IndexDivergence {
+    pub(crate) block_hash: BlockHash,
+    pub(crate) body_hash: Digest,
+    pub(crate) db_name: &'static str,
+    pub(crate) key: Digest,
+    pub(crate) kind: DivergenceKind,
+}
+
+/// Result of checking the merklized block body databases against the
+/// block headers.
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub(crate) struct IndexReport {
+    pub(crate) block_count: usize,
+    /// Blocks hashed with the first hashing algorithm whose body isn't in
+    /// the `block_body` database.
+    pub(crate) missing_bodies: usize,
+    /// Blocks hashed with the first hashing algorithm for which no merkle
+    /// root node exists, meaning their body was never indexed in the first
+    /// place.
+    pub(crate) unindexed_bodies: usize,
+    pub(crate) divergences: Vec<IndexDivergence>,
+    /// Number of divergences left in place: all of them unless rebuilding,
+    /// otherwise the ones of bodies which can't be restored.
+    pub(crate) unrepaired_divergences: usize,
+    /// Number of entries written when rebuilding the indices.
+    pub(crate) rebuilt_entries: usize,
+}
+
+/// Entry expected in one of the index databases.
+struct ExpectedEntry {
+    db: LmdbDatabase,
+    db_name: &'static str,
+    key: Digest,
+    value: Vec<u8>,
+}
+
+/// Handles of the databases holding the block bodies.
+struct IndexDbs {
+    body: LmdbDatabase,
+    merkle: LmdbDatabase,
+    deploy_hashes: LmdbDatabase,
+    transfer_hashes: LmdbDatabase,
+    proposers: LmdbDatabase,
+}
+
+/// Part of a merklized block body, in the order of the merkle linked list.
+#[derive(Clone, Copy)]
+enum BodyPart {
+    DeployHashes,
+    TransferHashes,
+    Proposer,
+}
+
+impl BodyPart {
+    const ALL: [BodyPart; BlockBody::PARTS_COUNT] = [
+        BodyPart::DeployHashes,
+        BodyPart::TransferHashes,
+        BodyPart::Proposer,
+    ];
+
+    fn db(self, dbs: &IndexDbs) -> LmdbDatabase {
+        match self {
+            BodyPart::DeployHashes => dbs.deploy_hashes,
+            BodyPart::TransferHashes => dbs.transfer_hashes,
+            BodyPart::Proposer => dbs.proposers,
+        }
+    }
+
+    fn db_name(self) -> &'static str {
+        match self {
+            BodyPart::DeployHashes => DeployHashesDatabase::db_name(),
+            BodyPart::TransferHashes => TransferHashesDatabase::db_name(),
+            BodyPart::Proposer => ProposerDatabase::db_name(),
+        }
+    }
+
+    /// Hashes a serialized part value the same way the node does when
+    /// merklizing a block body, or returns `None` if it doesn't parse.
+    fn value_hash(self, bytes: &[u8]) -> Option<Digest> {
+        match self {
+            BodyPart::DeployHashes | BodyPart::TransferHashes => {
+                let hashes: Vec<DeployHash> = bytesrepr::deserialize(bytes.to_vec()).ok()?;
+                Some(Digest::hash_vec_merkle_tree(
+                    hashes.into_iter().map(Digest::from).collect(),
+                ))
+            }
+            BodyPart::Proposer => {
+                let _: PublicKey = bytesrepr::deserialize(bytes.to_vec()).ok()?;
+                Some(Digest::hash(bytes))
+            }
+        }
+    }
+}
+
+/// Returns the merkle linked list node and the part value which the node
+/// stores for the given block body part.
+fn part_entries<T: ToBytes>(
+    merkle_db: LmdbDatabase,
+    part_db: LmdbDatabase,
+    part_db_name: &'static str,
+    part: &MerkleBlockBodyPart<T>,
+) -> Result<[ExpectedEntry; 2], Error> {
+    let node_value = part
+        .value_and_rest_hashes_pair()
+        .to_bytes()
+        .map_err(|bytesrepr_err| Error::IndexSerialize(bytesrepr_err.to_string()))?;
+    let part_value = part
+        .value()
+        .to_bytes()
+        .map_err(|bytesrepr_err| Error::IndexSerialize(bytesrepr_err.to_string()))?;
+    Ok([
+        ExpectedEntry {
+            db: merkle_db,
+            db_name: BlockBodyMerkleDatabase::db_name(),
+            key: *part.merkle_linked_list_node_hash(),
+            value: node_value,
+        },
+        ExpectedEntry {
+            db: part_db,
+            db_name: part_db_name,
+            key: *part.value_hash(),
+            value: part_value,
+        },
+    ])
+}
+
+/// Returns all the entries the node stores in the index databases for a
+/// block body, starting with the merkle root node.
+fn expected_entries(dbs: &IndexDbs, body: &BlockBody) -> Result<Vec<ExpectedEntry>, Error> {
+    let merkle_body = body.merklize();
+    let mut entries = Vec::with_capacity(2 * BlockBody::PARTS_COUNT);
+    entries.extend(part_entries(
+        dbs.merkle,
+        dbs.deploy_hashes,
+        DeployHashesDatabase::db_name(),
+        &merkle_body.deploy_hashes,
+    )?);
+    entries.extend(part_entries(
+        dbs.merkle,
+        dbs.transfer_hashes,
+        TransferHashesDatabase::db_name(),
+        &merkle_body.transfer_hashes,
+    )?);
+    entries.extend(part_entries(
+        dbs.merkle,
+        dbs.proposers,
+        ProposerDatabase::db_name(),
+        &merkle_body.proposer,
+    )?);
+    Ok(entries)
+}
+
+/// Reads the body stored under `body_hash` in the `block_body` database.
+fn read_body(
+    txn: &RoTransaction,
+    dbs: &IndexDbs,
+    body_hash: Digest,
+) -> Result<Option<BlockBody>, Error> {
+    match txn.get(dbs.body, &body_hash) {
+        Ok(raw_body) => bincode::deserialize(raw_body)
+            .map(Some)
+            .map_err(|bincode_err| Error::BodyParsing(body_hash, bincode_err)),
+        Err(LmdbError::NotFound) => Ok(None),
+        Err(lmdb_err) => Err(Error::IndexDatabase(lmdb_err)),
+    }
+}
+
+/// Returns the entries derived from a block body which differ from the
+/// ones in the index databases, along with how they differ.
+fn divergent_entries(
+    txn: &RoTransaction,
+    entries: Vec<ExpectedEntry>,
+) -> Result<Vec<(ExpectedEntry, DivergenceKind)>, Error> {
+    let mut divergences = vec![];
+    for entry in entries {
+        let kind = match txn.get(entry.db, &entry.key) {
+            Ok(value) if value == entry.value.as_slice() => continue,
+            Ok(_) => DivergenceKind::Mismatch,
+            Err(LmdbError::NotFound) => DivergenceKind::Missing,
+            Err(lmdb_err) => return Err(Error::IndexDatabase(lmdb_err)),
+        };
+        divergences.push((entry, kind));
+    }
+    Ok(divergences)
+}
+
+/// Writes the entries derived from the block bodies in a single
+/// transaction, emptying `entries`.
+fn rebuild_entries(
+    env: &Environment,
+    entries: &mut Vec<ExpectedEntry>,
+    report: &mut IndexReport,
+) -> Result<(), Error> {
+    let mut txn = env.begin_rw_txn().map_err(Error::IndexDatabase)?;
+    for entry in entries.iter() {
+        txn.put(entry.db, &entry.key, &entry.value, WriteFlags::empty())
+            .map_err(Error::IndexDatabase)?;
+    }
+    txn.commit().map_err(Error::IndexDatabase)?;
+    report.rebuilt_entries += entries.len();
+    entries.clear();
+    Ok(())
+}
+
+/// Follows the merkle linked list of a block body from `body_hash`,
+/// checking that every node and part value hashes to the key it's stored
+/// under and that every link resolves. The walk stops at the first node
+/// which can't be followed.
+fn walk_merkle_body(
+    txn: &RoTransaction,
+    dbs: &IndexDbs,
+    block_hash: BlockHash,
+    body_hash: Digest,
+) -> Result<Vec<IndexDivergence>, Error> {
+    let mut divergences = vec![];
+    let mut divergence = |db_name, key, kind| {
+        divergences.push(IndexDivergence {
+            block_hash,
+            body_hash,
+            db_name,
+            key,
+            kind,
+        })
+    };
+    let mut node_hash = body_hash;
+    for part in BodyPart::ALL {
+        let (value_hash, rest_hash) = match txn.get(dbs.merkle, &node_hash) {
+            Ok(raw_node) => match bytesrepr::deserialize::<(Digest, Digest)>(raw_node.to_vec()) {
+                Ok((value_hash, rest_hash))
+                    if Digest::hash_pair(value_hash, rest_hash) == node_hash =>
+                {
+                    (value_hash, rest_hash)
+                }
+                _ => {
+                    divergence(
+                        BlockBodyMerkleDatabase::db_name(),
+                        node_hash,
+                        DivergenceKind::Corrupt,
+                    );
+                    break;
+                }
+            },
+            Err(LmdbError::NotFound) => {
+                divergence(
+                    BlockBodyMerkleDatabase::db_name(),
+                    node_hash,
+                    DivergenceKind::Missing,
+                );
+                break;
+            }
+            Err(lmdb_err) => return Err(Error::IndexDatabase(lmdb_err)),
+        };
+        match txn.get(part.db(dbs), &value_hash) {
+            Ok(raw_value) if part.value_hash(raw_value) == Some(value_hash) => {}
+            Ok(_) => divergence(part.db_name(), value_hash, DivergenceKind::Corrupt),
+            Err(LmdbError::NotFound) => {
+                divergence(part.db_name(), value_hash, DivergenceKind::Missing)
+            }
+            Err(lmdb_err) => return Err(Error::IndexDatabase(lmdb_err)),
+        }
+        // The proposer is the last part of the list.
+        if matches!(part, BodyPart::Proposer) && rest_hash != Digest::SENTINEL_RFOLD {
+            divergence(
+                BlockBodyMerkleDatabase::db_name(),
+                node_hash,
+                DivergenceKind::Corrupt,
+            );
+        }
+        node_hash = rest_hash;
+    }
+    Ok(divergences)
+}
+
+/// Checks the merklized block bodies in the `block_body_merkle`,
+/// `deploy_hashes`, `transfer_hashes` and `proposers` databases against
+/// the block headers.
+///
+/// Bodies of blocks hashed with the second hashing algorithm are only
+/// stored merklized, so the merkle linked list is followed from the body
+/// hash of the header, and a missing root node is reported like any other
+/// missing entry. Bodies of blocks hashed with the first algorithm are
+/// stored in the `block_body` database, and the merklized entries derived
+/// from them are compared with the index databases if their root node
+/// exists.
+///
+/// If `rebuild` is set, the divergent entries are overwritten with the ones
+/// derived from the bodies in the `block_body` database, in write
+/// transactions of `REBUILD_BATCH_SIZE` entries committed along the scan.
+/// The divergences of bodies missing from that database are counted as
+/// unrepaired.
+pub(crate) fn check_indices(env: &Environment, rebuild: bool) -> Result<IndexReport, Error> {
+    let txn = env.begin_ro_txn().map_err(Error::IndexDatabase)?;
+    let open_db = |db_name| unsafe { txn.open_db(Some(db_name)).map_err(Error::IndexDatabase) };
+    let header_db = open_db(BlockHeaderDatabase::db_name())?;
+    let dbs = IndexDbs {
+        body: open_db(BlockBodyDatabase::db_name())?,
+        merkle: open_db(BlockBodyMerkleDatabase::db_name())?,
+        deploy_hashes: open_db(DeployHashesDatabase::db_name())?,
+        transfer_hashes: open_db(TransferHashesDatabase::db_name())?,
+        proposers: open_db(ProposerDatabase::db_name())?,
+    };
+
+    let mut maybe_progress_tracker = match lmdb_utils::entry_count(&txn, header_db).ok() {
+        Some(entry_count) => ProgressTracker::new(
+            entry_count,
+            Box::new(|completion| info!("Block body index check {}% complete...", completion)),
+        )
+        .ok(),
+        None => {
+            info!("Skipping progress tracking for block body index check");
+            None
+        }
+    };
+
+    let mut report = IndexReport::default();
+    let mut entries_to_rebuild = vec![];
+    {
+        let mut cursor = txn
+            .open_ro_cursor(header_db)
+            .map_err(Error::IndexDatabase)?;
+        for (idx, (raw_key, raw_value)) in cursor.iter().enumerate() {
+            if let Some(progress_tracker) = maybe_progress_tracker.as_mut() {
+                progress_tracker.advance_by(1);
+            }
+            let block_hash: BlockHash = Digest::try_from(raw_key)
+                .map_err(|_| Error::InvalidHeaderKey(idx))?
+                .into();
+            let block_header: BlockHeader = bincode::deserialize(raw_value)
+                .map_err(|bincode_err| Error::HeaderParsing(block_hash, bincode_err))?;
+            let body_hash = *block_header.body_hash();
+            report.block_count += 1;
+
+            let (divergences, repaired) = match block_header.hashing_algorithm_version() {
+                HashingAlgorithmVersion::V2 => {
+                    let divergences = walk_merkle_body(&txn, &dbs, block_hash, body_hash)?;
+                    let mut repaired = false;
+                    if rebuild && !divergences.is_empty() {
+                        // Only a body merklizing to the same root can
+                        // restore the entries, and the node usually stores
+                        // these bodies merklized only.
+                        match read_body(&txn, &dbs, body_hash)?
+                            .filter(|body| body.hash(HashingAlgorithmVersion::V2) == body_hash)
+                        {
+                            Some(body) => {
+                                let entries = expected_entries(&dbs, &body)?;
+                                entries_to_rebuild.extend(
+                                    divergent_entries(&txn, entries)?
+                                        .into_iter()
+                                        .map(|(entry, _)| entry),
+                                );
+                                repaired = true;
+                            }
+                            None => warn!(
+                                "Can't restore the index entries of body {body_hash} of block \
+                                {block_hash}, the body isn't in the {} database",
+                                BlockBodyDatabase::db_name()
+                            ),
+                        }
+                    }
+                    (divergences, repaired)
+                }
+                HashingAlgorithmVersion::V1 => {
+                    let body = match read_body(&txn, &dbs, body_hash)? {
+                        Some(body) => body,
+                        None => {
+                            warn!("Missing body {body_hash} for block {block_hash}");
+                            report.missing_bodies += 1;
+                            continue;
+                        }
+                    };
+                    // A missing root node means none of the body was
+                    // indexed, as the node only reads these bodies from the
+                    // `block_body` database. Parts may still be present
+                    // since they are shared by all bodies with the same
+                    // values.
+                    let entries = expected_entries(&dbs, &body)?;
+                    match txn.get(dbs.merkle, &entries[0].key) {
+                        Ok(_) => {}
+                        Err(LmdbError::NotFound) => {
+                            report.unindexed_bodies += 1;
+                            continue;
+                        }
+                        Err(lmdb_err) => return Err(Error::IndexDatabase(lmdb_err)),
+                    }
+                    let mut divergences = vec![];
+                    for (entry, kind) in divergent_entries(&txn, entries)? {
+                        divergences.push(IndexDivergence {
+                            block_hash,
+                            body_hash,
+                            db_name: entry.db_name,
+                            key: entry.key,
+                            kind,
+                        });
+                        if rebuild {
+                            entries_to_rebuild.push(entry);
+                        }
+                    }
+                    (divergences, rebuild)
+                }
+            };
+            if !repaired {
+                report.unrepaired_divergences += divergences.len();
+            }
+            for divergence in divergences {
+                warn!(
+                    "{:?} entry {} in {} database for body {} of block {}",
+                    divergence.kind,
+                    divergence.key,
+                    divergence.db_name,
+                    divergence.body_hash,
+                    divergence.block_hash
+                );
+                report.divergences.push(divergence);
+            }
+            // The environment is opened without thread-local storage, so a
+            // write transaction can be committed while scanning the headers.
+            if entries_to_rebuild.len() >= REBUILD_BATCH_SIZE {
+                rebuild_entries(env, &mut entries_to_rebuild, &mut report)?;
+                info!("Rebuilt {} index entries so far.", report.rebuilt_entries);
+            }
+        }
+    }
+    txn.commit().map_err(Error::IndexDatabase)?;
+
+    if !entries_to_rebuild.is_empty() {
+        rebuild_entries(env, &mut entries_to_rebuild, &mut report)?;
+    }
+    Ok(report)
+}
+
+/// Writes the index report in JSON format.
+pub(crate) fn dump_index_report<W: Write + ?Sized>(
+    report: &IndexReport,
+    out_writer: Box<W>,
+) -> Result<(), Error> {
+    serde_json::to_writer_pretty(out_writer, report).map_err(Error::ReportSerialize)
+}
@@ -0,0 +1,589 @@
+use std::fs;
+
+use casper_hashing::Digest;
+use casper_node::types::{BlockBody, BlockHash, HashingAlgorithmVersion, MerkleBlockBodyPart};
+use casper_types::{bytesrepr::ToBytes, EraId, ProtocolVersion};
+use lmdb::{Error as LmdbError, Transaction, WriteFlags};
+
+use crate::{
+    common::db::{
+        BlockBodyDatabase, BlockBodyMerkleDatabase, BlockHeaderDatabase, Database,
+        DeployHashesDatabase, ProposerDatabase, TransferHashesDatabase, STORAGE_FILE_NAME,
+    },
+    subcommands::execution_results_summary::block_body::BlockBody as MockBlockBody,
+    test_utils::{self, LmdbTestFixture, MockBlockHeader, MockSwitchBlockHeader},
+};
+
+use super::{
+    chain::{self, BrokenLink, EraRegression, Fork, Gap, MissedEraTransition},
+    indices::{self, DivergenceKind, IndexDivergence},
+    Error,
+};
+
+fn block_hash(idx: u8) -> BlockHash {
+    Digest::from([idx; Digest::LENGTH]).into()
+}
+
+/// Inserts a header with the hash derived from `idx` into the header
+/// database of the fixture.
+fn insert_header(
+    fixture: &LmdbTestFixture,
+    idx: u8,
+    height: u64,
+    era_id: u64,
+    parent_idx: u8,
+    switch_block: bool,
+) {
+    let serialized = if switch_block {
+        let header = MockSwitchBlockHeader {
+            height,
+            era_id: EraId::new(era_id),
+            parent_hash: block_hash(parent_idx),
+            ..Default::default()
+        };
+        bincode::serialize(&header).unwrap()
+    } else {
+        let header = MockBlockHeader {
+            height,
+            era_id: EraId::new(era_id),
+            parent_hash: block_hash(parent_idx),
+            ..Default::default()
+        };
+        bincode::serialize(&header).unwrap()
+    };
+    let db = fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap();
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    txn.put(*db, &block_hash(idx), &serialized, WriteFlags::empty())
+        .unwrap();
+    txn.commit().unwrap();
+}
+
+#[test]
+fn continuous_chain_should_pass() {
+    let fixture = LmdbTestFixture::new(vec![BlockHeaderDatabase::db_name()], None);
+    // Era 0 with heights 0 to 2, era 1 with heights 3 to 5, the last era
+    // still being in progress.
+    insert_header(&fixture, 0, 0, 0, 0, false);
+    insert_header(&fixture, 1, 1, 0, 0, false);
+    insert_header(&fixture, 2, 2, 0, 1, true);
+    insert_header(&fixture, 3, 3, 1, 2, false);
+    insert_header(&fixture, 4, 4, 1, 3, false);
+    insert_header(&fixture, 5, 5, 1, 4, false);
+
+    let report = chain::check_chain(&fixture.env).unwrap();
+    assert_eq!(report.block_count, 6);
+    assert_eq!(report.lowest_height, Some(0));
+    assert_eq!(report.highest_height, Some(5));
+    assert_eq!(report.issue_count(), 0);
+}
+
+#[test]
+fn empty_chain_should_pass() {
+    let fixture = LmdbTestFixture::new(vec![BlockHeaderDatabase::db_name()], None);
+    let report = chain::check_chain(&fixture.env).unwrap();
+    assert_eq!(report.block_count, 0);
+    assert_eq!(report.lowest_height, None);
+    assert_eq!(report.issue_count(), 0);
+}
+
+#[test]
+fn chain_issues_should_be_reported() {
+    let fixture = LmdbTestFixture::new(vec![BlockHeaderDatabase::db_name()], None);
+    insert_header(&fixture, 0, 0, 0, 0, false);
+    insert_header(&fixture, 1, 1, 0, 0, false);
+    // Fork at height 2, both blocks with a valid parent.
+    insert_header(&fixture, 2, 2, 0, 1, false);
+    insert_header(&fixture, 20, 2, 0, 1, false);
+    // Era 0 is never closed by a switch block.
+    insert_header(&fixture, 3, 3, 1, 2, false);
+    // Broken link at height 4.
+    insert_header(&fixture, 4, 4, 1, 30, true);
+    // Gap for heights 5 to 6.
+    insert_header(&fixture, 7, 7, 2, 6, false);
+    // Era regression at height 8.
+    insert_header(&fixture, 8, 8, 1, 7, false);
+
+    let report = chain::check_chain(&fixture.env).unwrap();
+    assert_eq!(report.block_count, 8);
+    assert_eq!(report.lowest_height, Some(0));
+    assert_eq!(report.highest_height, Some(8));
+    assert_eq!(
+        report.gaps,
+        vec![Gap {
+            first_missing_height: 5,
+            last_missing_height: 6
+        }]
+    );
+    assert_eq!(
+        report.forks,
+        vec![Fork {
+            height: 2,
+            block_hashes: vec![block_hash(2), block_hash(20)]
+        }]
+    );
+    assert_eq!(
+        report.broken_links,
+        vec![BrokenLink {
+            height: 4,
+            block_hash: block_hash(4),
+            parent_hash: block_hash(30)
+        }]
+    );
+    assert_eq!(
+        report.era_regressions,
+        vec![EraRegression {
+            height: 8,
+            block_hash: block_hash(8),
+            era_id: EraId::new(1),
+            parent_era_id: EraId::new(2)
+        }]
+    );
+    assert_eq!(report.unclosed_eras, vec![EraId::new(0)]);
+    assert_eq!(report.issue_count(), 5);
+}
+
+#[test]
+fn missed_era_transitions_should_be_reported() {
+    let fixture = LmdbTestFixture::new(vec![BlockHeaderDatabase::db_name()], None);
+    insert_header(&fixture, 0, 0, 0, 0, false);
+    insert_header(&fixture, 1, 1, 0, 0, true);
+    // The block after the switch block of era 0 stays in era 0.
+    insert_header(&fixture, 2, 2, 0, 1, true);
+    // The block after the switch block of era 0 skips era 1.
+    insert_header(&fixture, 3, 3, 2, 2, true);
+    insert_header(&fixture, 4, 4, 3, 3, false);
+
+    let report = chain::check_chain(&fixture.env).unwrap();
+    assert_eq!(
+        report.missed_era_transitions,
+        vec![
+            MissedEraTransition {
+                height: 2,
+                block_hash: block_hash(2),
+                era_id: EraId::new(0),
+                expected_era_id: EraId::new(1)
+            },
+            MissedEraTransition {
+                height: 3,
+                block_hash: block_hash(3),
+                era_id: EraId::new(2),
+                expected_era_id: EraId::new(1)
+            }
+        ]
+    );
+    assert!(report.era_regressions.is_empty());
+    assert_eq!(report.issue_count(), 2);
+}
+
+#[test]
+fn chain_check_should_output_report() {
+    let fixture = LmdbTestFixture::new(
+        vec![BlockHeaderDatabase::db_name()],
+        Some(STORAGE_FILE_NAME),
+    );
+    insert_header(&fixture, 0, 0, 0, 0, false);
+    insert_header(&fixture, 1, 1, 0, 0, false);
+    let out_path = fixture.tmp_dir.path().join("report.json");
+    assert!(super::check_chain(fixture.tmp_dir.path(), Some(&out_path), false).is_ok());
+    let report: serde_json::Value = serde_json::from_slice(&fs::read(&out_path).unwrap()).unwrap();
+    assert_eq!(report["block_count"], 2);
+
+    // Existing output shouldn't be overwritten unless requested.
+    assert!(matches!(
+        super::check_chain(fixture.tmp_dir.path(), Some(&out_path), false),
+        Err(Error::Output(_))
+    ));
+
+    insert_header(&fixture, 3, 3, 0, 2, false);
+    assert!(matches!(
+        super::check_chain(fixture.tmp_dir.path(), Some(&out_path), true),
+        Err(Error::ChainDiscontinuity(1))
+    ));
+    let report: serde_json::Value = serde_json::from_slice(&fs::read(&out_path).unwrap()).unwrap();
+    assert_eq!(report["gaps"][0]["first_missing_height"], 2);
+}
+
+#[test]
+fn unknown_dbs_should_be_discovered() {
+    let fixture = LmdbTestFixture::new(vec!["unknown_db"], Some(STORAGE_FILE_NAME));
+    let db = fixture.db(Some("unknown_db")).unwrap();
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    txn.put(*db, &[0u8; 3], &[1u8; 5], WriteFlags::empty())
+        .unwrap();
+    txn.commit().unwrap();
+
+    for scan_unknown in [false, true] {
+        assert!(super::check_db(
+            fixture.tmp_dir.path(),
+            true,
+            Some("unknown_db"),
+            0,
+            None,
+            false,
+            scan_unknown
+        )
+        .is_ok());
+    }
+    assert!(matches!(
+        super::check_db(
+            fixture.tmp_dir.path(),
+            true,
+            Some("missing_db"),
+            0,
+            None,
+            false,
+            true
+        ),
+        Err(Error::UnknownDb(_))
+    ));
+}
+
+/// Protocol version of blocks hashed with the first hashing algorithm.
+const V1_PROTOCOL_VERSION: ProtocolVersion = ProtocolVersion::V1_0_0;
+/// Protocol version of blocks hashed with the second hashing algorithm.
+const V2_PROTOCOL_VERSION: ProtocolVersion = ProtocolVersion::from_parts(9001, 0, 0);
+
+fn index_db_names() -> Vec<&'static str> {
+    vec![
+        BlockHeaderDatabase::db_name(),
+        BlockBodyDatabase::db_name(),
+        BlockBodyMerkleDatabase::db_name(),
+        DeployHashesDatabase::db_name(),
+        TransferHashesDatabase::db_name(),
+        ProposerDatabase::db_name(),
+    ]
+}
+
+/// Returns a body with a deploy and a transfer derived from `idx`.
+fn mock_body(idx: u8) -> BlockBody {
+    let mut body = MockBlockBody::new(vec![test_utils::mock_deploy_hash(idx)]);
+    body.transfer_hashes = vec![test_utils::mock_deploy_hash(idx.wrapping_add(100))];
+    bincode::deserialize(&bincode::serialize(&body).unwrap()).unwrap()
+}
+
+/// Puts a merklized part of a block body in the index databases, the same
+/// way the node does.
+fn put_body_part<T: ToBytes>(
+    fixture: &LmdbTestFixture,
+    part_db_name: &str,
+    part: &MerkleBlockBodyPart<T>,
+) {
+    let merkle_db = fixture
+        .db(Some(BlockBodyMerkleDatabase::db_name()))
+        .unwrap();
+    let part_db = fixture.db(Some(part_db_name)).unwrap();
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    txn.put(
+        *merkle_db,
+        part.merkle_linked_list_node_hash(),
+        &part.value_and_rest_hashes_pair().to_bytes().unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.put(
+        *part_db,
+        part.value_hash(),
+        &part.value().to_bytes().unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.commit().unwrap();
+}
+
+/// Puts all the merklized parts of a block body in the index databases.
+fn put_merkle_body(fixture: &LmdbTestFixture, body: &BlockBody) {
+    let merkle_body = body.merklize();
+    put_body_part(
+        fixture,
+        DeployHashesDatabase::db_name(),
+        &merkle_body.deploy_hashes,
+    );
+    put_body_part(
+        fixture,
+        TransferHashesDatabase::db_name(),
+        &merkle_body.transfer_hashes,
+    );
+    put_body_part(fixture, ProposerDatabase::db_name(), &merkle_body.proposer);
+}
+
+/// Puts a block body in the `block_body` database under `body_hash`.
+fn put_body(fixture: &LmdbTestFixture, body_hash: Digest, body: &BlockBody) {
+    let db = fixture.db(Some(BlockBodyDatabase::db_name())).unwrap();
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    txn.put(
+        *db,
+        &body_hash,
+        &bincode::serialize(body).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.commit().unwrap();
+}
+
+/// Inserts a header with the hash derived from `idx` referring to `body`,
+/// and stores the body the way the node does for the hashing algorithm of
+/// `protocol_version`: in the `block_body` database for the first one,
+/// merklized for the second one. Returns the body hash.
+fn insert_block(
+    fixture: &LmdbTestFixture,
+    idx: u8,
+    body: &BlockBody,
+    protocol_version: ProtocolVersion,
+) -> Digest {
+    let body_hash = if protocol_version == V2_PROTOCOL_VERSION {
+        put_merkle_body(fixture, body);
+        body.hash(HashingAlgorithmVersion::V2)
+    } else {
+        let body_hash = body.hash(HashingAlgorithmVersion::V1);
+        put_body(fixture, body_hash, body);
+        body_hash
+    };
+    let header = MockBlockHeader {
+        height: idx.into(),
+        body_hash,
+        protocol_version,
+        ..Default::default()
+    };
+    let db = fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap();
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    txn.put(
+        *db,
+        &block_hash(idx),
+        &bincode::serialize(&header).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.commit().unwrap();
+    body_hash
+}
+
+#[test]
+fn consistent_indices_should_pass() {
+    let fixture = LmdbTestFixture::new(index_db_names(), None);
+    // Indexed and unindexed bodies of blocks hashed with the first
+    // algorithm.
+    let body = mock_body(1);
+    insert_block(&fixture, 1, &body, V1_PROTOCOL_VERSION);
+    put_merkle_body(&fixture, &body);
+    insert_block(&fixture, 2, &mock_body(2), V1_PROTOCOL_VERSION);
+    // Merklized body of a block hashed with the second algorithm.
+    insert_block(&fixture, 3, &mock_body(3), V2_PROTOCOL_VERSION);
+
+    let report = indices::check_indices(&fixture.env, false).unwrap();
+    assert_eq!(report.block_count, 3);
+    assert_eq!(report.missing_bodies, 0);
+    assert_eq!(report.unindexed_bodies, 1);
+    assert!(report.divergences.is_empty());
+    assert_eq!(report.unrepaired_divergences, 0);
+    assert_eq!(report.rebuilt_entries, 0);
+}
+
+#[test]
+fn divergent_indices_should_be_reported_and_rebuilt() {
+    let fixture = LmdbTestFixture::new(index_db_names(), Some(STORAGE_FILE_NAME));
+    insert_block(&fixture, 1, &mock_body(1), V1_PROTOCOL_VERSION);
+    let body = mock_body(2);
+    let body_hash = insert_block(&fixture, 2, &body, V1_PROTOCOL_VERSION);
+    put_merkle_body(&fixture, &body);
+    let merkle_body = body.merklize();
+    let deploy_key = *merkle_body.deploy_hashes.value_hash();
+    let transfer_key = *merkle_body.transfer_hashes.value_hash();
+
+    // Replace the deploy hashes of the body and remove its transfer hashes.
+    let deploy_hashes_db = fixture.db(Some(DeployHashesDatabase::db_name())).unwrap();
+    let transfer_hashes_db = fixture.db(Some(TransferHashesDatabase::db_name())).unwrap();
+    let tampered_deploy_hashes = vec![test_utils::mock_deploy_hash(3)].to_bytes().unwrap();
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    txn.put(
+        *deploy_hashes_db,
+        &deploy_key,
+        &tampered_deploy_hashes,
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.del(*transfer_hashes_db, &transfer_key, None).unwrap();
+    txn.commit().unwrap();
+
+    let expected_divergences = vec![
+        IndexDivergence {
+            block_hash: block_hash(2),
+            body_hash,
+            db_name: DeployHashesDatabase::db_name(),
+            key: deploy_key,
+            kind: DivergenceKind::Mismatch,
+        },
+        IndexDivergence {
+            block_hash: block_hash(2),
+            body_hash,
+            db_name: TransferHashesDatabase::db_name(),
+            key: transfer_key,
+            kind: DivergenceKind::Missing,
+        },
+    ];
+    let out_path = fixture.tmp_dir.path().join("report.json");
+    assert!(matches!(
+        super::check_indices(fixture.tmp_dir.path(), Some(&out_path), false, false),
+        Err(Error::IndexDivergence(2))
+    ));
+    let report: serde_json::Value = serde_json::from_slice(&fs::read(&out_path).unwrap()).unwrap();
+    assert_eq!(report["divergences"][1]["kind"], "missing");
+    let report = indices::check_indices(&fixture.env, false).unwrap();
+    assert_eq!(report.unindexed_bodies, 1);
+    assert_eq!(report.divergences, expected_divergences);
+
+    assert_eq!(report.unrepaired_divergences, 2);
+
+    // Rebuilding restores the entries derived from the body.
+    let report = indices::check_indices(&fixture.env, true).unwrap();
+    assert_eq!(report.divergences, expected_divergences);
+    assert_eq!(report.unrepaired_divergences, 0);
+    assert_eq!(report.rebuilt_entries, 2);
+    let txn = fixture.env.begin_ro_txn().unwrap();
+    assert_eq!(
+        txn.get(*deploy_hashes_db, &deploy_key).unwrap(),
+        vec![test_utils::mock_deploy_hash(2)].to_bytes().unwrap()
+    );
+    assert!(!matches!(
+        txn.get(*transfer_hashes_db, &transfer_key),
+        Err(LmdbError::NotFound)
+    ));
+    txn.commit().unwrap();
+    let report = indices::check_indices(&fixture.env, false).unwrap();
+    assert!(report.divergences.is_empty());
+    assert!(super::check_indices(fixture.tmp_dir.path(), Some(&out_path), true, false).is_ok());
+}
+
+#[test]
+fn merkle_bodies_should_be_followed_from_headers() {
+    let fixture = LmdbTestFixture::new(index_db_names(), None);
+    let merkle_db = fixture
+        .db(Some(BlockBodyMerkleDatabase::db_name()))
+        .unwrap();
+    let deploy_hashes_db = fixture.db(Some(DeployHashesDatabase::db_name())).unwrap();
+
+    // The deploy hashes of the first body don't hash to their key.
+    let body_1 = mock_body(1);
+    let body_hash_1 = insert_block(&fixture, 1, &body_1, V2_PROTOCOL_VERSION);
+    let deploy_key = *body_1.merklize().deploy_hashes.value_hash();
+    // The node linking the deploy hashes of the second body to its
+    // transfer hashes is missing.
+    let body_2 = mock_body(2);
+    let body_hash_2 = insert_block(&fixture, 2, &body_2, V2_PROTOCOL_VERSION);
+    let transfer_node_key = *body_2
+        .merklize()
+        .transfer_hashes
+        .merkle_linked_list_node_hash();
+    // The root node of the third body doesn't hash to its key.
+    let body_hash_3 = insert_block(&fixture, 3, &mock_body(3), V2_PROTOCOL_VERSION);
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    txn.put(
+        *deploy_hashes_db,
+        &deploy_key,
+        &vec![test_utils::mock_deploy_hash(5)].to_bytes().unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.del(*merkle_db, &transfer_node_key, None).unwrap();
+    txn.put(
+        *merkle_db,
+        &body_hash_3,
+        &(deploy_key, Digest::SENTINEL_RFOLD).to_bytes().unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.commit().unwrap();
+    // The fourth body was never merklized, although it's in the
+    // `block_body` database.
+    let body_4 = mock_body(4);
+    let body_hash_4 = body_4.hash(HashingAlgorithmVersion::V2);
+    put_body(&fixture, body_hash_4, &body_4);
+    let header = MockBlockHeader {
+        height: 4,
+        body_hash: body_hash_4,
+        protocol_version: V2_PROTOCOL_VERSION,
+        ..Default::default()
+    };
+    let header_db = fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap();
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    txn.put(
+        *header_db,
+        &block_hash(4),
+        &bincode::serialize(&header).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.commit().unwrap();
+
+    let merkle_db_name = BlockBodyMerkleDatabase::db_name();
+    let expected_divergences = vec![
+        IndexDivergence {
+            block_hash: block_hash(1),
+            body_hash: body_hash_1,
+            db_name: DeployHashesDatabase::db_name(),
+            key: deploy_key,
+            kind: DivergenceKind::Corrupt,
+        },
+        IndexDivergence {
+            block_hash: block_hash(2),
+            body_hash: body_hash_2,
+            db_name: merkle_db_name,
+            key: transfer_node_key,
+            kind: DivergenceKind::Missing,
+        },
+        IndexDivergence {
+            block_hash: block_hash(3),
+            body_hash: body_hash_3,
+            db_name: merkle_db_name,
+            key: body_hash_3,
+            kind: DivergenceKind::Corrupt,
+        },
+        IndexDivergence {
+            block_hash: block_hash(4),
+            body_hash: body_hash_4,
+            db_name: merkle_db_name,
+            key: body_hash_4,
+            kind: DivergenceKind::Missing,
+        },
+    ];
+    let report = indices::check_indices(&fixture.env, false).unwrap();
+    assert_eq!(report.block_count, 4);
+    assert_eq!(report.divergences, expected_divergences);
+
+    // Only the body found in the `block_body` database can be restored.
+    let report = indices::check_indices(&fixture.env, true).unwrap();
+    assert_eq!(report.divergences, expected_divergences);
+    assert_eq!(report.unrepaired_divergences, 3);
+    // The proposer entries are shared with the other bodies.
+    assert_eq!(report.rebuilt_entries, 4);
+    let report = indices::check_indices(&fixture.env, false).unwrap();
+    assert_eq!(report.divergences, expected_divergences[..3]);
+}
+
+#[test]
+fn rebuild_should_fail_on_merkle_bodies_without_block_body() {
+    let fixture = LmdbTestFixture::new(index_db_names(), Some(STORAGE_FILE_NAME));
+    let merkle_db = fixture
+        .db(Some(BlockBodyMerkleDatabase::db_name()))
+        .unwrap();
+    // The body is only stored merklized, like the node does for blocks
+    // hashed with the second algorithm.
+    let body = mock_body(1);
+    insert_block(&fixture, 1, &body, V2_PROTOCOL_VERSION);
+    let transfer_node_key = *body
+        .merklize()
+        .transfer_hashes
+        .merkle_linked_list_node_hash();
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    txn.del(*merkle_db, &transfer_node_key, None).unwrap();
+    txn.commit().unwrap();
+
+    let out_path = fixture.tmp_dir.path().join("report.json");
+    assert!(matches!(
+        super::check_indices(fixture.tmp_dir.path(), Some(&out_path), false, true),
+        Err(Error::IndexDivergence(1))
+    ));
+    let report = indices::check_indices(&fixture.env, true).unwrap();
+    assert_eq!(report.divergences.len(), 1);
+    assert_eq!(report.unrepaired_divergences, 1);
+    assert_eq!(report.rebuilt_entries, 0);
+}
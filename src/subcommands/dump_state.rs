@@ -0,0 +1,221 @@
+mod dump;
+mod state_root;
+#[cfg(test)]
+mod tests;
+
+use std::{io::Error as IoError, path::Path};
+
+use anyhow::Error as AnyError;
+use clap::{Arg, ArgMatches, Command};
+use serde_json::Error as JsonSerializationError;
+use thiserror::Error as ThisError;
+
+use casper_hashing::Digest;
+use casper_types::{bytesrepr::Error as BytesreprError, Key};
+
+pub(crate) use state_root::{Error as StateRootError, StateRootSelector};
+
+use super::trie_compact::DEFAULT_MAX_DB_SIZE;
+use crate::common::trie_walk::Error as TrieWalkError;
+
+pub const COMMAND_NAME: &str = "dump-state";
+const BLOCK_HASH: &str = "block-hash";
+const BLOCK_HEIGHT: &str = "block-height";
+const KEY_TYPE: &str = "key-type";
+const MAX_DB_SIZE: &str = "max-db-size";
+const OUTPUT: &str = "output";
+const OVERWRITE: &str = "overwrite";
+const STATE_ROOT_HASH: &str = "state-root-hash";
+pub(crate) const STORAGE_PATH: &str = "storage-path";
+const TRIE_STORE_PATH: &str = "trie";
+
+/// Errors encountered when dumping the global state.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error("Error serializing output: {0}")]
+    JsonSerialize(#[from] JsonSerializationError),
+    /// Error opening the trie store.
+    #[error("Error opening the trie store: {0}")]
+    OpenTrie(AnyError),
+    #[error("Error writing output: {0}")]
+    Output(#[from] IoError),
+    /// Error finding the state root to dump.
+    #[error("Error finding the state root: {0}")]
+    StateRoot(#[from] StateRootError),
+    /// Error converting the stored value under the given key to JSON.
+    #[error("Error converting the value under {} to JSON: {1}", .0.to_formatted_string())]
+    ValueConversion(Key, BytesreprError),
+    /// Error walking the tries under the state root.
+    #[error("Error walking the tries: {0}")]
+    Walk(#[from] TrieWalkError),
+}
+
+enum DisplayOrder {
+    TriePath,
+    StateRoot,
+    KeyType,
+    MaxDbSize,
+    Output,
+    Overwrite,
+}
+
+/// Arguments selecting a state root, directly or through a block in
+/// storage.
+pub(crate) fn state_root_args(display_order: usize) -> [Arg<'static>; 4] {
+    [
+        Arg::new(STATE_ROOT_HASH)
+            .display_order(display_order)
+            .required_unless_present_any([BLOCK_HASH, BLOCK_HEIGHT])
+            .conflicts_with_all(&[BLOCK_HASH, BLOCK_HEIGHT])
+            .short('s')
+            .long(STATE_ROOT_HASH)
+            .takes_value(true)
+            .value_name("STATE_ROOT_HASH")
+            .help("Hex encoded state root hash of the global state."),
+        Arg::new(BLOCK_HASH)
+            .display_order(display_order)
+            .conflicts_with(BLOCK_HEIGHT)
+            .requires(STORAGE_PATH)
+            .long(BLOCK_HASH)
+            .takes_value(true)
+            .value_name("BLOCK_HASH")
+            .help("Hex encoded hash of the block whose global state is used."),
+        Arg::new(BLOCK_HEIGHT)
+            .display_order(display_order)
+            .requires(STORAGE_PATH)
+            .long(BLOCK_HEIGHT)
+            .takes_value(true)
+            .value_name("HEIGHT")
+            .help("Height of the block whose global state is used."),
+        Arg::new(STORAGE_PATH)
+            .display_order(display_order)
+            .short('b')
+            .long(STORAGE_PATH)
+            .takes_value(true)
+            .value_name("STORAGE_DIR_PATH")
+            .help(
+                "Path of the directory with the `storage.lmdb` file. Used to find the state \
+                root hash of the block given by `--block-hash` or `--block-height`.",
+            ),
+    ]
+}
+
+/// Returns the state root hash selected by the [`state_root_args`],
+/// looking it up in storage if needed.
+pub(crate) fn resolve_state_root(matches: &ArgMatches) -> Result<Digest, StateRootError> {
+    let selector = if let Some(state_root_hash_str) = matches.value_of(STATE_ROOT_HASH) {
+        StateRootSelector::StateRootHash(
+            Digest::from_hex(state_root_hash_str)
+                .expect("should parse state root hash to hex format"),
+        )
+    } else if let Some(block_hash_str) = matches.value_of(BLOCK_HASH) {
+        StateRootSelector::BlockHash(
+            Digest::from_hex(block_hash_str)
+                .expect("should parse block hash to hex format")
+                .into(),
+        )
+    } else {
+        StateRootSelector::BlockHeight(
+            matches
+                .value_of(BLOCK_HEIGHT)
+                .expect("should have either state-root-hash, block-hash or block-height arg")
+                .parse()
+                .expect("Value of \"--block-height\" must be an integer."),
+        )
+    };
+    selector.resolve(matches.value_of(STORAGE_PATH))
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Writes the keys and values of the global state under a state root as one JSON \
+            object per line.",
+        )
+        .arg(
+            Arg::new(TRIE_STORE_PATH)
+                .display_order(DisplayOrder::TriePath as usize)
+                .required(true)
+                .short('t')
+                .long(TRIE_STORE_PATH)
+                .takes_value(true)
+                .value_name("TRIE_STORE_DIR_PATH")
+                .help("Path of the directory with the `data.lmdb` file."),
+        )
+        .args(state_root_args(DisplayOrder::StateRoot as usize))
+        .arg(
+            Arg::new(KEY_TYPE)
+                .display_order(DisplayOrder::KeyType as usize)
+                .short('k')
+                .long(KEY_TYPE)
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .possible_values(dump::KEY_TYPES)
+                .value_name("KEY_TYPE")
+                .help(
+                    "Only dump the entries with keys of this type. Can be given several times. \
+                    If unspecified, all entries are dumped.",
+                ),
+        )
+        .arg(
+            Arg::new(MAX_DB_SIZE)
+                .display_order(DisplayOrder::MaxDbSize as usize)
+                .required(false)
+                .short('m')
+                .long(MAX_DB_SIZE)
+                .takes_value(true)
+                .default_value(DEFAULT_MAX_DB_SIZE)
+                .value_name("MAX_DB_SIZE")
+                .help("Maximum size the DB files are allowed to be, in bytes."),
+        )
+        .arg(
+            Arg::new(OUTPUT)
+                .display_order(DisplayOrder::Output as usize)
+                .short('o')
+                .long(OUTPUT)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help(
+                    "Path to where the program will output the entries. \
+                    If unspecified, defaults to standard output.",
+                ),
+        )
+        .arg(
+            Arg::new(OVERWRITE)
+                .display_order(DisplayOrder::Overwrite as usize)
+                .required(false)
+                .short('w')
+                .long(OVERWRITE)
+                .takes_value(false)
+                .requires(OUTPUT)
+                .help(
+                    "Overwrite an already existing output file in destination \
+                    directory.",
+                ),
+        )
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let trie_path = Path::new(matches.value_of(TRIE_STORE_PATH).unwrap());
+    let state_root_hash = resolve_state_root(matches)?;
+    let key_types: Vec<String> = matches
+        .values_of(KEY_TYPE)
+        .map(|key_types| key_types.map(str::to_string).collect())
+        .unwrap_or_default();
+    let max_db_size = matches
+        .value_of(MAX_DB_SIZE)
+        .unwrap()
+        .parse()
+        .expect("Value of \"--max-db-size\" must be an integer.");
+    let output = matches.value_of(OUTPUT).map(Path::new);
+    let overwrite = matches.is_present(OVERWRITE);
+    dump::dump_state(
+        trie_path,
+        max_db_size,
+        state_root_hash,
+        &key_types,
+        output,
+        overwrite,
+    )
+}
@@ -0,0 +1,139 @@
+use std::{
+    convert::TryFrom,
+    fs::OpenOptions,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use casper_execution_engine::storage::trie::Trie;
+use casper_hashing::Digest;
+use casper_node::types::json_compatibility::StoredValue as JsonStoredValue;
+use casper_types::Key;
+
+use crate::{common::trie_walk, subcommands::trie_compact::load_execution_engine};
+
+use super::Error;
+
+/// Names of the key types the dump can be limited to. Each is the prefix of
+/// the formatted string of the keys of that type.
+pub(crate) const KEY_TYPES: [&str; 12] = [
+    "account-hash",
+    "hash",
+    "uref",
+    "transfer",
+    "deploy",
+    "era",
+    "balance",
+    "bid",
+    "withdraw",
+    "dictionary",
+    "system-contract-registry",
+    "era-summary",
+];
+
+/// Returns the name of the type of `key`, one of [`KEY_TYPES`].
+pub(crate) fn key_type(key: &Key) -> &'static str {
+    match key {
+        Key::Account(_) => "account-hash",
+        Key::Hash(_) => "hash",
+        Key::URef(_) => "uref",
+        Key::Transfer(_) => "transfer",
+        Key::DeployInfo(_) => "deploy",
+        Key::EraInfo(_) => "era",
+        Key::Balance(_) => "balance",
+        Key::Bid(_) => "bid",
+        Key::Withdraw(_) => "withdraw",
+        Key::Dictionary(_) => "dictionary",
+        Key::SystemContractRegistry => "system-contract-registry",
+        Key::EraSummary => "era-summary",
+    }
+}
+
+/// A global state entry, as written on each line of the dump.
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct DumpedEntry {
+    /// Formatted string of the key.
+    pub(crate) key: String,
+    /// JSON representation of the stored value.
+    pub(crate) value: JsonStoredValue,
+}
+
+/// Writes every leaf under `state_root_hash` whose key type is in
+/// `key_types`, or every leaf if `key_types` is empty, to `out_writer` as
+/// one JSON object per line. Returns the number of written leaves.
+pub(crate) fn dump_leaves<P: AsRef<Path>, W: Write + ?Sized>(
+    trie_path: P,
+    max_db_size: usize,
+    state_root_hash: Digest,
+    key_types: &[String],
+    out_writer: &mut W,
+) -> Result<usize, Error> {
+    let (engine_state, _env) =
+        load_execution_engine(trie_path, max_db_size, Digest::default(), true)
+            .map_err(Error::OpenTrie)?;
+
+    let mut written_leaves = 0usize;
+    trie_walk::walk_tries(&engine_state, state_root_hash, |trie, _depth| {
+        let (key, value) = match trie {
+            Trie::Leaf { key, value } => (key, value),
+            _ => return Ok(()),
+        };
+        if !key_types.is_empty() && !key_types.iter().any(|name| name == key_type(key)) {
+            return Ok(());
+        }
+        let entry = DumpedEntry {
+            key: key.to_formatted_string(),
+            value: JsonStoredValue::try_from(value.clone())
+                .map_err(|bytesrepr_err| Error::ValueConversion(*key, bytesrepr_err))?,
+        };
+        serde_json::to_writer(&mut *out_writer, &entry)?;
+        writeln!(out_writer)?;
+        written_leaves += 1;
+        Ok::<_, Error>(())
+    })?;
+    out_writer.flush()?;
+    Ok(written_leaves)
+}
+
+pub fn dump_state<P1: AsRef<Path>, P2: AsRef<Path>>(
+    trie_path: P1,
+    max_db_size: usize,
+    state_root_hash: Digest,
+    key_types: &[String],
+    output: Option<P2>,
+    overwrite: bool,
+) -> Result<(), Error> {
+    // Validate the output file early so that, in case this fails
+    // we don't unnecessarily walk the whole trie. Nothing is logged when
+    // dumping to standard output, so the output stays valid NDJSON.
+    let mut log_progress = false;
+    let mut out_writer: Box<dyn Write> = if let Some(out_path) = output {
+        let file = OpenOptions::new()
+            .create_new(!overwrite)
+            .write(true)
+            .truncate(true)
+            .open(out_path)?;
+        log_progress = true;
+        Box::new(BufWriter::new(file))
+    } else {
+        Box::new(BufWriter::new(io::stdout()))
+    };
+
+    if log_progress {
+        info!("Dumping the global state under state root {state_root_hash}.");
+    }
+    let written_leaves = dump_leaves(
+        trie_path,
+        max_db_size,
+        state_root_hash,
+        key_types,
+        &mut out_writer,
+    )?;
+    if log_progress {
+        info!("Wrote {written_leaves} entries.");
+    }
+    Ok(())
+}
@@ -0,0 +1,64 @@
+use std::path::Path;
+
+use anyhow::Error as AnyError;
+use thiserror::Error as ThisError;
+
+use casper_hashing::Digest;
+use casper_node::{
+    storage::{Error as StorageError, Storage},
+    types::BlockHash,
+};
+
+use crate::subcommands::trie_compact::create_storage;
+
+/// Errors encountered when resolving a state root from storage.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// No block with the given hash in storage.
+    #[error("Storage database is missing block {0}")]
+    MissingBlockHash(BlockHash),
+    /// No block at the given height in storage.
+    #[error("Storage database is missing block at height {0}")]
+    MissingBlockHeight(u64),
+    /// The block to look up needs the storage, which wasn't given.
+    #[error("A storage path is needed to look up blocks")]
+    NoStoragePath,
+    /// Error opening the block/deploys LMDB store.
+    #[error("Error opening the block/deploy storage: {0}")]
+    OpenStorage(AnyError),
+    /// Error reading a block from storage.
+    #[error("Storage error while trying to retrieve block: {0}")]
+    Storage(#[from] StorageError),
+}
+
+/// Identifies the state root to operate on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StateRootSelector {
+    /// The state root with the given hash.
+    StateRootHash(Digest),
+    /// The state root of the block with the given hash.
+    BlockHash(BlockHash),
+    /// The state root of the block at the given height.
+    BlockHeight(u64),
+}
+
+impl StateRootSelector {
+    /// Returns the selected state root hash, looking the block up in the
+    /// storage at `storage_path` if needed.
+    pub fn resolve<P: AsRef<Path>>(&self, storage_path: Option<P>) -> Result<Digest, Error> {
+        let block = match self {
+            StateRootSelector::StateRootHash(state_root_hash) => return Ok(*state_root_hash),
+            StateRootSelector::BlockHash(block_hash) => open_storage(storage_path)?
+                .read_block(block_hash)?
+                .ok_or(Error::MissingBlockHash(*block_hash))?,
+            StateRootSelector::BlockHeight(height) => open_storage(storage_path)?
+                .read_block_by_height(*height)?
+                .ok_or(Error::MissingBlockHeight(*height))?,
+        };
+        Ok(*block.take_header().state_root_hash())
+    }
+}
+
+fn open_storage<P: AsRef<Path>>(storage_path: Option<P>) -> Result<Storage, Error> {
+    create_storage(storage_path.ok_or(Error::NoStoragePath)?).map_err(Error::OpenStorage)
+}
@@ -0,0 +1,154 @@
+use std::{collections::HashMap, convert::TryFrom, path::Path};
+
+use casper_hashing::Digest;
+use casper_node::types::json_compatibility::StoredValue as JsonStoredValue;
+use casper_types::{
+    account::AccountHash, AccessRights, CLValue, ContractWasm, Key, StoredValue, URef, U512,
+};
+
+use crate::subcommands::trie_compact::{
+    create_storage,
+    tests::{create_test_global_state, create_test_storage, DEFAULT_MAX_DB_SIZE},
+};
+
+use super::{
+    dump::{self, DumpedEntry},
+    StateRootError, StateRootSelector,
+};
+
+fn test_stored_values() -> HashMap<Key, StoredValue> {
+    let mut stored_values = HashMap::new();
+    stored_values.insert(
+        Key::Account(AccountHash::new([1u8; 32])),
+        StoredValue::CLValue(CLValue::from_t(1u64).unwrap()),
+    );
+    stored_values.insert(
+        Key::Hash([2u8; 32]),
+        StoredValue::ContractWasm(ContractWasm::new(vec![0u8; 10])),
+    );
+    stored_values.insert(
+        Key::Hash([3u8; 32]),
+        StoredValue::CLValue(CLValue::from_t(String::from("value")).unwrap()),
+    );
+    stored_values.insert(
+        Key::URef(URef::new([4u8; 32], AccessRights::READ_ADD_WRITE)),
+        StoredValue::CLValue(CLValue::from_t(U512::from(10)).unwrap()),
+    );
+    stored_values.insert(
+        Key::Balance([5u8; 32]),
+        StoredValue::CLValue(CLValue::from_t(U512::from(20)).unwrap()),
+    );
+    stored_values
+}
+
+/// Dumps the global state at `trie_path` and returns the parsed lines.
+fn dump_entries<P: AsRef<Path>>(
+    trie_path: P,
+    state_root_hash: Digest,
+    key_types: &[&str],
+) -> Vec<DumpedEntry> {
+    let key_types: Vec<String> = key_types.iter().map(|name| name.to_string()).collect();
+    let mut output = vec![];
+    let written_leaves = dump::dump_leaves(
+        trie_path,
+        *DEFAULT_MAX_DB_SIZE,
+        state_root_hash,
+        &key_types,
+        &mut output,
+    )
+    .unwrap();
+    let entries: Vec<DumpedEntry> = String::from_utf8(output)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(entries.len(), written_leaves);
+    entries
+}
+
+/// Returns the expected dumped entries with keys of the given types.
+fn expected_entries(key_types: &[&str]) -> Vec<(String, JsonStoredValue)> {
+    let mut entries: Vec<(String, JsonStoredValue)> = test_stored_values()
+        .into_iter()
+        .filter(|(key, _)| key_types.is_empty() || key_types.contains(&dump::key_type(key)))
+        .map(|(key, value)| {
+            (
+                key.to_formatted_string(),
+                JsonStoredValue::try_from(value).unwrap(),
+            )
+        })
+        .collect();
+    entries.sort_by(|(key1, _), (key2, _)| key1.cmp(key2));
+    entries
+}
+
+fn sorted(entries: Vec<DumpedEntry>) -> Vec<(String, JsonStoredValue)> {
+    let mut entries: Vec<(String, JsonStoredValue)> = entries
+        .into_iter()
+        .map(|entry| (entry.key, entry.value))
+        .collect();
+    entries.sort_by(|(key1, _), (key2, _)| key1.cmp(key2));
+    entries
+}
+
+#[test]
+fn dump_should_write_all_entries() {
+    let (trie_dir, state_root_hash) = create_test_global_state(test_stored_values());
+
+    let entries = dump_entries(trie_dir.path(), state_root_hash, &[]);
+    assert_eq!(sorted(entries), expected_entries(&[]));
+}
+
+#[test]
+fn dump_should_filter_by_key_type() {
+    let (trie_dir, state_root_hash) = create_test_global_state(test_stored_values());
+
+    for key_types in [
+        vec!["hash"],
+        vec!["account-hash", "balance"],
+        vec!["uref"],
+        vec!["bid"],
+    ] {
+        let entries = dump_entries(trie_dir.path(), state_root_hash, &key_types);
+        assert_eq!(sorted(entries), expected_entries(&key_types));
+    }
+}
+
+#[test]
+fn state_root_should_be_resolved_from_storage() {
+    let state_roots = [Digest::hash(b"root 0"), Digest::hash(b"root 1")];
+    let storage_dir = create_test_storage(&state_roots);
+    let block_hash = *create_storage(&storage_dir)
+        .unwrap()
+        .read_block_by_height(1)
+        .unwrap()
+        .unwrap()
+        .hash();
+
+    assert_eq!(
+        StateRootSelector::StateRootHash(state_roots[0])
+            .resolve(None::<&Path>)
+            .unwrap(),
+        state_roots[0]
+    );
+    assert_eq!(
+        StateRootSelector::BlockHeight(0)
+            .resolve(Some(&storage_dir))
+            .unwrap(),
+        state_roots[0]
+    );
+    assert_eq!(
+        StateRootSelector::BlockHash(block_hash)
+            .resolve(Some(&storage_dir))
+            .unwrap(),
+        state_roots[1]
+    );
+    assert!(matches!(
+        StateRootSelector::BlockHeight(2).resolve(Some(&storage_dir)),
+        Err(StateRootError::MissingBlockHeight(2))
+    ));
+    assert!(matches!(
+        StateRootSelector::BlockHeight(0).resolve(None::<&Path>),
+        Err(StateRootError::NoStoragePath)
+    ));
+}
@@ -1,6 +1,6 @@
 pub(crate) mod block_body;
 mod read_db;
-mod summary;
+pub(crate) mod summary;
 #[cfg(test)]
 mod tests;
 
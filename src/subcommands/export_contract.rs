@@ -0,0 +1,204 @@
+mod export;
+#[cfg(test)]
+mod tests;
+
+use std::{io::Error as IoError, path::Path};
+
+use anyhow::Error as AnyError;
+use clap::{Arg, ArgMatches, Command};
+use serde_json::Error as JsonSerializationError;
+use thiserror::Error as ThisError;
+
+use casper_types::{ContractHash, ContractPackageHash, ContractVersion, Key};
+
+use super::{
+    dump_state::{self, StateRootError},
+    query_state::Error as QueryStateError,
+    trie_compact::DEFAULT_MAX_DB_SIZE,
+};
+
+pub const COMMAND_NAME: &str = "export-contract";
+const CONTRACT_HASH: &str = "contract-hash";
+const CONTRACT_PACKAGE_HASH: &str = "contract-package-hash";
+const CONTRACT_VERSION: &str = "contract-version";
+const MAX_DB_SIZE: &str = "max-db-size";
+const OUTPUT_DIR: &str = "output-dir";
+const OVERWRITE: &str = "overwrite";
+const TRIE_STORE_PATH: &str = "trie";
+
+/// Errors encountered when exporting a contract.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error("Error serializing output: {0}")]
+    JsonSerialize(#[from] JsonSerializationError),
+    /// The contract package has no version with the given number.
+    #[error("Contract package {} has no contract version {1}", .0.to_formatted_string())]
+    MissingContractVersion(ContractPackageHash, ContractVersion),
+    /// The contract package has no enabled version.
+    #[error("Contract package {} has no enabled contract version", .0.to_formatted_string())]
+    NoEnabledVersion(ContractPackageHash),
+    /// The value under the given key isn't a `Contract`.
+    #[error("No contract under {}", .0.to_formatted_string())]
+    NotAContract(Key),
+    /// The value under the given key isn't a `ContractPackage`.
+    #[error("No contract package under {}", .0.to_formatted_string())]
+    NotAContractPackage(Key),
+    /// The value under the given key isn't a `ContractWasm`.
+    #[error("No contract wasm under {}", .0.to_formatted_string())]
+    NotAContractWasm(Key),
+    /// Error opening the trie store.
+    #[error("Error opening the trie store: {0}")]
+    OpenTrie(AnyError),
+    #[error("Error writing output: {0}")]
+    Output(#[from] IoError),
+    /// Error reading a value from the global state.
+    #[error("Error querying the global state: {0}")]
+    Query(#[from] QueryStateError),
+    /// Error finding the state root to export the contract from.
+    #[error("Error finding the state root: {0}")]
+    StateRoot(#[from] StateRootError),
+}
+
+enum DisplayOrder {
+    TriePath,
+    StateRoot,
+    ContractHash,
+    ContractPackageHash,
+    ContractVersion,
+    MaxDbSize,
+    OutputDir,
+    Overwrite,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Writes the wasm of a contract under a state root to a `.wasm` file, along with a \
+            JSON file describing its entry points, named keys and package versions.",
+        )
+        .arg(
+            Arg::new(TRIE_STORE_PATH)
+                .display_order(DisplayOrder::TriePath as usize)
+                .required(true)
+                .short('t')
+                .long(TRIE_STORE_PATH)
+                .takes_value(true)
+                .value_name("TRIE_STORE_DIR_PATH")
+                .help("Path of the directory with the `data.lmdb` file."),
+        )
+        .args(dump_state::state_root_args(
+            DisplayOrder::StateRoot as usize,
+        ))
+        .arg(
+            Arg::new(CONTRACT_HASH)
+                .display_order(DisplayOrder::ContractHash as usize)
+                .required_unless_present(CONTRACT_PACKAGE_HASH)
+                .conflicts_with(CONTRACT_PACKAGE_HASH)
+                .short('c')
+                .long(CONTRACT_HASH)
+                .takes_value(true)
+                .value_name("FORMATTED_CONTRACT_HASH")
+                .help("Hash of the contract to export, formatted as `contract-<HEX>`."),
+        )
+        .arg(
+            Arg::new(CONTRACT_PACKAGE_HASH)
+                .display_order(DisplayOrder::ContractPackageHash as usize)
+                .short('p')
+                .long(CONTRACT_PACKAGE_HASH)
+                .takes_value(true)
+                .value_name("FORMATTED_CONTRACT_PACKAGE_HASH")
+                .help(
+                    "Hash of the package of the contract to export, formatted as \
+                    `contract-package-wasm<HEX>`. The latest enabled version of the package \
+                    is exported unless `--contract-version` is given.",
+                ),
+        )
+        .arg(
+            Arg::new(CONTRACT_VERSION)
+                .display_order(DisplayOrder::ContractVersion as usize)
+                .requires(CONTRACT_PACKAGE_HASH)
+                .short('v')
+                .long(CONTRACT_VERSION)
+                .takes_value(true)
+                .value_name("VERSION")
+                .help(
+                    "Version of the contract to export from the package. If the version \
+                    exists for several protocol versions, the latest one is exported.",
+                ),
+        )
+        .arg(
+            Arg::new(MAX_DB_SIZE)
+                .display_order(DisplayOrder::MaxDbSize as usize)
+                .required(false)
+                .short('m')
+                .long(MAX_DB_SIZE)
+                .takes_value(true)
+                .default_value(DEFAULT_MAX_DB_SIZE)
+                .value_name("MAX_DB_SIZE")
+                .help("Maximum size the DB files are allowed to be, in bytes."),
+        )
+        .arg(
+            Arg::new(OUTPUT_DIR)
+                .display_order(DisplayOrder::OutputDir as usize)
+                .required(true)
+                .short('o')
+                .long(OUTPUT_DIR)
+                .takes_value(true)
+                .value_name("DIR_PATH")
+                .help(
+                    "Path of the directory where the `contract-<HEX>.wasm` and \
+                    `contract-<HEX>.json` files are written.",
+                ),
+        )
+        .arg(
+            Arg::new(OVERWRITE)
+                .display_order(DisplayOrder::Overwrite as usize)
+                .required(false)
+                .short('w')
+                .long(OVERWRITE)
+                .takes_value(false)
+                .help("Overwrite already existing output files in destination directory."),
+        )
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let trie_path = Path::new(matches.value_of(TRIE_STORE_PATH).unwrap());
+    let state_root_hash = dump_state::resolve_state_root(matches)?;
+    let selector = if let Some(contract_hash_str) = matches.value_of(CONTRACT_HASH) {
+        export::ContractSelector::Contract(
+            ContractHash::from_formatted_str(contract_hash_str)
+                .expect("should parse contract hash from formatted string"),
+        )
+    } else {
+        export::ContractSelector::Package(
+            matches
+                .value_of(CONTRACT_PACKAGE_HASH)
+                .map(|package_hash_str| {
+                    ContractPackageHash::from_formatted_str(package_hash_str)
+                        .expect("should parse contract package hash from formatted string")
+                })
+                .expect("should have either contract-hash or contract-package-hash arg"),
+            matches.value_of(CONTRACT_VERSION).map(|version| {
+                version
+                    .parse()
+                    .expect("Value of \"--contract-version\" must be an integer.")
+            }),
+        )
+    };
+    let max_db_size = matches
+        .value_of(MAX_DB_SIZE)
+        .unwrap()
+        .parse()
+        .expect("Value of \"--max-db-size\" must be an integer.");
+    let output_dir = Path::new(matches.value_of(OUTPUT_DIR).unwrap());
+    let overwrite = matches.is_present(OVERWRITE);
+    export::export_contract(
+        trie_path,
+        max_db_size,
+        state_root_hash,
+        selector,
+        output_dir,
+        overwrite,
+    )
+}
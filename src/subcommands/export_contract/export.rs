@@ -0,0 +1,292 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use log::{info, warn};
+use serde::Serialize;
+
+use casper_execution_engine::{
+    core::engine_state::EngineState, storage::global_state::lmdb::LmdbGlobalState,
+};
+use casper_hashing::Digest;
+use casper_node::types::json_compatibility::{
+    Contract as JsonContract, ContractPackage as JsonContractPackage,
+};
+use casper_types::{
+    Contract, ContractHash, ContractPackage, ContractPackageHash, ContractVersion,
+    ContractVersionKey, ContractWasm, Key, StoredValue,
+};
+
+use crate::subcommands::{query_state, trie_compact::load_execution_engine};
+
+use super::Error;
+
+/// Extension of the file holding the wasm of the exported contract.
+const WASM_EXTENSION: &str = "wasm";
+/// Extension of the file describing the exported contract.
+const DESCRIPTION_EXTENSION: &str = "json";
+
+/// Identifies the contract to export.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContractSelector {
+    /// The contract with the given hash.
+    Contract(ContractHash),
+    /// The contract with the given version in the package with the given
+    /// hash, or its latest enabled version if no version is given.
+    Package(ContractPackageHash, Option<ContractVersion>),
+}
+
+/// A contract read from the global state, with its package and wasm.
+pub(crate) struct ExportedContract {
+    pub(crate) contract_hash: ContractHash,
+    pub(crate) contract: Contract,
+    pub(crate) contract_package: ContractPackage,
+    pub(crate) contract_wasm: ContractWasm,
+}
+
+/// JSON description of an exported contract.
+#[derive(Serialize)]
+pub(crate) struct ContractDescription {
+    contract_hash: ContractHash,
+    /// Version of the contract in its package, if the package lists it.
+    contract_version_key: Option<ContractVersionKey>,
+    wasm_size: usize,
+    contract: JsonContract,
+    contract_package: JsonContractPackage,
+}
+
+impl From<&ExportedContract> for ContractDescription {
+    fn from(exported_contract: &ExportedContract) -> Self {
+        let contract_version_key = exported_contract
+            .contract_package
+            .versions()
+            .iter()
+            .find(|(_, contract_hash)| **contract_hash == exported_contract.contract_hash)
+            .map(|(contract_version_key, _)| *contract_version_key);
+        Self {
+            contract_hash: exported_contract.contract_hash,
+            contract_version_key,
+            wasm_size: exported_contract.contract_wasm.bytes().len(),
+            contract: JsonContract::from(&exported_contract.contract),
+            contract_package: JsonContractPackage::from(&exported_contract.contract_package),
+        }
+    }
+}
+
+fn read_contract(
+    engine_state: &EngineState<LmdbGlobalState>,
+    state_root_hash: Digest,
+    contract_hash: ContractHash,
+) -> Result<Contract, Error> {
+    let key = Key::from(contract_hash);
+    match query_state::run_query(engine_state, state_root_hash, key, vec![])? {
+        StoredValue::Contract(contract) => Ok(contract),
+        _ => Err(Error::NotAContract(key)),
+    }
+}
+
+fn read_contract_package(
+    engine_state: &EngineState<LmdbGlobalState>,
+    state_root_hash: Digest,
+    contract_package_hash: ContractPackageHash,
+) -> Result<ContractPackage, Error> {
+    let key = Key::from(contract_package_hash);
+    match query_state::run_query(engine_state, state_root_hash, key, vec![])? {
+        StoredValue::ContractPackage(contract_package) => Ok(contract_package),
+        _ => Err(Error::NotAContractPackage(key)),
+    }
+}
+
+fn read_contract_wasm(
+    engine_state: &EngineState<LmdbGlobalState>,
+    state_root_hash: Digest,
+    contract: &Contract,
+) -> Result<ContractWasm, Error> {
+    let key = Key::from(contract.contract_wasm_hash());
+    match query_state::run_query(engine_state, state_root_hash, key, vec![])? {
+        StoredValue::ContractWasm(contract_wasm) => Ok(contract_wasm),
+        _ => Err(Error::NotAContractWasm(key)),
+    }
+}
+
+/// Returns the hash of the contract selected in `contract_package`.
+fn select_version(
+    contract_package_hash: ContractPackageHash,
+    contract_package: &ContractPackage,
+    maybe_version: Option<ContractVersion>,
+) -> Result<ContractHash, Error> {
+    match maybe_version {
+        // Versions are ordered by protocol version first, so the last match
+        // is the one of the latest protocol version.
+        Some(version) => contract_package
+            .versions()
+            .iter()
+            .rev()
+            .find(|(version_key, _)| version_key.contract_version() == version)
+            .map(|(_, contract_hash)| *contract_hash)
+            .ok_or(Error::MissingContractVersion(
+                contract_package_hash,
+                version,
+            )),
+        None => contract_package
+            .current_contract_hash()
+            .ok_or(Error::NoEnabledVersion(contract_package_hash)),
+    }
+}
+
+/// Returns the hash of the selected contract, along with its package if it
+/// was read to find the contract.
+fn selected_contract_hash(
+    engine_state: &EngineState<LmdbGlobalState>,
+    state_root_hash: Digest,
+    selector: ContractSelector,
+) -> Result<(ContractHash, Option<ContractPackage>), Error> {
+    match selector {
+        ContractSelector::Contract(contract_hash) => Ok((contract_hash, None)),
+        ContractSelector::Package(contract_package_hash, maybe_version) => {
+            let contract_package =
+                read_contract_package(engine_state, state_root_hash, contract_package_hash)?;
+            let contract_hash =
+                select_version(contract_package_hash, &contract_package, maybe_version)?;
+            Ok((contract_hash, Some(contract_package)))
+        }
+    }
+}
+
+/// Reads the contract with the given hash, its package unless already
+/// given, and its wasm.
+fn read_contract_with_hash(
+    engine_state: &EngineState<LmdbGlobalState>,
+    state_root_hash: Digest,
+    contract_hash: ContractHash,
+    maybe_contract_package: Option<ContractPackage>,
+) -> Result<ExportedContract, Error> {
+    let contract = read_contract(engine_state, state_root_hash, contract_hash)?;
+    let contract_package = match maybe_contract_package {
+        Some(contract_package) => contract_package,
+        None => read_contract_package(
+            engine_state,
+            state_root_hash,
+            contract.contract_package_hash(),
+        )?,
+    };
+    let contract_wasm = read_contract_wasm(engine_state, state_root_hash, &contract)?;
+    Ok(ExportedContract {
+        contract_hash,
+        contract,
+        contract_package,
+        contract_wasm,
+    })
+}
+
+/// Reads the selected contract, its package and its wasm from the global
+/// state at `state_root_hash`.
+#[cfg(test)]
+pub(crate) fn read_exported_contract<P: AsRef<Path>>(
+    trie_path: P,
+    max_db_size: usize,
+    state_root_hash: Digest,
+    selector: ContractSelector,
+) -> Result<ExportedContract, Error> {
+    let (engine_state, _env) =
+        load_execution_engine(trie_path, max_db_size, Digest::default(), true)
+            .map_err(Error::OpenTrie)?;
+    let (contract_hash, maybe_contract_package) =
+        selected_contract_hash(&engine_state, state_root_hash, selector)?;
+    read_contract_with_hash(
+        &engine_state,
+        state_root_hash,
+        contract_hash,
+        maybe_contract_package,
+    )
+}
+
+/// Returns the path of the output file of the contract with the given
+/// extension.
+pub(crate) fn output_path<P: AsRef<Path>>(
+    output_dir: P,
+    contract_hash: ContractHash,
+    extension: &str,
+) -> PathBuf {
+    output_dir
+        .as_ref()
+        .join(contract_hash.to_formatted_string())
+        .with_extension(extension)
+}
+
+/// Opens an output file, which must not exist unless `overwrite` is set.
+fn open_output(path: &Path, overwrite: bool) -> io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true);
+    if overwrite {
+        options.create(true).truncate(true);
+    } else {
+        options.create_new(true);
+    }
+    options.open(path)
+}
+
+/// Removes the output files of a failed export.
+fn remove_outputs(paths: &[&Path]) {
+    for path in paths {
+        if let Err(io_err) = fs::remove_file(path) {
+            warn!("Couldn't remove output file {}: {io_err}", path.display());
+        }
+    }
+}
+
+pub fn export_contract<P1: AsRef<Path>, P2: AsRef<Path>>(
+    trie_path: P1,
+    max_db_size: usize,
+    state_root_hash: Digest,
+    selector: ContractSelector,
+    output_dir: P2,
+    overwrite: bool,
+) -> Result<(), Error> {
+    let (engine_state, _env) =
+        load_execution_engine(trie_path, max_db_size, Digest::default(), true)
+            .map_err(Error::OpenTrie)?;
+    let (contract_hash, maybe_contract_package) =
+        selected_contract_hash(&engine_state, state_root_hash, selector)?;
+
+    // Validate both output files early so that, in case this fails, we
+    // don't unnecessarily read the contract nor leave a single file behind.
+    let wasm_path = output_path(&output_dir, contract_hash, WASM_EXTENSION);
+    let description_path = output_path(&output_dir, contract_hash, DESCRIPTION_EXTENSION);
+    let mut wasm_file = open_output(&wasm_path, overwrite)?;
+    let description_file = match open_output(&description_path, overwrite) {
+        Ok(file) => file,
+        Err(io_err) => {
+            remove_outputs(&[&wasm_path]);
+            return Err(io_err.into());
+        }
+    };
+
+    let write_outputs = || {
+        let exported_contract = read_contract_with_hash(
+            &engine_state,
+            state_root_hash,
+            contract_hash,
+            maybe_contract_package,
+        )?;
+        wasm_file.write_all(exported_contract.contract_wasm.bytes())?;
+        serde_json::to_writer_pretty(
+            description_file,
+            &ContractDescription::from(&exported_contract),
+        )?;
+        Ok::<_, Error>(())
+    };
+    if let Err(err) = write_outputs() {
+        remove_outputs(&[&wasm_path, &description_path]);
+        return Err(err);
+    }
+    info!(
+        "Exported contract {} to {} and {}.",
+        contract_hash.to_formatted_string(),
+        wasm_path.display(),
+        description_path.display()
+    );
+    Ok(())
+}
@@ -0,0 +1,286 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+};
+
+use casper_types::{
+    contracts::{ContractPackageStatus, NamedKeys},
+    AccessRights, CLType, Contract, ContractHash, ContractPackage, ContractPackageHash,
+    ContractVersionKey, ContractWasm, ContractWasmHash, EntryPoint, EntryPointAccess,
+    EntryPointType, EntryPoints, Key, ProtocolVersion, StoredValue, URef,
+};
+
+use crate::subcommands::{
+    query_state::Error as QueryStateError,
+    trie_compact::tests::{create_test_global_state, DEFAULT_MAX_DB_SIZE},
+};
+
+use super::{
+    export::{self, ContractSelector},
+    Error,
+};
+
+const PACKAGE_HASH: ContractPackageHash = ContractPackageHash::new([1u8; 32]);
+const CONTRACT_V1_HASH: ContractHash = ContractHash::new([2u8; 32]);
+const CONTRACT_V2_HASH: ContractHash = ContractHash::new([3u8; 32]);
+const WASM_V1_HASH: ContractWasmHash = ContractWasmHash::new([4u8; 32]);
+const WASM_V2_HASH: ContractWasmHash = ContractWasmHash::new([5u8; 32]);
+
+const WASM_V1: &[u8] = b"\0asm\x01\0\0\0version one";
+const WASM_V2: &[u8] = b"\0asm\x01\0\0\0version two";
+
+fn test_contract(wasm_hash: ContractWasmHash, entry_point_name: &str) -> Contract {
+    let mut named_keys = NamedKeys::new();
+    named_keys.insert(
+        "counter".to_string(),
+        Key::URef(URef::new([6u8; 32], AccessRights::READ_ADD_WRITE)),
+    );
+    let mut entry_points = EntryPoints::new();
+    entry_points.add_entry_point(EntryPoint::new(
+        entry_point_name,
+        vec![],
+        CLType::Unit,
+        EntryPointAccess::Public,
+        EntryPointType::Contract,
+    ));
+    Contract::new(
+        PACKAGE_HASH,
+        wasm_hash,
+        named_keys,
+        entry_points,
+        ProtocolVersion::V1_0_0,
+    )
+}
+
+/// Creates the global state of a package with two versions of a contract,
+/// the latest one being disabled if `disable_latest` is set.
+fn contract_stored_values(disable_latest: bool) -> HashMap<Key, StoredValue> {
+    let mut contract_package = ContractPackage::new(
+        URef::new([7u8; 32], AccessRights::READ_ADD_WRITE),
+        BTreeMap::new(),
+        Default::default(),
+        Default::default(),
+        ContractPackageStatus::default(),
+    );
+    contract_package.insert_contract_version(1, CONTRACT_V1_HASH);
+    contract_package.insert_contract_version(1, CONTRACT_V2_HASH);
+    if disable_latest {
+        contract_package
+            .disable_contract_version(CONTRACT_V2_HASH)
+            .unwrap();
+    }
+
+    let mut stored_values = HashMap::new();
+    stored_values.insert(
+        Key::from(PACKAGE_HASH),
+        StoredValue::ContractPackage(contract_package),
+    );
+    stored_values.insert(
+        Key::from(CONTRACT_V1_HASH),
+        StoredValue::Contract(test_contract(WASM_V1_HASH, "call_v1")),
+    );
+    stored_values.insert(
+        Key::from(CONTRACT_V2_HASH),
+        StoredValue::Contract(test_contract(WASM_V2_HASH, "call_v2")),
+    );
+    stored_values.insert(
+        Key::from(WASM_V1_HASH),
+        StoredValue::ContractWasm(ContractWasm::new(WASM_V1.to_vec())),
+    );
+    stored_values.insert(
+        Key::from(WASM_V2_HASH),
+        StoredValue::ContractWasm(ContractWasm::new(WASM_V2.to_vec())),
+    );
+    stored_values
+}
+
+#[test]
+fn export_contract_should_write_wasm_and_description() {
+    let (trie_dir, state_root_hash) = create_test_global_state(contract_stored_values(false));
+    let out_dir = tempfile::tempdir().unwrap();
+
+    export::export_contract(
+        trie_dir.path(),
+        *DEFAULT_MAX_DB_SIZE,
+        state_root_hash,
+        ContractSelector::Contract(CONTRACT_V1_HASH),
+        out_dir.path(),
+        false,
+    )
+    .unwrap();
+
+    let wasm_path = export::output_path(out_dir.path(), CONTRACT_V1_HASH, "wasm");
+    assert_eq!(fs::read(&wasm_path).unwrap(), WASM_V1);
+    let description_path = export::output_path(out_dir.path(), CONTRACT_V1_HASH, "json");
+    let description: serde_json::Value =
+        serde_json::from_slice(&fs::read(&description_path).unwrap()).unwrap();
+    assert_eq!(
+        description["contract_hash"],
+        CONTRACT_V1_HASH.to_formatted_string()
+    );
+    assert_eq!(
+        description["contract_version_key"],
+        serde_json::json!([1, 1])
+    );
+    assert_eq!(description["wasm_size"], WASM_V1.len());
+    assert_eq!(
+        description["contract"]["entry_points"][0]["name"],
+        "call_v1"
+    );
+    assert_eq!(description["contract"]["named_keys"][0]["name"], "counter");
+    assert_eq!(
+        description["contract_package"]["versions"]
+            .as_array()
+            .unwrap()
+            .len(),
+        2
+    );
+
+    // Exporting again fails unless overwriting is allowed.
+    assert!(matches!(
+        export::export_contract(
+            trie_dir.path(),
+            *DEFAULT_MAX_DB_SIZE,
+            state_root_hash,
+            ContractSelector::Contract(CONTRACT_V1_HASH),
+            out_dir.path(),
+            false,
+        ),
+        Err(Error::Output(_))
+    ));
+    export::export_contract(
+        trie_dir.path(),
+        *DEFAULT_MAX_DB_SIZE,
+        state_root_hash,
+        ContractSelector::Contract(CONTRACT_V1_HASH),
+        out_dir.path(),
+        true,
+    )
+    .unwrap();
+}
+
+#[test]
+fn export_contract_should_not_leave_files_behind_on_error() {
+    let (trie_dir, state_root_hash) = create_test_global_state(contract_stored_values(false));
+    let out_dir = tempfile::tempdir().unwrap();
+
+    // The description file is in the way, so the wasm file isn't written.
+    let description_path = export::output_path(out_dir.path(), CONTRACT_V1_HASH, "json");
+    fs::write(&description_path, b"{}").unwrap();
+    assert!(matches!(
+        export::export_contract(
+            trie_dir.path(),
+            *DEFAULT_MAX_DB_SIZE,
+            state_root_hash,
+            ContractSelector::Contract(CONTRACT_V1_HASH),
+            out_dir.path(),
+            false,
+        ),
+        Err(Error::Output(_))
+    ));
+    assert!(!export::output_path(out_dir.path(), CONTRACT_V1_HASH, "wasm").exists());
+    assert_eq!(fs::read(&description_path).unwrap(), b"{}");
+    fs::remove_file(&description_path).unwrap();
+
+    // The package hash doesn't hold a contract, both files are removed.
+    let package_as_contract = ContractHash::new(PACKAGE_HASH.value());
+    assert!(matches!(
+        export::export_contract(
+            trie_dir.path(),
+            *DEFAULT_MAX_DB_SIZE,
+            state_root_hash,
+            ContractSelector::Contract(package_as_contract),
+            out_dir.path(),
+            true,
+        ),
+        Err(Error::NotAContract(_))
+    ));
+    assert_eq!(fs::read_dir(out_dir.path()).unwrap().count(), 0);
+}
+
+#[test]
+fn export_contract_should_select_package_version() {
+    let (trie_dir, state_root_hash) = create_test_global_state(contract_stored_values(false));
+
+    // The latest enabled version is selected by default.
+    let exported_contract = export::read_exported_contract(
+        trie_dir.path(),
+        *DEFAULT_MAX_DB_SIZE,
+        state_root_hash,
+        ContractSelector::Package(PACKAGE_HASH, None),
+    )
+    .unwrap();
+    assert_eq!(exported_contract.contract_hash, CONTRACT_V2_HASH);
+    assert_eq!(exported_contract.contract_wasm.bytes(), WASM_V2);
+
+    let exported_contract = export::read_exported_contract(
+        trie_dir.path(),
+        *DEFAULT_MAX_DB_SIZE,
+        state_root_hash,
+        ContractSelector::Package(PACKAGE_HASH, Some(1)),
+    )
+    .unwrap();
+    assert_eq!(exported_contract.contract_hash, CONTRACT_V1_HASH);
+    assert_eq!(exported_contract.contract_wasm.bytes(), WASM_V1);
+    assert_eq!(
+        exported_contract
+            .contract_package
+            .versions()
+            .keys()
+            .next_back(),
+        Some(&ContractVersionKey::new(1, 2))
+    );
+
+    match export::read_exported_contract(
+        trie_dir.path(),
+        *DEFAULT_MAX_DB_SIZE,
+        state_root_hash,
+        ContractSelector::Package(PACKAGE_HASH, Some(3)),
+    ) {
+        Err(Error::MissingContractVersion(package_hash, version)) => {
+            assert_eq!(package_hash, PACKAGE_HASH);
+            assert_eq!(version, 3);
+        }
+        Err(err) => panic!("Unexpected error: {err}"),
+        Ok(_) => panic!("Unexpected success"),
+    }
+
+    // Disabled versions aren't selected by default.
+    let (trie_dir, state_root_hash) = create_test_global_state(contract_stored_values(true));
+    let exported_contract = export::read_exported_contract(
+        trie_dir.path(),
+        *DEFAULT_MAX_DB_SIZE,
+        state_root_hash,
+        ContractSelector::Package(PACKAGE_HASH, None),
+    )
+    .unwrap();
+    assert_eq!(exported_contract.contract_hash, CONTRACT_V1_HASH);
+}
+
+#[test]
+fn export_contract_should_fail_on_invalid_hashes() {
+    let (trie_dir, state_root_hash) = create_test_global_state(contract_stored_values(false));
+
+    // The package hash doesn't hold a contract.
+    match export::read_exported_contract(
+        trie_dir.path(),
+        *DEFAULT_MAX_DB_SIZE,
+        state_root_hash,
+        ContractSelector::Contract(ContractHash::new(PACKAGE_HASH.value())),
+    ) {
+        Err(Error::NotAContract(key)) => assert_eq!(key, Key::from(PACKAGE_HASH)),
+        Err(err) => panic!("Unexpected error: {err}"),
+        Ok(_) => panic!("Unexpected success"),
+    }
+
+    match export::read_exported_contract(
+        trie_dir.path(),
+        *DEFAULT_MAX_DB_SIZE,
+        state_root_hash,
+        ContractSelector::Contract(ContractHash::new([8u8; 32])),
+    ) {
+        Err(Error::Query(QueryStateError::ValueNotFound(_))) => {}
+        Err(err) => panic!("Unexpected error: {err}"),
+        Ok(_) => panic!("Unexpected success"),
+    }
+}
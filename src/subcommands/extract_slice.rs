@@ -54,9 +54,9 @@ pub fn command(display_order: usize) -> Command<'static> {
         .about(
             "Reads all data for a given block hash (block, deploys, execution \
                 results, global state) from a storage directory and stores \
-                them to a new directory in two LMDB files. If a state root \
-                hash is provided instead of a block hash, only the global \
-                state under that root hash will be stored in the new \
+                them to a new directory in two LMDB files. If state root \
+                hashes are provided instead of a block hash, only the global \
+                state under those root hashes will be stored in the new \
                 directory",
         )
         .arg(
@@ -101,8 +101,12 @@ pub fn command(display_order: usize) -> Command<'static> {
                 .short('s')
                 .long(STATE_ROOT_HASH)
                 .takes_value(true)
+                .multiple_occurrences(true)
                 .value_name("STATE_ROOT_HASH")
-                .help("State root hash to be copied over to the new database."),
+                .help(
+                    "State root hash to be copied over to the new database. Can be \
+                    given several times to copy several state roots.",
+                ),
         )
 }
 
@@ -123,11 +127,15 @@ pub fn run(matches: &ArgMatches) -> Result<(), Error> {
         })
         .unwrap_or_else(|| {
             matches
-                .value_of(STATE_ROOT_HASH)
-                .map(|state_root_hash_str| {
-                    let state_root_hash = Digest::from_hex(state_root_hash_str)
-                        .expect("should parse state root hash to hex format");
-                    SliceIdentifier::StateRootHash(state_root_hash)
+                .values_of(STATE_ROOT_HASH)
+                .map(|state_root_hash_strs| {
+                    let state_root_hashes = state_root_hash_strs
+                        .map(|state_root_hash_str| {
+                            Digest::from_hex(state_root_hash_str)
+                                .expect("should parse state root hash to hex format")
+                        })
+                        .collect();
+                    SliceIdentifier::StateRootHashes(state_root_hashes)
                 })
                 .expect("should have either BLOCK_HASH or STATE_ROOT_HASH arg")
         });
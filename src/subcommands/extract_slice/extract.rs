@@ -7,7 +7,7 @@ use super::{global_state, storage, Error};
 
 pub enum SliceIdentifier {
     BlockHash(BlockHash),
-    StateRootHash(Digest),
+    StateRootHashes(Vec<Digest>),
 }
 
 pub fn extract_slice<P1: AsRef<Path>, P2: AsRef<Path>>(
@@ -16,12 +16,12 @@ pub fn extract_slice<P1: AsRef<Path>, P2: AsRef<Path>>(
     slice_identifier: SliceIdentifier,
 ) -> Result<(), Error> {
     storage::create_output_db(&output)?;
-    let state_root_hash = match slice_identifier {
+    let state_root_hashes = match slice_identifier {
         SliceIdentifier::BlockHash(block_hash) => {
-            storage::transfer_block_info(&db_path, &output, block_hash)?
+            vec![storage::transfer_block_info(&db_path, &output, block_hash)?]
         }
-        SliceIdentifier::StateRootHash(state_root_hash) => state_root_hash,
+        SliceIdentifier::StateRootHashes(state_root_hashes) => state_root_hashes,
     };
-    global_state::transfer_global_state(&db_path, &output, state_root_hash)?;
+    global_state::transfer_global_state(&db_path, &output, &state_root_hashes)?;
     Ok(())
 }
@@ -9,12 +9,12 @@ use crate::subcommands::trie_compact::{
 
 use super::Error;
 
-/// Transfers the global state under a state root hash from a trie store to a
-/// new one.
+/// Transfers the global state under the state root hashes from a trie store
+/// to a new one.
 pub(crate) fn transfer_global_state<P1: AsRef<Path>, P2: AsRef<Path>>(
     source: P1,
     destination: P2,
-    state_root_hash: Digest,
+    state_root_hashes: &[Digest],
 ) -> Result<(), Error> {
     let max_db_size = DEFAULT_MAX_DB_SIZE
         .parse()
@@ -26,11 +26,13 @@ pub(crate) fn transfer_global_state<P1: AsRef<Path>, P2: AsRef<Path>>(
     // Create the destination trie store.
     let (destination_state, _env) = create_execution_engine(destination, max_db_size, true)
         .map_err(Error::CreateExecutionEngine)?;
-    info!("Starting transfer process for state root hash {state_root_hash}");
-    // Copy the state root along with missing descendants over to the new trie
-    // store.
-    copy_state_root(state_root_hash, &source_state, &destination_state)
-        .map_err(Error::StateRootTransfer)?;
+    for state_root_hash in state_root_hashes {
+        info!("Starting transfer process for state root hash {state_root_hash}");
+        // Copy the state root along with missing descendants over to the new
+        // trie store.
+        copy_state_root(*state_root_hash, &source_state, &destination_state)
+            .map_err(Error::StateRootTransfer)?;
+    }
     destination_state.flush_environment()?;
 
     Ok(())
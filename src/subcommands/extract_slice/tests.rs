@@ -20,7 +20,9 @@ use crate::{
         execution_results_summary::block_body::BlockBody,
         extract_slice::{db_helpers, global_state, storage},
         trie_compact::{
-            create_execution_engine, load_execution_engine, tests::create_data, DEFAULT_MAX_DB_SIZE,
+            create_execution_engine, load_execution_engine,
+            tests::{create_data, create_test_trie_store, trie_store_entries},
+            DEFAULT_MAX_DB_SIZE,
         },
     },
     test_utils::{
@@ -172,7 +174,7 @@ fn transfer_blocks() {
     block_bodies.push(BlockBody::new(vec![deploy_hashes[2], deploy_hashes[3]]));
     block_body_deploy_map.push(vec![2, 3]);
 
-    let deploy_metadatas = vec![
+    let deploy_metadatas = [
         mock_deploy_metadata(slice::from_ref(&block_headers[0].0)),
         mock_deploy_metadata(&[block_headers[0].0, block_headers[1].0]),
         mock_deploy_metadata(&[block_headers[1].0, block_headers[2].0]),
@@ -468,14 +470,14 @@ fn transfer_global_state_information() {
     global_state::transfer_global_state(
         source_tmp_dir.path(),
         destination_tmp_dir.path(),
-        data[4].0,
+        &[data[4].0],
     )
     .unwrap();
 
     let destination_store = LmdbTrieStore::new(&dst_env, None, DatabaseFlags::empty()).unwrap();
     {
         let txn = dst_env.create_read_write_txn().unwrap();
-        let keys = vec![data[1].0, data[2].0, data[4].0];
+        let keys = [data[1].0, data[2].0, data[4].0];
         let entries: Vec<Option<Trie<Bytes, Bytes>>> =
             destination_store.get_many(&txn, keys.iter()).unwrap();
         for entry in entries {
@@ -488,7 +490,7 @@ fn transfer_global_state_information() {
                     // Hashes should be equal.
                     assert_eq!(
                         trie_in_data.unwrap().0,
-                        Digest::hash(&trie.to_bytes().unwrap())
+                        Digest::hash(trie.to_bytes().unwrap())
                     );
                 }
                 None => panic!(),
@@ -500,3 +502,26 @@ fn transfer_global_state_information() {
     source_tmp_dir.close().unwrap();
     destination_tmp_dir.close().unwrap();
 }
+
+#[test]
+fn transfer_global_state_of_several_state_roots() {
+    let (source_tmp_dir, data) = create_test_trie_store();
+    let destination_tmp_dir = tempfile::tempdir().unwrap();
+
+    // Copy from `node2` and `leaf1`, leaving out `node1` and the extension
+    // node.
+    global_state::transfer_global_state(
+        source_tmp_dir.path(),
+        destination_tmp_dir.path(),
+        &[data[4].0, data[0].0],
+    )
+    .unwrap();
+
+    let entries = trie_store_entries(destination_tmp_dir.path());
+    for (idx, test_data) in data.iter().enumerate() {
+        let is_copied = entries
+            .iter()
+            .any(|(key, _)| key == &test_data.0.to_bytes().unwrap());
+        assert_eq!(is_copied, [0, 1, 2, 4].contains(&idx));
+    }
+}
@@ -0,0 +1,64 @@
+#[cfg(test)]
+mod tests;
+
+use std::{
+    io::{self, Error as IoError, Write},
+    path::{Path, PathBuf},
+};
+
+use clap::{Arg, ArgMatches, Command};
+use lmdb::{Environment, Error as LmdbError, Transaction};
+use thiserror::Error as ThisError;
+
+use crate::common::{db, lmdb_utils};
+
+pub const COMMAND_NAME: &str = "list-dbs";
+const DB_PATH: &str = "file-path";
+
+#[derive(ThisError, Debug)]
+pub enum Error {
+    #[error("Failed to open lmdb database at {0}: {1}")]
+    Lmdb(PathBuf, LmdbError),
+    #[error("Error operating the database: {0}")]
+    Database(#[from] LmdbError),
+    #[error("Error writing output: {0}")]
+    Output(#[from] IoError),
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Lists the named databases in an LMDB environment along with their number of \
+            entries.",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(0)
+                .value_name("DB_PATH")
+                .required(true)
+                .help("Path to the storage.lmdb or data.lmdb file."),
+        )
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let path = Path::new(
+        matches
+            .value_of(DB_PATH)
+            .expect("should have file-path arg"),
+    );
+    let env =
+        db::read_only_db_env(path).map_err(|lmdb_err| Error::Lmdb(path.to_path_buf(), lmdb_err))?;
+    list_dbs(&env, &mut io::stdout())
+}
+
+fn list_dbs<W: Write + ?Sized>(env: &Environment, out: &mut W) -> Result<(), Error> {
+    let names = lmdb_utils::db_names(env)?;
+    let txn = env.begin_ro_txn()?;
+    for name in names {
+        let db = unsafe { txn.open_db(Some(&name))? };
+        writeln!(out, "{} {}", name, lmdb_utils::entry_count(&txn, db)?)?;
+    }
+    txn.commit()?;
+    Ok(())
+}
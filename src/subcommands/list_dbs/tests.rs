@@ -0,0 +1,26 @@
+use lmdb::{Transaction, WriteFlags};
+
+use crate::{common::db, test_utils::LmdbTestFixture};
+
+#[test]
+fn should_list_dbs_with_entry_count() {
+    let fixture = LmdbTestFixture::new(vec!["a", "b"], None);
+    let db = fixture.db(Some("b")).unwrap();
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    for key in 0u8..3 {
+        txn.put(*db, &[key], &[key], WriteFlags::empty()).unwrap();
+    }
+    txn.commit().unwrap();
+
+    let mut out = vec![];
+    super::list_dbs(&fixture.env, &mut out).expect("listing should succeed");
+    assert_eq!(String::from_utf8(out).unwrap(), "a 0\nb 3\n");
+}
+
+#[test]
+fn should_not_create_missing_db_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("storage.lmdb");
+    assert!(db::read_only_db_env(&path).is_err());
+    assert!(!path.exists());
+}
@@ -13,6 +13,8 @@ use clap::{Arg, ArgMatches, Command};
 use lmdb::Error as LmdbError;
 use thiserror::Error as ThisError;
 
+pub(crate) use purge::initialize_indices;
+
 pub const COMMAND_NAME: &str = "purge-signatures";
 const DB_PATH: &str = "db-path";
 const NO_FINALITY: &str = "no-finality";
@@ -27,8 +27,7 @@ pub(super) fn strip_signatures(
 ) -> bool {
     // Calculate the total weight.
     let total_weight: U512 = weights
-        .iter()
-        .map(|(_, weight)| weight)
+        .values()
         .fold(U512::zero(), |acc, weight| acc + *weight);
 
     // Store the signature keys sorted by their respective weight.
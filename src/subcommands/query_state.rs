@@ -0,0 +1,155 @@
+mod query;
+#[cfg(test)]
+mod tests;
+
+use std::{io::Error as IoError, path::Path};
+
+use anyhow::Error as AnyError;
+use clap::{Arg, ArgMatches, Command};
+use serde_json::Error as JsonSerializationError;
+use thiserror::Error as ThisError;
+
+use casper_execution_engine::core::engine_state::Error as EngineStateError;
+use casper_hashing::Digest;
+use casper_types::{bytesrepr::Error as BytesreprError, Key};
+
+pub(crate) use query::run_query;
+
+use super::{
+    dump_state::{self, StateRootError},
+    trie_compact::DEFAULT_MAX_DB_SIZE,
+};
+
+pub const COMMAND_NAME: &str = "query-state";
+const KEY: &str = "key";
+const MAX_DB_SIZE: &str = "max-db-size";
+const QUERY_PATH: &str = "query-path";
+const TRIE_STORE_PATH: &str = "trie";
+
+/// Separator of the named keys in the query path.
+const QUERY_PATH_SEPARATOR: char = '/';
+
+/// Errors encountered when querying the global state.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// The query path loops through named keys.
+    #[error("Circular reference in the query path: {0}")]
+    CircularReference(String),
+    /// The query path is too long.
+    #[error("Query path exceeds the depth limit of {0}")]
+    DepthLimit(u64),
+    #[error("Error serializing output: {0}")]
+    JsonSerialize(#[from] JsonSerializationError),
+    /// Error opening the trie store.
+    #[error("Error opening the trie store: {0}")]
+    OpenTrie(AnyError),
+    #[error("Error writing output: {0}")]
+    Output(#[from] IoError),
+    /// Error running the query in the execution engine.
+    #[error("Error running the query: {0}")]
+    Query(#[from] EngineStateError),
+    /// The state root isn't in the trie store.
+    #[error("State root {0} not found in the trie store")]
+    RootNotFound(Digest),
+    /// Error finding the state root to query.
+    #[error("Error finding the state root: {0}")]
+    StateRoot(#[from] StateRootError),
+    /// Error converting the stored value to JSON.
+    #[error("Error converting the value to JSON: {0}")]
+    ValueConversion(BytesreprError),
+    /// Nothing under the key or one of the named keys of the query path.
+    #[error("Value not found: {0}")]
+    ValueNotFound(String),
+}
+
+enum DisplayOrder {
+    TriePath,
+    StateRoot,
+    Key,
+    QueryPath,
+    MaxDbSize,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Prints the value stored under a key in the global state under a state root in \
+            JSON format.",
+        )
+        .arg(
+            Arg::new(TRIE_STORE_PATH)
+                .display_order(DisplayOrder::TriePath as usize)
+                .required(true)
+                .short('t')
+                .long(TRIE_STORE_PATH)
+                .takes_value(true)
+                .value_name("TRIE_STORE_DIR_PATH")
+                .help("Path of the directory with the `data.lmdb` file."),
+        )
+        .args(dump_state::state_root_args(
+            DisplayOrder::StateRoot as usize,
+        ))
+        .arg(
+            Arg::new(KEY)
+                .display_order(DisplayOrder::Key as usize)
+                .required(true)
+                .short('k')
+                .long(KEY)
+                .takes_value(true)
+                .value_name("FORMATTED_KEY")
+                .help(
+                    "Formatted string of the key to query, e.g. `account-hash-<HEX>`, \
+                    `hash-<HEX>` or `uref-<HEX>-<ACCESS_RIGHTS>`.",
+                ),
+        )
+        .arg(
+            Arg::new(QUERY_PATH)
+                .display_order(DisplayOrder::QueryPath as usize)
+                .short('q')
+                .long(QUERY_PATH)
+                .takes_value(true)
+                .value_name("PATH")
+                .help(
+                    "Named keys to follow from the value under the key, separated by `/`, \
+                    e.g. `contract/counter`.",
+                ),
+        )
+        .arg(
+            Arg::new(MAX_DB_SIZE)
+                .display_order(DisplayOrder::MaxDbSize as usize)
+                .required(false)
+                .short('m')
+                .long(MAX_DB_SIZE)
+                .takes_value(true)
+                .default_value(DEFAULT_MAX_DB_SIZE)
+                .value_name("MAX_DB_SIZE")
+                .help("Maximum size the DB files are allowed to be, in bytes."),
+        )
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let trie_path = Path::new(matches.value_of(TRIE_STORE_PATH).unwrap());
+    let state_root_hash = dump_state::resolve_state_root(matches)?;
+    let key = matches
+        .value_of(KEY)
+        .map(|key_str| {
+            Key::from_formatted_str(key_str).expect("should parse key from formatted string")
+        })
+        .expect("should have key arg");
+    let path = matches
+        .value_of(QUERY_PATH)
+        .map(|path| {
+            path.split(QUERY_PATH_SEPARATOR)
+                .filter(|name| !name.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default();
+    let max_db_size = matches
+        .value_of(MAX_DB_SIZE)
+        .unwrap()
+        .parse()
+        .expect("Value of \"--max-db-size\" must be an integer.");
+    query::print_query_result(trie_path, max_db_size, state_root_hash, key, path)
+}
@@ -0,0 +1,65 @@
+use std::{
+    convert::TryFrom,
+    io::{self, Write},
+    path::Path,
+};
+
+use casper_execution_engine::{
+    core::engine_state::{EngineState, QueryRequest, QueryResult},
+    shared::newtypes::CorrelationId,
+    storage::global_state::lmdb::LmdbGlobalState,
+};
+use casper_hashing::Digest;
+use casper_node::types::json_compatibility::StoredValue as JsonStoredValue;
+use casper_types::{Key, StoredValue};
+
+use crate::subcommands::trie_compact::load_execution_engine;
+
+use super::Error;
+
+/// Returns the value under `key` in the global state at `state_root_hash`,
+/// following the named keys in `path`.
+pub(crate) fn query_state<P: AsRef<Path>>(
+    trie_path: P,
+    max_db_size: usize,
+    state_root_hash: Digest,
+    key: Key,
+    path: Vec<String>,
+) -> Result<StoredValue, Error> {
+    let (engine_state, _env) =
+        load_execution_engine(trie_path, max_db_size, Digest::default(), true)
+            .map_err(Error::OpenTrie)?;
+    run_query(&engine_state, state_root_hash, key, path)
+}
+
+/// Same as [`query_state`], with an already loaded execution engine.
+pub(crate) fn run_query(
+    engine_state: &EngineState<LmdbGlobalState>,
+    state_root_hash: Digest,
+    key: Key,
+    path: Vec<String>,
+) -> Result<StoredValue, Error> {
+    let query_request = QueryRequest::new(state_root_hash, key, path);
+    match engine_state.run_query(CorrelationId::new(), query_request)? {
+        QueryResult::Success { value, .. } => Ok(*value),
+        QueryResult::RootNotFound => Err(Error::RootNotFound(state_root_hash)),
+        QueryResult::ValueNotFound(message) => Err(Error::ValueNotFound(message)),
+        QueryResult::CircularReference(message) => Err(Error::CircularReference(message)),
+        QueryResult::DepthLimit { depth } => Err(Error::DepthLimit(depth)),
+    }
+}
+
+pub fn print_query_result<P: AsRef<Path>>(
+    trie_path: P,
+    max_db_size: usize,
+    state_root_hash: Digest,
+    key: Key,
+    path: Vec<String>,
+) -> Result<(), Error> {
+    let value = query_state(trie_path, max_db_size, state_root_hash, key, path)?;
+    let json_value = JsonStoredValue::try_from(value).map_err(Error::ValueConversion)?;
+    let mut out = io::stdout();
+    serde_json::to_writer_pretty(&mut out, &json_value)?;
+    writeln!(out)?;
+    Ok(())
+}
@@ -0,0 +1,102 @@
+use std::collections::{BTreeMap, HashMap};
+
+use casper_hashing::Digest;
+use casper_types::{
+    account::{Account, AccountHash},
+    AccessRights, CLValue, Key, StoredValue, URef,
+};
+
+use crate::subcommands::trie_compact::tests::{create_test_global_state, DEFAULT_MAX_DB_SIZE};
+
+use super::{query, Error};
+
+const ACCOUNT_HASH: AccountHash = AccountHash::new([1u8; 32]);
+
+fn test_stored_values() -> HashMap<Key, StoredValue> {
+    let mut named_keys = BTreeMap::new();
+    named_keys.insert(String::from("counter"), Key::Hash([2u8; 32]));
+    named_keys.insert(String::from("missing"), Key::Hash([3u8; 32]));
+    let main_purse = URef::new([4u8; 32], AccessRights::READ_ADD_WRITE);
+
+    let mut stored_values = HashMap::new();
+    stored_values.insert(
+        Key::Account(ACCOUNT_HASH),
+        StoredValue::Account(Account::create(ACCOUNT_HASH, named_keys, main_purse)),
+    );
+    stored_values.insert(
+        Key::Hash([2u8; 32]),
+        StoredValue::CLValue(CLValue::from_t(7u64).unwrap()),
+    );
+    stored_values
+}
+
+#[test]
+fn query_should_return_value_under_key() {
+    let (trie_dir, state_root_hash) = create_test_global_state(test_stored_values());
+
+    let value = query::query_state(
+        trie_dir.path(),
+        *DEFAULT_MAX_DB_SIZE,
+        state_root_hash,
+        Key::Account(ACCOUNT_HASH),
+        vec![],
+    )
+    .unwrap();
+    assert_eq!(value, test_stored_values()[&Key::Account(ACCOUNT_HASH)]);
+}
+
+#[test]
+fn query_should_follow_named_keys() {
+    let (trie_dir, state_root_hash) = create_test_global_state(test_stored_values());
+
+    let value = query::query_state(
+        trie_dir.path(),
+        *DEFAULT_MAX_DB_SIZE,
+        state_root_hash,
+        Key::Account(ACCOUNT_HASH),
+        vec![String::from("counter")],
+    )
+    .unwrap();
+    assert_eq!(value, StoredValue::CLValue(CLValue::from_t(7u64).unwrap()));
+}
+
+#[test]
+fn query_should_fail_on_missing_value() {
+    let (trie_dir, state_root_hash) = create_test_global_state(test_stored_values());
+
+    for (key, path) in [
+        (Key::Hash([5u8; 32]), vec![]),
+        (Key::Account(ACCOUNT_HASH), vec![String::from("missing")]),
+        (Key::Account(ACCOUNT_HASH), vec![String::from("unknown")]),
+    ] {
+        match query::query_state(
+            trie_dir.path(),
+            *DEFAULT_MAX_DB_SIZE,
+            state_root_hash,
+            key,
+            path,
+        ) {
+            Err(Error::ValueNotFound(_)) => {}
+            Err(err) => panic!("Unexpected error: {err}"),
+            Ok(value) => panic!("Unexpected value: {value:?}"),
+        }
+    }
+}
+
+#[test]
+fn query_should_fail_on_missing_state_root() {
+    let (trie_dir, _state_root_hash) = create_test_global_state(test_stored_values());
+    let missing_root = Digest::hash(b"missing");
+
+    match query::query_state(
+        trie_dir.path(),
+        *DEFAULT_MAX_DB_SIZE,
+        missing_root,
+        Key::Account(ACCOUNT_HASH),
+        vec![],
+    ) {
+        Err(Error::RootNotFound(state_root_hash)) => assert_eq!(state_root_hash, missing_root),
+        Err(err) => panic!("Unexpected error: {err}"),
+        Ok(value) => panic!("Unexpected value: {value:?}"),
+    }
+}
@@ -0,0 +1,127 @@
+mod quarantine;
+#[cfg(test)]
+mod tests;
+
+use std::{io::Error as IoError, path::PathBuf};
+
+use clap::{Arg, ArgMatches, Command};
+use lmdb::Error as LmdbError;
+use serde_json::Error as JsonError;
+use thiserror::Error as ThisError;
+
+pub const COMMAND_NAME: &str = "repair";
+const BATCH_SIZE: &str = "batch-size";
+const DB_PATH: &str = "db-path";
+const SPECIFIC: &str = "specific";
+const UNDO: &str = "undo";
+const UNDO_LOG: &str = "undo-log";
+
+/// Errors encountered when operating on the storage database.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Database operation error.
+    #[error("Error operating the database: {0}")]
+    Database(#[from] LmdbError),
+    /// Error initializing the lmdb environment.
+    #[error("Error initializing lmdb environment at {0}: {1}")]
+    Path(PathBuf, LmdbError),
+    /// Unknown database name.
+    #[error("Unknown database {0}")]
+    UnknownDb(String),
+    /// Error reading or writing the undo log.
+    #[error("Error accessing undo log {0}: {1}")]
+    UndoLog(PathBuf, IoError),
+    /// Error (de)serializing an undo log entry.
+    #[error("Error (de)serializing entry {0} of the undo log: {1}")]
+    UndoLogEntry(usize, JsonError),
+}
+
+enum DisplayOrder {
+    DbPath,
+    Specific,
+    UndoLog,
+    Undo,
+    BatchSize,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Moves the entries of a storage database which fail to deserialize into a \
+            quarantine database in the same environment, or puts them back with \"--undo\".",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help("Path of the directory with the `storage.lmdb` file."),
+        )
+        .arg(
+            Arg::new(SPECIFIC)
+                .display_order(DisplayOrder::Specific as usize)
+                .short('s')
+                .long(SPECIFIC)
+                .takes_value(true)
+                .value_name("DB_NAME")
+                .conflicts_with(UNDO)
+                .help("Repair a specific database. If unspecified, all databases are repaired."),
+        )
+        .arg(
+            Arg::new(UNDO_LOG)
+                .display_order(DisplayOrder::UndoLog as usize)
+                .required(true)
+                .short('u')
+                .long(UNDO_LOG)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help(
+                    "Path of the file recording the quarantined entries. New entries are \
+                    appended to it.",
+                ),
+        )
+        .arg(
+            Arg::new(UNDO)
+                .display_order(DisplayOrder::Undo as usize)
+                .long(UNDO)
+                .takes_value(false)
+                .help(
+                    "Put the entries recorded in the undo log back into their original \
+                    databases.",
+                ),
+        )
+        .arg(
+            Arg::new(BATCH_SIZE)
+                .display_order(DisplayOrder::BatchSize as usize)
+                .short('b')
+                .long(BATCH_SIZE)
+                .takes_value(true)
+                .value_name("ENTRY_COUNT")
+                .default_value("1000")
+                .help("Maximum number of entries moved in a single write transaction."),
+        )
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let path = matches.value_of(DB_PATH).expect("should have db-path arg");
+    let undo_log = matches
+        .value_of(UNDO_LOG)
+        .expect("should have undo-log arg");
+    let batch_size: usize = matches
+        .value_of(BATCH_SIZE)
+        .expect("should have a default")
+        .parse()
+        .ok()
+        .filter(|batch_size| *batch_size > 0)
+        .unwrap_or_else(|| panic!("Value of \"--{BATCH_SIZE}\" must be a positive integer."));
+    if matches.is_present(UNDO) {
+        quarantine::undo_repair(path, undo_log, batch_size)
+    } else {
+        let specific = matches.value_of(SPECIFIC);
+        quarantine::repair(path, specific, undo_log, batch_size)
+    }
+}
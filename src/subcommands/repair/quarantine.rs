@@ -0,0 +1,277 @@
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use lmdb::{
+    Cursor, Database, DatabaseFlags, Environment, Error as LmdbError, Transaction, WriteFlags,
+};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::common::db::{self, DB_NAMES, STORAGE_FILE_NAME};
+
+use super::Error;
+
+const QUARANTINE_PREFIX: &str = "quarantine_";
+
+/// Record of an entry moved into quarantine.
+#[derive(Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub(crate) struct UndoEntry {
+    /// Name of the database the entry was moved out of.
+    pub(crate) db_name: String,
+    /// Key of the entry.
+    #[serde(with = "hex")]
+    pub(crate) key: Vec<u8>,
+}
+
+/// Append-only log of the quarantined entries, one JSON object per line.
+pub(crate) struct UndoLog {
+    path: PathBuf,
+    writer: BufWriter<File>,
+}
+
+impl UndoLog {
+    /// Opens the undo log at `path`, creating it if it doesn't exist.
+    pub(crate) fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|io_err| Error::UndoLog(path.clone(), io_err))?;
+        Ok(Self {
+            path,
+            writer: BufWriter::new(file),
+        })
+    }
+
+    /// Appends the entries to the log and makes sure they reach the disk.
+    fn append(&mut self, entries: &[UndoEntry]) -> Result<(), Error> {
+        for (idx, entry) in entries.iter().enumerate() {
+            serde_json::to_writer(&mut self.writer, entry)
+                .map_err(|json_err| Error::UndoLogEntry(idx, json_err))?;
+            self.writer
+                .write_all(b"\n")
+                .map_err(|io_err| Error::UndoLog(self.path.clone(), io_err))?;
+        }
+        self.writer
+            .flush()
+            .and_then(|_| self.writer.get_ref().sync_data())
+            .map_err(|io_err| Error::UndoLog(self.path.clone(), io_err))
+    }
+}
+
+/// Reads all the entries recorded in the undo log at `path`.
+pub(crate) fn read_undo_log<P: AsRef<Path>>(path: P) -> Result<Vec<UndoEntry>, Error> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|io_err| Error::UndoLog(path.to_path_buf(), io_err))?;
+    let mut entries = vec![];
+    for (idx, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|io_err| Error::UndoLog(path.to_path_buf(), io_err))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: UndoEntry =
+            serde_json::from_str(&line).map_err(|json_err| Error::UndoLogEntry(idx, json_err))?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Returns the name of the quarantine database for `db_name`.
+pub(crate) fn quarantine_db_name(db_name: &str) -> String {
+    format!("{QUARANTINE_PREFIX}{db_name}")
+}
+
+/// Moves every entry of `db_name` which fails to parse into the quarantine
+/// database, at most `batch_size` entries per write transaction.
+///
+/// The entries of each batch are recorded in the undo log before the batch
+/// is committed. Returns the number of quarantined entries.
+pub(crate) fn quarantine_entries(
+    env: &Environment,
+    db_name: &str,
+    batch_size: usize,
+    undo_log: &mut UndoLog,
+) -> Result<usize, Error> {
+    let parse_fn = db::parse_fn(db_name).ok_or_else(|| Error::UnknownDb(db_name.to_string()))?;
+    info!("Repairing {} database.", db_name);
+    let db = env.open_db(Some(db_name))?;
+
+    let corrupted_keys: Vec<Vec<u8>> = {
+        let txn = env.begin_ro_txn()?;
+        let mut keys = vec![];
+        {
+            let mut cursor = txn.open_ro_cursor(db)?;
+            for (raw_key, raw_val) in cursor.iter() {
+                if let Err(parsing_err) = parse_fn(raw_val) {
+                    warn!(
+                        "Entry with key {} in {} database failed to parse: {}",
+                        hex::encode(raw_key),
+                        db_name,
+                        parsing_err
+                    );
+                    keys.push(raw_key.to_vec());
+                }
+            }
+        }
+        txn.commit()?;
+        keys
+    };
+    if corrupted_keys.is_empty() {
+        info!("No corrupted entries found in {} database.", db_name);
+        return Ok(0);
+    }
+
+    let quarantine_db =
+        env.create_db(Some(&quarantine_db_name(db_name)), DatabaseFlags::empty())?;
+    for batch in corrupted_keys.chunks(batch_size) {
+        let entries: Vec<UndoEntry> = batch
+            .iter()
+            .map(|key| UndoEntry {
+                db_name: db_name.to_string(),
+                key: key.clone(),
+            })
+            .collect();
+        // Record the batch before moving it, so that an interruption never
+        // leaves quarantined entries missing from the log.
+        undo_log.append(&entries)?;
+        let mut txn = env.begin_rw_txn()?;
+        for key in batch {
+            let value = txn.get(db, key)?.to_vec();
+            txn.put(quarantine_db, key, &value, WriteFlags::empty())?;
+            txn.del(db, key, None)?;
+        }
+        txn.commit()?;
+    }
+    info!(
+        "Moved {} entries from {} to {} database.",
+        corrupted_keys.len(),
+        db_name,
+        quarantine_db_name(db_name)
+    );
+    Ok(corrupted_keys.len())
+}
+
+/// Moves the entries recorded in the undo log out of quarantine and back
+/// into their original databases, at most `batch_size` entries per write
+/// transaction.
+///
+/// Entries no longer in quarantine, or whose key was meanwhile reused in
+/// the original database, are skipped. Returns the number of restored
+/// entries.
+pub(crate) fn restore_entries(
+    env: &Environment,
+    entries: &[UndoEntry],
+    batch_size: usize,
+) -> Result<usize, Error> {
+    // Databases can't be opened while a write transaction is in progress,
+    // so open all of them upfront.
+    let mut dbs: HashMap<&str, (Database, Database)> = HashMap::new();
+    for entry in entries {
+        if dbs.contains_key(entry.db_name.as_str()) {
+            continue;
+        }
+        if db::parse_fn(&entry.db_name).is_none() {
+            return Err(Error::UnknownDb(entry.db_name.clone()));
+        }
+        let db = env.open_db(Some(&entry.db_name))?;
+        let quarantine_db = env.create_db(
+            Some(&quarantine_db_name(&entry.db_name)),
+            DatabaseFlags::empty(),
+        )?;
+        let _ = dbs.insert(&entry.db_name, (db, quarantine_db));
+    }
+
+    let mut restored_count = 0;
+    for batch in entries.chunks(batch_size) {
+        let mut txn = env.begin_rw_txn()?;
+        for entry in batch {
+            let (db, quarantine_db) = dbs[entry.db_name.as_str()];
+            let value = match txn.get(quarantine_db, &entry.key) {
+                Ok(value) => value.to_vec(),
+                Err(LmdbError::NotFound) => {
+                    warn!(
+                        "Entry with key {} is not in the {} database, skipping.",
+                        hex::encode(&entry.key),
+                        quarantine_db_name(&entry.db_name)
+                    );
+                    continue;
+                }
+                Err(lmdb_err) => return Err(lmdb_err.into()),
+            };
+            match txn.put(db, &entry.key, &value, WriteFlags::NO_OVERWRITE) {
+                Ok(()) => {}
+                Err(LmdbError::KeyExist) => {
+                    warn!(
+                        "Entry with key {} already exists in the {} database, leaving it in \
+                        quarantine.",
+                        hex::encode(&entry.key),
+                        entry.db_name
+                    );
+                    continue;
+                }
+                Err(lmdb_err) => return Err(lmdb_err.into()),
+            }
+            txn.del(quarantine_db, &entry.key, None)?;
+            restored_count += 1;
+        }
+        txn.commit()?;
+    }
+    Ok(restored_count)
+}
+
+fn open_env<P: AsRef<Path>>(db_path: P) -> Result<Environment, Error> {
+    let storage_path = db_path.as_ref().join(STORAGE_FILE_NAME);
+    db::db_env(storage_path)
+        .map_err(|lmdb_err| Error::Path(db_path.as_ref().to_path_buf(), lmdb_err))
+}
+
+pub(crate) fn repair<P1: AsRef<Path>, P2: AsRef<Path>>(
+    db_path: P1,
+    specific: Option<&str>,
+    undo_log_path: P2,
+    batch_size: usize,
+) -> Result<(), Error> {
+    let db_names = match specific {
+        Some(db_name) => {
+            let db_name = db_name.trim();
+            if db::parse_fn(db_name).is_none() {
+                return Err(Error::UnknownDb(db_name.to_string()));
+            }
+            vec![db_name]
+        }
+        None => DB_NAMES.to_vec(),
+    };
+    let env = open_env(db_path)?;
+    let mut undo_log = UndoLog::open(&undo_log_path)?;
+    let mut quarantined_count = 0;
+    for db_name in db_names {
+        quarantined_count += quarantine_entries(&env, db_name, batch_size, &mut undo_log)?;
+    }
+    info!(
+        "Quarantined {} entries, recorded in {}.",
+        quarantined_count,
+        undo_log_path.as_ref().display()
+    );
+    Ok(())
+}
+
+pub(crate) fn undo_repair<P1: AsRef<Path>, P2: AsRef<Path>>(
+    db_path: P1,
+    undo_log_path: P2,
+    batch_size: usize,
+) -> Result<(), Error> {
+    let entries = read_undo_log(undo_log_path)?;
+    let env = open_env(db_path)?;
+    let restored_count = restore_entries(&env, &entries, batch_size)?;
+    info!(
+        "Restored {} out of {} entries recorded in the undo log.",
+        restored_count,
+        entries.len()
+    );
+    Ok(())
+}
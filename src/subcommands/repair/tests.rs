@@ -0,0 +1,191 @@
+use std::fs;
+
+use lmdb::{Error as LmdbError, Transaction, WriteFlags};
+
+use crate::{
+    common::db::{BlockHeaderDatabase, Database, STORAGE_FILE_NAME},
+    subcommands::repair::{
+        quarantine::{self, UndoEntry, UndoLog},
+        Error,
+    },
+    test_utils::{mock_block_header, LmdbTestFixture},
+};
+
+const VALID_COUNT: u8 = 6;
+const CORRUPTED_COUNT: u8 = 5;
+
+/// Populates the header database with valid headers for keys below
+/// `VALID_COUNT` and garbage for the following `CORRUPTED_COUNT` keys.
+fn populate_headers(fixture: &LmdbTestFixture) {
+    let db = fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap();
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    for idx in 0..VALID_COUNT {
+        let (block_hash, header) = mock_block_header(idx);
+        txn.put(
+            *db,
+            &block_hash,
+            &bincode::serialize(&header).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+    }
+    for idx in VALID_COUNT..VALID_COUNT + CORRUPTED_COUNT {
+        txn.put(*db, &[idx; 32], &[idx; 3], WriteFlags::empty())
+            .unwrap();
+    }
+    txn.commit().unwrap();
+}
+
+fn header_keys(fixture: &LmdbTestFixture) -> Vec<[u8; 32]> {
+    let db = fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap();
+    let txn = fixture.env.begin_ro_txn().unwrap();
+    let keys = (0..VALID_COUNT + CORRUPTED_COUNT)
+        .map(|idx| [idx; 32])
+        .filter(|key| match txn.get(*db, key) {
+            Ok(_) => true,
+            Err(LmdbError::NotFound) => false,
+            Err(lmdb_err) => panic!("unexpected error {lmdb_err}"),
+        })
+        .collect();
+    txn.commit().unwrap();
+    keys
+}
+
+#[test]
+fn repair_should_quarantine_corrupted_entries() {
+    let fixture = LmdbTestFixture::new(vec![BlockHeaderDatabase::db_name()], None);
+    populate_headers(&fixture);
+    let undo_log_path = fixture.tmp_dir.path().join("undo.log");
+    let mut undo_log = UndoLog::open(&undo_log_path).unwrap();
+
+    // A batch size which doesn't divide the corrupted entry count.
+    let quarantined = quarantine::quarantine_entries(
+        &fixture.env,
+        BlockHeaderDatabase::db_name(),
+        2,
+        &mut undo_log,
+    )
+    .unwrap();
+    assert_eq!(quarantined, CORRUPTED_COUNT as usize);
+    let expected_keys: Vec<[u8; 32]> = (0..VALID_COUNT).map(|idx| [idx; 32]).collect();
+    assert_eq!(header_keys(&fixture), expected_keys);
+
+    let quarantine_db = fixture
+        .env
+        .open_db(Some(&quarantine::quarantine_db_name(
+            BlockHeaderDatabase::db_name(),
+        )))
+        .unwrap();
+    let txn = fixture.env.begin_ro_txn().unwrap();
+    for idx in VALID_COUNT..VALID_COUNT + CORRUPTED_COUNT {
+        assert_eq!(txn.get(quarantine_db, &[idx; 32]).unwrap(), &[idx; 3]);
+    }
+    txn.commit().unwrap();
+
+    let logged_entries = quarantine::read_undo_log(&undo_log_path).unwrap();
+    let expected_entries: Vec<UndoEntry> = (VALID_COUNT..VALID_COUNT + CORRUPTED_COUNT)
+        .map(|idx| UndoEntry {
+            db_name: BlockHeaderDatabase::db_name().to_string(),
+            key: vec![idx; 32],
+        })
+        .collect();
+    assert_eq!(logged_entries, expected_entries);
+
+    // A second run finds nothing left to quarantine.
+    assert_eq!(
+        quarantine::quarantine_entries(
+            &fixture.env,
+            BlockHeaderDatabase::db_name(),
+            2,
+            &mut undo_log,
+        )
+        .unwrap(),
+        0
+    );
+    assert_eq!(
+        quarantine::read_undo_log(&undo_log_path).unwrap().len(),
+        CORRUPTED_COUNT as usize
+    );
+}
+
+#[test]
+fn undo_should_restore_quarantined_entries() {
+    let fixture = LmdbTestFixture::new(
+        vec![BlockHeaderDatabase::db_name()],
+        Some(STORAGE_FILE_NAME),
+    );
+    populate_headers(&fixture);
+    let undo_log_path = fixture.tmp_dir.path().join("undo.log");
+
+    quarantine::repair(
+        fixture.tmp_dir.path(),
+        Some(BlockHeaderDatabase::db_name()),
+        &undo_log_path,
+        3,
+    )
+    .unwrap();
+    assert_eq!(header_keys(&fixture).len(), VALID_COUNT as usize);
+
+    // Reuse one of the quarantined keys, it should stay in quarantine.
+    let reused_key = [VALID_COUNT; 32];
+    {
+        let db = fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap();
+        let mut txn = fixture.env.begin_rw_txn().unwrap();
+        txn.put(*db, &reused_key, &[0u8], WriteFlags::empty())
+            .unwrap();
+        txn.commit().unwrap();
+    }
+
+    quarantine::undo_repair(fixture.tmp_dir.path(), &undo_log_path, 3).unwrap();
+    assert_eq!(
+        header_keys(&fixture).len(),
+        (VALID_COUNT + CORRUPTED_COUNT) as usize
+    );
+    let db = fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap();
+    let quarantine_db = fixture
+        .env
+        .open_db(Some(&quarantine::quarantine_db_name(
+            BlockHeaderDatabase::db_name(),
+        )))
+        .unwrap();
+    let txn = fixture.env.begin_ro_txn().unwrap();
+    assert_eq!(txn.get(*db, &reused_key).unwrap(), &[0u8]);
+    assert_eq!(
+        txn.get(quarantine_db, &reused_key).unwrap(),
+        &[VALID_COUNT; 3]
+    );
+    for idx in VALID_COUNT + 1..VALID_COUNT + CORRUPTED_COUNT {
+        assert_eq!(txn.get(*db, &[idx; 32]).unwrap(), &[idx; 3]);
+        assert!(matches!(
+            txn.get(quarantine_db, &[idx; 32]),
+            Err(LmdbError::NotFound)
+        ));
+    }
+    txn.commit().unwrap();
+}
+
+#[test]
+fn repair_unknown_db_should_fail() {
+    let fixture = LmdbTestFixture::new(
+        vec![BlockHeaderDatabase::db_name()],
+        Some(STORAGE_FILE_NAME),
+    );
+    let undo_log_path = fixture.tmp_dir.path().join("undo.log");
+    assert!(matches!(
+        quarantine::repair(fixture.tmp_dir.path(), Some("bogus"), &undo_log_path, 3),
+        Err(Error::UnknownDb(_))
+    ));
+    assert!(!undo_log_path.exists());
+
+    fs::write(&undo_log_path, "{\"db_name\":\"bogus\",\"key\":\"00\"}\n").unwrap();
+    assert!(matches!(
+        quarantine::undo_repair(fixture.tmp_dir.path(), &undo_log_path, 3),
+        Err(Error::UnknownDb(_))
+    ));
+
+    fs::write(&undo_log_path, "not an entry\n").unwrap();
+    assert!(matches!(
+        quarantine::undo_repair(fixture.tmp_dir.path(), &undo_log_path, 3),
+        Err(Error::UndoLogEntry(0, _))
+    ));
+}
@@ -0,0 +1,217 @@
+mod diff;
+#[cfg(test)]
+mod tests;
+
+use std::{io::Error as IoError, path::Path};
+
+use anyhow::Error as AnyError;
+use clap::{Arg, ArgMatches, Command};
+use serde_json::Error as JsonSerializationError;
+use thiserror::Error as ThisError;
+
+use casper_hashing::Digest;
+use casper_types::{bytesrepr::Error as BytesreprError, Key};
+
+use super::{
+    dump_state::{StateRootError, StateRootSelector},
+    trie_compact::DEFAULT_MAX_DB_SIZE,
+};
+use crate::common::trie_walk::Error as TrieWalkError;
+
+pub const COMMAND_NAME: &str = "state-diff";
+const MAX_DB_SIZE: &str = "max-db-size";
+const NEW_BLOCK_HASH: &str = "new-block-hash";
+const NEW_STATE_ROOT_HASH: &str = "new-state-root-hash";
+const OLD_BLOCK_HASH: &str = "old-block-hash";
+const OLD_STATE_ROOT_HASH: &str = "old-state-root-hash";
+const OUTPUT: &str = "output";
+const OVERWRITE: &str = "overwrite";
+const STORAGE_PATH: &str = "storage-path";
+const TRIE_STORE_PATH: &str = "trie";
+
+/// Errors encountered when diffing two state roots.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error("Error serializing output: {0}")]
+    JsonSerialize(#[from] JsonSerializationError),
+    /// Error opening the trie store.
+    #[error("Error opening the trie store: {0}")]
+    OpenTrie(AnyError),
+    #[error("Error writing output: {0}")]
+    Output(#[from] IoError),
+    /// Error finding a state root to diff.
+    #[error("Error finding the state root: {0}")]
+    StateRoot(#[from] StateRootError),
+    /// Error converting the stored value under the given key to JSON.
+    #[error("Error converting the value under {} to JSON: {1}", .0.to_formatted_string())]
+    ValueConversion(Key, BytesreprError),
+    /// Error walking the tries under the state roots.
+    #[error("Error walking the tries: {0}")]
+    Walk(#[from] TrieWalkError),
+}
+
+enum DisplayOrder {
+    TriePath,
+    OldStateRootHash,
+    OldBlockHash,
+    NewStateRootHash,
+    NewBlockHash,
+    StoragePath,
+    MaxDbSize,
+    Output,
+    Overwrite,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Writes the keys added, removed and modified between two state roots, with their \
+            old and new values, as one JSON object per line.",
+        )
+        .arg(
+            Arg::new(TRIE_STORE_PATH)
+                .display_order(DisplayOrder::TriePath as usize)
+                .required(true)
+                .short('t')
+                .long(TRIE_STORE_PATH)
+                .takes_value(true)
+                .value_name("TRIE_STORE_DIR_PATH")
+                .help("Path of the directory with the `data.lmdb` file."),
+        )
+        .arg(
+            Arg::new(OLD_STATE_ROOT_HASH)
+                .display_order(DisplayOrder::OldStateRootHash as usize)
+                .required_unless_present(OLD_BLOCK_HASH)
+                .conflicts_with(OLD_BLOCK_HASH)
+                .long(OLD_STATE_ROOT_HASH)
+                .takes_value(true)
+                .value_name("STATE_ROOT_HASH")
+                .help("Hex encoded hash of the old state root."),
+        )
+        .arg(
+            Arg::new(OLD_BLOCK_HASH)
+                .display_order(DisplayOrder::OldBlockHash as usize)
+                .requires(STORAGE_PATH)
+                .long(OLD_BLOCK_HASH)
+                .takes_value(true)
+                .value_name("BLOCK_HASH")
+                .help("Hex encoded hash of the block with the old state root."),
+        )
+        .arg(
+            Arg::new(NEW_STATE_ROOT_HASH)
+                .display_order(DisplayOrder::NewStateRootHash as usize)
+                .required_unless_present(NEW_BLOCK_HASH)
+                .conflicts_with(NEW_BLOCK_HASH)
+                .long(NEW_STATE_ROOT_HASH)
+                .takes_value(true)
+                .value_name("STATE_ROOT_HASH")
+                .help("Hex encoded hash of the new state root."),
+        )
+        .arg(
+            Arg::new(NEW_BLOCK_HASH)
+                .display_order(DisplayOrder::NewBlockHash as usize)
+                .requires(STORAGE_PATH)
+                .long(NEW_BLOCK_HASH)
+                .takes_value(true)
+                .value_name("BLOCK_HASH")
+                .help("Hex encoded hash of the block with the new state root."),
+        )
+        .arg(
+            Arg::new(STORAGE_PATH)
+                .display_order(DisplayOrder::StoragePath as usize)
+                .short('b')
+                .long(STORAGE_PATH)
+                .takes_value(true)
+                .value_name("STORAGE_DIR_PATH")
+                .help(
+                    "Path of the directory with the `storage.lmdb` file. Used to find the \
+                    state root hashes of the blocks given by `--old-block-hash` and \
+                    `--new-block-hash`.",
+                ),
+        )
+        .arg(
+            Arg::new(MAX_DB_SIZE)
+                .display_order(DisplayOrder::MaxDbSize as usize)
+                .required(false)
+                .short('m')
+                .long(MAX_DB_SIZE)
+                .takes_value(true)
+                .default_value(DEFAULT_MAX_DB_SIZE)
+                .value_name("MAX_DB_SIZE")
+                .help("Maximum size the DB files are allowed to be, in bytes."),
+        )
+        .arg(
+            Arg::new(OUTPUT)
+                .display_order(DisplayOrder::Output as usize)
+                .short('o')
+                .long(OUTPUT)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help(
+                    "Path to where the program will output the changed entries. \
+                    If unspecified, defaults to standard output.",
+                ),
+        )
+        .arg(
+            Arg::new(OVERWRITE)
+                .display_order(DisplayOrder::Overwrite as usize)
+                .required(false)
+                .short('w')
+                .long(OVERWRITE)
+                .takes_value(false)
+                .requires(OUTPUT)
+                .help(
+                    "Overwrite an already existing output file in destination \
+                    directory.",
+                ),
+        )
+}
+
+/// Returns the state root given by either of the `state_root_hash_arg` or
+/// `block_hash_arg` arguments.
+fn resolve_state_root(
+    matches: &ArgMatches,
+    state_root_hash_arg: &str,
+    block_hash_arg: &str,
+) -> Result<Digest, StateRootError> {
+    let selector = if let Some(state_root_hash_str) = matches.value_of(state_root_hash_arg) {
+        StateRootSelector::StateRootHash(
+            Digest::from_hex(state_root_hash_str)
+                .expect("should parse state root hash to hex format"),
+        )
+    } else {
+        StateRootSelector::BlockHash(
+            matches
+                .value_of(block_hash_arg)
+                .map(|block_hash_str| {
+                    Digest::from_hex(block_hash_str)
+                        .expect("should parse block hash to hex format")
+                        .into()
+                })
+                .expect("should have either state root hash or block hash arg"),
+        )
+    };
+    selector.resolve(matches.value_of(STORAGE_PATH))
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let trie_path = Path::new(matches.value_of(TRIE_STORE_PATH).unwrap());
+    let old_state_root = resolve_state_root(matches, OLD_STATE_ROOT_HASH, OLD_BLOCK_HASH)?;
+    let new_state_root = resolve_state_root(matches, NEW_STATE_ROOT_HASH, NEW_BLOCK_HASH)?;
+    let max_db_size = matches
+        .value_of(MAX_DB_SIZE)
+        .unwrap()
+        .parse()
+        .expect("Value of \"--max-db-size\" must be an integer.");
+    let output = matches.value_of(OUTPUT).map(Path::new);
+    let overwrite = matches.is_present(OVERWRITE);
+    diff::state_diff(
+        trie_path,
+        max_db_size,
+        old_state_root,
+        new_state_root,
+        output,
+        overwrite,
+    )
+}
@@ -0,0 +1,263 @@
+use std::{
+    collections::BTreeMap,
+    convert::TryFrom,
+    fs::OpenOptions,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use lmdb::{Database, RoTransaction};
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use casper_execution_engine::storage::{
+    transaction_source::{Transaction, TransactionSource},
+    trie::Trie,
+};
+use casper_hashing::Digest;
+use casper_node::types::json_compatibility::StoredValue as JsonStoredValue;
+use casper_types::{Key, StoredValue};
+
+use crate::{
+    common::trie_walk::{self, Error as TrieWalkError},
+    subcommands::trie_compact::load_execution_engine,
+};
+
+use super::Error;
+
+/// Kind of change of a global state entry.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ChangeKind {
+    /// The key is only under the new state root.
+    Added,
+    /// The key is only under the old state root.
+    Removed,
+    /// The key is under both state roots, with different values.
+    Modified,
+}
+
+/// A changed global state entry, as written on each line of the diff.
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct DiffEntry {
+    /// Formatted string of the key.
+    pub(crate) key: String,
+    pub(crate) change: ChangeKind,
+    /// JSON representation of the value under the old state root.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) old: Option<JsonStoredValue>,
+    /// JSON representation of the value under the new state root.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) new: Option<JsonStoredValue>,
+}
+
+/// Number of changed entries by kind.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct DiffSummary {
+    pub(crate) added: usize,
+    pub(crate) removed: usize,
+    pub(crate) modified: usize,
+}
+
+/// Returns the leaves under `trie_key`, or none if there is no trie.
+fn collect_leaves(
+    txn: &RoTransaction,
+    db: Database,
+    trie_key: Option<Digest>,
+) -> Result<BTreeMap<Key, StoredValue>, TrieWalkError> {
+    let mut leaves = BTreeMap::new();
+    if let Some(trie_key) = trie_key {
+        trie_walk::walk_tries_in_txn(txn, db, trie_key, |trie, _depth| {
+            if let Trie::Leaf { key, value } = trie {
+                leaves.insert(*key, value.clone());
+            }
+            Ok::<_, TrieWalkError>(())
+        })?;
+    }
+    Ok(leaves)
+}
+
+/// Walks the tries under `old_state_root` and `new_state_root` side by side,
+/// calling `visit` with the key, old value and new value of every changed
+/// entry.
+///
+/// Subtries with the same digest under both state roots are identical, so
+/// they are skipped. Where the two tries stop having the same shape, the
+/// leaves of both subtries are compared instead.
+pub(crate) fn diff_state_roots<E, F>(
+    txn: &RoTransaction,
+    db: Database,
+    old_state_root: Digest,
+    new_state_root: Digest,
+    mut visit: F,
+) -> Result<(), E>
+where
+    E: From<TrieWalkError>,
+    F: FnMut(Key, Option<StoredValue>, Option<StoredValue>) -> Result<(), E>,
+{
+    let mut pending_pairs = vec![(Some(old_state_root), Some(new_state_root))];
+    while let Some((old_trie_key, new_trie_key)) = pending_pairs.pop() {
+        if old_trie_key == new_trie_key {
+            continue;
+        }
+        let old_trie = old_trie_key
+            .map(|trie_key| trie_walk::read_trie(txn, db, trie_key))
+            .transpose()?;
+        let new_trie = new_trie_key
+            .map(|trie_key| trie_walk::read_trie(txn, db, trie_key))
+            .transpose()?;
+        match (old_trie, new_trie) {
+            (
+                Some(Trie::Node {
+                    pointer_block: old_block,
+                }),
+                Some(Trie::Node {
+                    pointer_block: new_block,
+                }),
+            ) => {
+                // Push the children in reverse so they are visited in key
+                // order.
+                for index in (0..=u8::MAX as usize).rev() {
+                    let old_child = old_block[index].map(|pointer| *pointer.hash());
+                    let new_child = new_block[index].map(|pointer| *pointer.hash());
+                    if old_child != new_child {
+                        pending_pairs.push((old_child, new_child));
+                    }
+                }
+            }
+            (
+                Some(Trie::Extension {
+                    affix: old_affix,
+                    pointer: old_pointer,
+                }),
+                Some(Trie::Extension {
+                    affix: new_affix,
+                    pointer: new_pointer,
+                }),
+            ) if old_affix == new_affix => {
+                pending_pairs.push((Some(*old_pointer.hash()), Some(*new_pointer.hash())))
+            }
+            _ => {
+                let mut changes: BTreeMap<Key, (Option<StoredValue>, Option<StoredValue>)> =
+                    collect_leaves(txn, db, old_trie_key)?
+                        .into_iter()
+                        .map(|(key, value)| (key, (Some(value), None)))
+                        .collect();
+                for (key, value) in collect_leaves(txn, db, new_trie_key)? {
+                    changes.entry(key).or_default().1 = Some(value);
+                }
+                for (key, (old_value, new_value)) in changes {
+                    if old_value != new_value {
+                        visit(key, old_value, new_value)?;
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn json_value(key: Key, value: Option<StoredValue>) -> Result<Option<JsonStoredValue>, Error> {
+    value
+        .map(JsonStoredValue::try_from)
+        .transpose()
+        .map_err(|bytesrepr_err| Error::ValueConversion(key, bytesrepr_err))
+}
+
+/// Writes the entries changed between `old_state_root` and `new_state_root`
+/// in the trie store at `trie_path` to `out_writer`, as one JSON object per
+/// line.
+pub(crate) fn write_diff<P: AsRef<Path>, W: Write + ?Sized>(
+    trie_path: P,
+    max_db_size: usize,
+    old_state_root: Digest,
+    new_state_root: Digest,
+    out_writer: &mut W,
+) -> Result<DiffSummary, Error> {
+    let (engine_state, _env) =
+        load_execution_engine(trie_path, max_db_size, Digest::default(), true)
+            .map_err(Error::OpenTrie)?;
+    let txn = engine_state
+        .get_state()
+        .environment()
+        .create_read_txn()
+        .map_err(TrieWalkError::from)?;
+    let db = engine_state.get_state().trie_store().get_db();
+
+    let mut summary = DiffSummary::default();
+    diff_state_roots(
+        &txn,
+        db,
+        old_state_root,
+        new_state_root,
+        |key, old_value, new_value| {
+            let change = match (&old_value, &new_value) {
+                (None, _) => {
+                    summary.added += 1;
+                    ChangeKind::Added
+                }
+                (_, None) => {
+                    summary.removed += 1;
+                    ChangeKind::Removed
+                }
+                _ => {
+                    summary.modified += 1;
+                    ChangeKind::Modified
+                }
+            };
+            let entry = DiffEntry {
+                key: key.to_formatted_string(),
+                change,
+                old: json_value(key, old_value)?,
+                new: json_value(key, new_value)?,
+            };
+            serde_json::to_writer(&mut *out_writer, &entry)?;
+            writeln!(out_writer)?;
+            Ok::<_, Error>(())
+        },
+    )?;
+    txn.commit().map_err(TrieWalkError::from)?;
+    out_writer.flush()?;
+    Ok(summary)
+}
+
+pub fn state_diff<P1: AsRef<Path>, P2: AsRef<Path>>(
+    trie_path: P1,
+    max_db_size: usize,
+    old_state_root: Digest,
+    new_state_root: Digest,
+    output: Option<P2>,
+    overwrite: bool,
+) -> Result<(), Error> {
+    // Validate the output file early so that, in case this fails
+    // we don't unnecessarily walk the tries. Nothing is logged when
+    // writing to standard output, so the output stays valid NDJSON.
+    let mut log_progress = false;
+    let mut out_writer: Box<dyn Write> = if let Some(out_path) = output {
+        let file = OpenOptions::new()
+            .create_new(!overwrite)
+            .write(true)
+            .truncate(true)
+            .open(out_path)?;
+        log_progress = true;
+        Box::new(BufWriter::new(file))
+    } else {
+        Box::new(BufWriter::new(io::stdout()))
+    };
+
+    let summary = write_diff(
+        trie_path,
+        max_db_size,
+        old_state_root,
+        new_state_root,
+        &mut out_writer,
+    )?;
+    if log_progress {
+        info!(
+            "Between state roots {old_state_root} and {new_state_root}, {} keys were added, {} \
+            removed and {} modified.",
+            summary.added, summary.removed, summary.modified
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,255 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::TryFrom,
+};
+
+use tempfile::TempDir;
+
+use casper_execution_engine::shared::newtypes::CorrelationId;
+use casper_hashing::Digest;
+use casper_node::types::json_compatibility::StoredValue as JsonStoredValue;
+use casper_types::{account::AccountHash, CLValue, ContractWasm, Key, StoredValue};
+
+use crate::{
+    common::trie_walk::Error as TrieWalkError,
+    subcommands::trie_compact::{
+        load_execution_engine,
+        tests::{create_test_global_state, DEFAULT_MAX_DB_SIZE},
+    },
+};
+
+use super::{
+    diff::{self, ChangeKind, DiffEntry, DiffSummary},
+    Error,
+};
+
+type Changes = BTreeMap<String, (ChangeKind, Option<StoredValue>, Option<StoredValue>)>;
+type DiffLine = (
+    String,
+    ChangeKind,
+    Option<JsonStoredValue>,
+    Option<JsonStoredValue>,
+);
+
+fn old_stored_values() -> HashMap<Key, StoredValue> {
+    let mut stored_values = HashMap::new();
+    for index in 0..20u8 {
+        stored_values.insert(
+            Key::Account(AccountHash::new([index; 32])),
+            StoredValue::CLValue(CLValue::from_t(index as u64).unwrap()),
+        );
+    }
+    stored_values.insert(
+        Key::Hash([1u8; 32]),
+        StoredValue::ContractWasm(ContractWasm::new(vec![0u8; 10])),
+    );
+    stored_values
+}
+
+/// Values written on top of the old ones to get the new state root.
+fn updated_stored_values() -> HashMap<Key, StoredValue> {
+    let mut stored_values = HashMap::new();
+    // Modified entries.
+    stored_values.insert(
+        Key::Account(AccountHash::new([3u8; 32])),
+        StoredValue::CLValue(CLValue::from_t(300u64).unwrap()),
+    );
+    stored_values.insert(
+        Key::Hash([1u8; 32]),
+        StoredValue::ContractWasm(ContractWasm::new(vec![1u8; 10])),
+    );
+    // Unchanged entry.
+    stored_values.insert(
+        Key::Account(AccountHash::new([4u8; 32])),
+        StoredValue::CLValue(CLValue::from_t(4u64).unwrap()),
+    );
+    // Added entries, some sharing a long prefix with existing keys.
+    let mut shared_prefix = [3u8; 32];
+    shared_prefix[31] = 4;
+    stored_values.insert(
+        Key::Account(AccountHash::new(shared_prefix)),
+        StoredValue::CLValue(CLValue::from_t(34u64).unwrap()),
+    );
+    stored_values.insert(
+        Key::Balance([7u8; 32]),
+        StoredValue::CLValue(CLValue::from_t(7u64).unwrap()),
+    );
+    stored_values.insert(
+        Key::Dictionary([8u8; 32]),
+        StoredValue::CLValue(CLValue::from_t(String::from("value")).unwrap()),
+    );
+    stored_values
+}
+
+/// Creates a trie store with the old state root and the new one, holding
+/// the old values overwritten by the updated ones.
+fn create_test_state_roots() -> (TempDir, Digest, Digest) {
+    let (trie_dir, old_state_root) = create_test_global_state(old_stored_values());
+    let (engine_state, _env) = load_execution_engine(
+        trie_dir.path(),
+        *DEFAULT_MAX_DB_SIZE,
+        Digest::default(),
+        true,
+    )
+    .unwrap();
+    let new_state_root = engine_state
+        .get_state()
+        .put_stored_values(
+            CorrelationId::new(),
+            old_state_root,
+            updated_stored_values(),
+        )
+        .unwrap();
+    (trie_dir, old_state_root, new_state_root)
+}
+
+/// Computes the changes between two sets of entries without any trie.
+fn expected_changes(
+    old_values: &HashMap<Key, StoredValue>,
+    new_values: &HashMap<Key, StoredValue>,
+) -> Changes {
+    let mut changes = Changes::new();
+    for (key, old_value) in old_values {
+        match new_values.get(key) {
+            None => {
+                changes.insert(
+                    key.to_formatted_string(),
+                    (ChangeKind::Removed, Some(old_value.clone()), None),
+                );
+            }
+            Some(new_value) if new_value != old_value => {
+                changes.insert(
+                    key.to_formatted_string(),
+                    (
+                        ChangeKind::Modified,
+                        Some(old_value.clone()),
+                        Some(new_value.clone()),
+                    ),
+                );
+            }
+            _ => {}
+        }
+    }
+    for (key, new_value) in new_values {
+        if !old_values.contains_key(key) {
+            changes.insert(
+                key.to_formatted_string(),
+                (ChangeKind::Added, None, Some(new_value.clone())),
+            );
+        }
+    }
+    changes
+}
+
+fn new_stored_values() -> HashMap<Key, StoredValue> {
+    let mut stored_values = old_stored_values();
+    stored_values.extend(updated_stored_values());
+    stored_values
+}
+
+/// Diffs two state roots and returns the parsed lines.
+fn diff_entries(
+    trie_dir: &TempDir,
+    old_state_root: Digest,
+    new_state_root: Digest,
+) -> (DiffSummary, Vec<DiffEntry>) {
+    let mut output = vec![];
+    let summary = diff::write_diff(
+        trie_dir.path(),
+        *DEFAULT_MAX_DB_SIZE,
+        old_state_root,
+        new_state_root,
+        &mut output,
+    )
+    .unwrap();
+    let entries = String::from_utf8(output)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    (summary, entries)
+}
+
+fn assert_entries_match(entries: Vec<DiffEntry>, expected: Changes) {
+    let json_value =
+        |value: Option<StoredValue>| value.map(|value| JsonStoredValue::try_from(value).unwrap());
+    let expected: Vec<DiffLine> = expected
+        .into_iter()
+        .map(|(key, (change, old, new))| (key, change, json_value(old), json_value(new)))
+        .collect();
+    let mut actual: Vec<DiffLine> = entries
+        .into_iter()
+        .map(|entry| (entry.key, entry.change, entry.old, entry.new))
+        .collect();
+    actual.sort_by(|entry1, entry2| entry1.0.cmp(&entry2.0));
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn diff_should_list_added_and_modified_keys() {
+    let (trie_dir, old_state_root, new_state_root) = create_test_state_roots();
+
+    let (summary, entries) = diff_entries(&trie_dir, old_state_root, new_state_root);
+    assert_eq!(
+        summary,
+        DiffSummary {
+            added: 3,
+            removed: 0,
+            modified: 2,
+        }
+    );
+    assert_entries_match(
+        entries,
+        expected_changes(&old_stored_values(), &new_stored_values()),
+    );
+}
+
+#[test]
+fn diff_should_list_removed_keys() {
+    let (trie_dir, old_state_root, new_state_root) = create_test_state_roots();
+
+    // Going back from the new state root to the old one removes the added
+    // keys.
+    let (summary, entries) = diff_entries(&trie_dir, new_state_root, old_state_root);
+    assert_eq!(
+        summary,
+        DiffSummary {
+            added: 0,
+            removed: 3,
+            modified: 2,
+        }
+    );
+    assert_entries_match(
+        entries,
+        expected_changes(&new_stored_values(), &old_stored_values()),
+    );
+}
+
+#[test]
+fn diff_should_be_empty_for_identical_state_roots() {
+    let (trie_dir, _old_state_root, new_state_root) = create_test_state_roots();
+
+    let (summary, entries) = diff_entries(&trie_dir, new_state_root, new_state_root);
+    assert_eq!(summary, DiffSummary::default());
+    assert!(entries.is_empty());
+}
+
+#[test]
+fn diff_should_fail_on_missing_state_root() {
+    let (trie_dir, old_state_root, _new_state_root) = create_test_state_roots();
+    let missing_root = Digest::hash(b"missing");
+
+    match diff::write_diff(
+        trie_dir.path(),
+        *DEFAULT_MAX_DB_SIZE,
+        old_state_root,
+        missing_root,
+        &mut vec![],
+    ) {
+        Err(Error::Walk(TrieWalkError::MissingTrie(trie_key))) => {
+            assert_eq!(trie_key, missing_root)
+        }
+        Err(err) => panic!("Unexpected error: {err}"),
+        Ok(_) => panic!("Unexpected successful diff"),
+    }
+}
@@ -0,0 +1,153 @@
+mod coverage;
+#[cfg(test)]
+mod tests;
+
+use std::{io::Error as IoError, path::Path};
+
+use anyhow::Error as AnyError;
+use bincode::Error as BincodeError;
+use clap::{Arg, ArgMatches, Command};
+use lmdb::Error as LmdbError;
+use serde_json::Error as JsonSerializationError;
+use thiserror::Error as ThisError;
+
+use casper_hashing::Digest;
+use casper_node::types::BlockHash;
+
+use super::trie_compact::{self, DEFAULT_MAX_DB_SIZE};
+
+pub const COMMAND_NAME: &str = "state-root-coverage";
+const MAX_DB_SIZE: &str = "max-db-size";
+const OUTPUT: &str = "output";
+const OVERWRITE: &str = "overwrite";
+const STORAGE_PATH: &str = "storage-path";
+const TRIE_STORE_PATH: &str = "trie";
+
+/// Errors encountered when mapping the state roots held by the trie store.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Error checking the tries under a state root.
+    #[error("Error checking state root {0}: {1}")]
+    CheckStateRoot(Digest, AnyError),
+    /// Error creating the scratch set of complete tries.
+    #[error("Error creating the scratch set of complete tries: {0}")]
+    CreateScratch(AnyError),
+    /// Database operation error on the storage database.
+    #[error("Error operating the database: {0}")]
+    Database(#[from] LmdbError),
+    /// Found two block headers with the same height.
+    #[error("Found more than one block header with height {0}")]
+    DuplicateBlock(u64),
+    /// Parsing error on entry in the block header database.
+    #[error("Error parsing block header with hash {0}: {1}")]
+    HeaderParsing(BlockHash, BincodeError),
+    #[error("Error serializing output: {0}")]
+    JsonSerialize(#[from] JsonSerializationError),
+    /// Error opening the trie store.
+    #[error("Error opening the trie store: {0}")]
+    OpenTrie(AnyError),
+    #[error("Error writing output: {0}")]
+    Output(#[from] IoError),
+}
+
+enum DisplayOrder {
+    TriePath,
+    StoragePath,
+    MaxDbSize,
+    ScratchDir,
+    Output,
+    Overwrite,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Checks the state root of every block header in storage against the trie store \
+            and outputs in JSON format which state roots are fully held, partially held or \
+            missing, along with the height ranges they cover.",
+        )
+        .arg(
+            Arg::new(TRIE_STORE_PATH)
+                .display_order(DisplayOrder::TriePath as usize)
+                .required(true)
+                .short('t')
+                .long(TRIE_STORE_PATH)
+                .takes_value(true)
+                .value_name("TRIE_STORE_DIR_PATH")
+                .help("Path of the directory with the `data.lmdb` file."),
+        )
+        .arg(
+            Arg::new(STORAGE_PATH)
+                .display_order(DisplayOrder::StoragePath as usize)
+                .required(true)
+                .short('b')
+                .long(STORAGE_PATH)
+                .takes_value(true)
+                .value_name("STORAGE_DIR_PATH")
+                .help(
+                    "Path of the directory with the `storage.lmdb` file. Used to find all \
+                    blocks' state root hashes.",
+                ),
+        )
+        .arg(
+            Arg::new(MAX_DB_SIZE)
+                .display_order(DisplayOrder::MaxDbSize as usize)
+                .required(false)
+                .short('m')
+                .long(MAX_DB_SIZE)
+                .takes_value(true)
+                .default_value(DEFAULT_MAX_DB_SIZE)
+                .value_name("MAX_DB_SIZE")
+                .help("Maximum size the DB files are allowed to be, in bytes."),
+        )
+        .arg(trie_compact::scratch_dir_arg(
+            DisplayOrder::ScratchDir as usize,
+        ))
+        .arg(
+            Arg::new(OUTPUT)
+                .display_order(DisplayOrder::Output as usize)
+                .short('o')
+                .long(OUTPUT)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help(
+                    "Path to where the program will output the coverage report. \
+                    If unspecified, defaults to standard output.",
+                ),
+        )
+        .arg(
+            Arg::new(OVERWRITE)
+                .display_order(DisplayOrder::Overwrite as usize)
+                .required(false)
+                .short('w')
+                .long(OVERWRITE)
+                .takes_value(false)
+                .requires(OUTPUT)
+                .help(
+                    "Overwrite an already existing output file in destination \
+                    directory.",
+                ),
+        )
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let trie_path = Path::new(matches.value_of(TRIE_STORE_PATH).unwrap());
+    let storage_path = Path::new(matches.value_of(STORAGE_PATH).unwrap());
+    let max_db_size = matches
+        .value_of(MAX_DB_SIZE)
+        .unwrap()
+        .parse()
+        .expect("Value of \"--max-db-size\" must be an integer.");
+    let output = matches.value_of(OUTPUT).map(Path::new);
+    let overwrite = matches.is_present(OVERWRITE);
+    let scratch_dir = trie_compact::scratch_dir(matches);
+    coverage::state_root_coverage(
+        trie_path,
+        storage_path,
+        max_db_size,
+        scratch_dir.as_deref(),
+        output,
+        overwrite,
+    )
+}
@@ -0,0 +1,254 @@
+use std::{
+    collections::{hash_map::Entry, BTreeMap, HashMap},
+    env,
+    fs::OpenOptions,
+    io::{self, Write},
+    path::Path,
+};
+
+use lmdb::{Cursor, Environment, Transaction};
+use log::{error, info};
+use serde::Serialize;
+
+use casper_hashing::Digest;
+use casper_node::types::{BlockHash, BlockHeader};
+
+use crate::{
+    common::db::{self, BlockHeaderDatabase, Database, STORAGE_FILE_NAME},
+    subcommands::trie_compact::{load_execution_engine, ReachableTries},
+};
+
+use super::Error;
+
+/// Number of checked state roots between progress messages.
+const STATE_ROOTS_PER_PROGRESS_MESSAGE: usize = 1_000;
+
+/// How much of the global state under a state root the trie store holds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum RootStatus {
+    /// Every trie under the state root is in the trie store.
+    Complete,
+    /// The state root is in the trie store, but some tries under it aren't.
+    Incomplete,
+    /// The state root itself isn't in the trie store.
+    Missing,
+}
+
+/// Contiguous block heights whose state roots have the same status.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct HeightRange {
+    pub(crate) start: u64,
+    pub(crate) end: u64,
+    pub(crate) status: RootStatus,
+}
+
+/// Status of a distinct state root along with the blocks referring to it.
+#[derive(Debug, Serialize)]
+pub(crate) struct StateRootCoverage {
+    pub(crate) state_root_hash: Digest,
+    pub(crate) status: RootStatus,
+    pub(crate) lowest_height: u64,
+    pub(crate) highest_height: u64,
+    pub(crate) block_count: usize,
+    /// First trie found missing under an incomplete state root.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) missing_trie: Option<Digest>,
+}
+
+/// Map of the state roots of the blocks in storage held by the trie store.
+#[derive(Debug, Serialize)]
+pub(crate) struct CoverageReport {
+    pub(crate) block_count: usize,
+    pub(crate) complete_roots: usize,
+    pub(crate) incomplete_roots: usize,
+    pub(crate) missing_roots: usize,
+    pub(crate) height_ranges: Vec<HeightRange>,
+    /// Distinct state roots, ordered by the lowest height referring to them.
+    pub(crate) state_roots: Vec<StateRootCoverage>,
+}
+
+/// Reads the state root of every block header in the storage database
+/// opened in `env`, keyed by block height.
+fn read_block_state_roots(env: &Environment) -> Result<BTreeMap<u64, Digest>, Error> {
+    let mut state_roots = BTreeMap::new();
+    let txn = env.begin_ro_txn()?;
+    let header_db = unsafe { txn.open_db(Some(BlockHeaderDatabase::db_name()))? };
+    {
+        let mut cursor = txn.open_ro_cursor(header_db)?;
+        for (raw_key, raw_value) in cursor.iter() {
+            let block_hash: BlockHash = match Digest::try_from(raw_key) {
+                Ok(digest) => digest.into(),
+                Err(digest_parsing_err) => {
+                    error!("Skipping block header because of invalid hash {raw_key:?}: {digest_parsing_err}");
+                    continue;
+                }
+            };
+            let block_header: BlockHeader = bincode::deserialize(raw_value)
+                .map_err(|bincode_err| Error::HeaderParsing(block_hash, bincode_err))?;
+            if state_roots
+                .insert(block_header.height(), *block_header.state_root_hash())
+                .is_some()
+            {
+                return Err(Error::DuplicateBlock(block_header.height()));
+            }
+        }
+    }
+    txn.commit()?;
+    Ok(state_roots)
+}
+
+/// Groups the block heights into ranges of contiguous heights whose state
+/// roots have the same status.
+fn height_ranges(
+    block_state_roots: &BTreeMap<u64, Digest>,
+    statuses: &HashMap<Digest, RootStatus>,
+) -> Vec<HeightRange> {
+    let mut ranges: Vec<HeightRange> = vec![];
+    for (height, state_root) in block_state_roots {
+        let status = statuses[state_root];
+        match ranges.last_mut() {
+            Some(range) if range.end + 1 == *height && range.status == status => {
+                range.end = *height
+            }
+            _ => ranges.push(HeightRange {
+                start: *height,
+                end: *height,
+                status,
+            }),
+        }
+    }
+    ranges
+}
+
+/// Checks the state root of every block in the storage at `storage_path`
+/// against the trie store at `trie_path`.
+///
+/// Complete subtrees are recorded in a temporary file under `scratch_dir`, or
+/// the system's temporary directory if unset, so the tries shared by
+/// successive state roots are only walked once.
+pub(crate) fn build_coverage_report<P1: AsRef<Path>, P2: AsRef<Path>>(
+    trie_path: P1,
+    storage_path: P2,
+    max_db_size: usize,
+    scratch_dir: Option<&Path>,
+    log_progress: bool,
+) -> Result<CoverageReport, Error> {
+    let env = db::read_only_db_env(storage_path.as_ref().join(STORAGE_FILE_NAME))?;
+    let block_state_roots = read_block_state_roots(&env)?;
+
+    let mut state_roots: Vec<StateRootCoverage> = vec![];
+    let mut root_indices: HashMap<Digest, usize> = HashMap::new();
+    for (height, state_root) in block_state_roots.iter() {
+        match root_indices.entry(*state_root) {
+            Entry::Occupied(entry) => {
+                let coverage = &mut state_roots[*entry.get()];
+                coverage.highest_height = *height;
+                coverage.block_count += 1;
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(state_roots.len());
+                state_roots.push(StateRootCoverage {
+                    state_root_hash: *state_root,
+                    status: RootStatus::Missing,
+                    lowest_height: *height,
+                    highest_height: *height,
+                    block_count: 1,
+                    missing_trie: None,
+                });
+            }
+        }
+    }
+    if log_progress {
+        info!(
+            "Found {} blocks with {} distinct state roots.",
+            block_state_roots.len(),
+            state_roots.len()
+        );
+    }
+
+    let (trie_state, _trie_env) =
+        load_execution_engine(&trie_path, max_db_size, Digest::default(), true)
+            .map_err(Error::OpenTrie)?;
+    let scratch_dir = scratch_dir.map_or_else(env::temp_dir, Path::to_path_buf);
+    let complete_tries =
+        ReachableTries::new(scratch_dir, max_db_size).map_err(Error::CreateScratch)?;
+    let mut statuses = HashMap::with_capacity(state_roots.len());
+    let mut roots_since_message = 0usize;
+    // The most recent state roots are checked first.
+    for (idx, coverage) in state_roots.iter_mut().rev().enumerate() {
+        let state_root = coverage.state_root_hash;
+        coverage.status = match complete_tries
+            .mark_complete_tries(state_root, &trie_state)
+            .map_err(|err| Error::CheckStateRoot(state_root, err))?
+        {
+            None => RootStatus::Complete,
+            Some(missing_trie) if missing_trie == state_root => RootStatus::Missing,
+            Some(missing_trie) => {
+                coverage.missing_trie = Some(missing_trie);
+                RootStatus::Incomplete
+            }
+        };
+        statuses.insert(state_root, coverage.status);
+        roots_since_message += 1;
+        if log_progress && roots_since_message == STATE_ROOTS_PER_PROGRESS_MESSAGE {
+            info!("Checked {} state roots so far.", idx + 1);
+            roots_since_message = 0;
+        }
+    }
+
+    let count_roots = |status| {
+        state_roots
+            .iter()
+            .filter(|coverage| coverage.status == status)
+            .count()
+    };
+    Ok(CoverageReport {
+        block_count: block_state_roots.len(),
+        complete_roots: count_roots(RootStatus::Complete),
+        incomplete_roots: count_roots(RootStatus::Incomplete),
+        missing_roots: count_roots(RootStatus::Missing),
+        height_ranges: height_ranges(&block_state_roots, &statuses),
+        state_roots,
+    })
+}
+
+pub fn state_root_coverage<P1: AsRef<Path>, P2: AsRef<Path>, P3: AsRef<Path>>(
+    trie_path: P1,
+    storage_path: P2,
+    max_db_size: usize,
+    scratch_dir: Option<&Path>,
+    output: Option<P3>,
+    overwrite: bool,
+) -> Result<(), Error> {
+    // Validate the output file early so that, in case this fails
+    // we don't unnecessarily walk the trie store.
+    let mut log_progress = false;
+    let out_writer: Box<dyn Write> = if let Some(out_path) = output {
+        let file = OpenOptions::new()
+            .create_new(!overwrite)
+            .write(true)
+            .truncate(true)
+            .open(out_path)?;
+        log_progress = true;
+        Box::new(file)
+    } else {
+        Box::new(io::stdout())
+    };
+
+    let report = build_coverage_report(
+        trie_path,
+        storage_path,
+        max_db_size,
+        scratch_dir,
+        log_progress,
+    )?;
+    if log_progress {
+        info!(
+            "{} state roots are complete, {} incomplete and {} missing.",
+            report.complete_roots, report.incomplete_roots, report.missing_roots
+        );
+    }
+    serde_json::to_writer_pretty(out_writer, &report)?;
+    Ok(())
+}
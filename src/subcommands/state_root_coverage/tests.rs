@@ -0,0 +1,181 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use lmdb::Transaction;
+use tempfile::TempDir;
+
+use casper_execution_engine::storage::{
+    transaction_source::lmdb::LmdbEnvironment, trie_store::lmdb::LmdbTrieStore,
+};
+use casper_hashing::Digest;
+use casper_types::bytesrepr::ToBytes;
+
+use crate::subcommands::trie_compact::tests::{
+    create_test_storage, create_test_trie_store, DEFAULT_MAX_DB_SIZE,
+};
+
+use super::coverage::{self, HeightRange, RootStatus};
+
+/// Returns the directory of the `storage.lmdb` file of a test storage.
+fn storage_file_dir(storage_dir: &TempDir) -> PathBuf {
+    storage_dir.path().join("casper")
+}
+
+/// Deletes the trie with the given key from the trie store at `path`.
+fn delete_trie<P: AsRef<Path>>(path: P, trie_key: Digest) {
+    let env = LmdbEnvironment::new(path, *DEFAULT_MAX_DB_SIZE, 512, true).unwrap();
+    let store = LmdbTrieStore::open(&env, None).unwrap();
+    let mut txn = env.env().begin_rw_txn().unwrap();
+    txn.del(store.get_db(), &trie_key.to_bytes().unwrap(), None)
+        .unwrap();
+    txn.commit().unwrap();
+}
+
+#[test]
+fn coverage_should_report_complete_roots() {
+    let (trie_dir, data) = create_test_trie_store();
+    // `node1` is the state root of the first two blocks, `node2` of the
+    // third one.
+    let storage_dir = create_test_storage(&[data[3].0, data[3].0, data[4].0]);
+    let scratch_dir = tempfile::tempdir().unwrap();
+
+    let report = coverage::build_coverage_report(
+        &trie_dir,
+        storage_file_dir(&storage_dir),
+        *DEFAULT_MAX_DB_SIZE,
+        Some(scratch_dir.path()),
+        false,
+    )
+    .unwrap();
+    // The set of complete tries was removed.
+    assert_eq!(fs::read_dir(scratch_dir.path()).unwrap().count(), 0);
+    assert_eq!(report.block_count, 3);
+    assert_eq!(report.complete_roots, 2);
+    assert_eq!(report.incomplete_roots, 0);
+    assert_eq!(report.missing_roots, 0);
+    assert_eq!(
+        report.height_ranges,
+        vec![HeightRange {
+            start: 0,
+            end: 2,
+            status: RootStatus::Complete,
+        }]
+    );
+    assert_eq!(report.state_roots.len(), 2);
+    assert_eq!(report.state_roots[0].state_root_hash, data[3].0);
+    assert_eq!(report.state_roots[0].lowest_height, 0);
+    assert_eq!(report.state_roots[0].highest_height, 1);
+    assert_eq!(report.state_roots[0].block_count, 2);
+    assert_eq!(report.state_roots[1].state_root_hash, data[4].0);
+    assert_eq!(report.state_roots[1].lowest_height, 2);
+    assert_eq!(report.state_roots[1].block_count, 1);
+}
+
+#[test]
+fn coverage_should_report_incomplete_and_missing_roots() {
+    let (trie_dir, data) = create_test_trie_store();
+    // `leaf2` is under both `node1` and `node2`.
+    delete_trie(&trie_dir, data[1].0);
+    let missing_root = Digest::hash([9u8; 32]);
+    let storage_dir = create_test_storage(&[data[3].0, data[4].0, missing_root, data[4].0]);
+
+    let report = coverage::build_coverage_report(
+        &trie_dir,
+        storage_file_dir(&storage_dir),
+        *DEFAULT_MAX_DB_SIZE,
+        None,
+        false,
+    )
+    .unwrap();
+    assert_eq!(report.block_count, 4);
+    assert_eq!(report.complete_roots, 0);
+    assert_eq!(report.incomplete_roots, 2);
+    assert_eq!(report.missing_roots, 1);
+    assert_eq!(
+        report.height_ranges,
+        vec![
+            HeightRange {
+                start: 0,
+                end: 1,
+                status: RootStatus::Incomplete,
+            },
+            HeightRange {
+                start: 2,
+                end: 2,
+                status: RootStatus::Missing,
+            },
+            HeightRange {
+                start: 3,
+                end: 3,
+                status: RootStatus::Incomplete,
+            },
+        ]
+    );
+    assert_eq!(report.state_roots.len(), 3);
+    for coverage in report.state_roots.iter() {
+        if coverage.state_root_hash == missing_root {
+            assert_eq!(coverage.status, RootStatus::Missing);
+            assert!(coverage.missing_trie.is_none());
+        } else {
+            assert_eq!(coverage.status, RootStatus::Incomplete);
+            assert_eq!(coverage.missing_trie, Some(data[1].0));
+        }
+    }
+}
+
+#[test]
+fn coverage_should_reuse_complete_subtrees() {
+    let (trie_dir, data) = create_test_trie_store();
+    // `leaf1` is only under `node1`.
+    delete_trie(&trie_dir, data[0].0);
+    // `node2` is checked first, its complete subtree is then skipped when
+    // checking `node1`.
+    let storage_dir = create_test_storage(&[data[3].0, data[4].0]);
+
+    let report = coverage::build_coverage_report(
+        &trie_dir,
+        storage_file_dir(&storage_dir),
+        *DEFAULT_MAX_DB_SIZE,
+        None,
+        false,
+    )
+    .unwrap();
+    assert_eq!(report.complete_roots, 1);
+    assert_eq!(report.incomplete_roots, 1);
+    assert_eq!(report.state_roots[0].status, RootStatus::Incomplete);
+    assert_eq!(report.state_roots[0].missing_trie, Some(data[0].0));
+    assert_eq!(report.state_roots[1].status, RootStatus::Complete);
+    assert_eq!(
+        report.height_ranges,
+        vec![
+            HeightRange {
+                start: 0,
+                end: 0,
+                status: RootStatus::Incomplete,
+            },
+            HeightRange {
+                start: 1,
+                end: 1,
+                status: RootStatus::Complete,
+            },
+        ]
+    );
+}
+
+#[test]
+fn coverage_should_not_create_missing_storage() {
+    let (trie_dir, _data) = create_test_trie_store();
+    let storage_dir = tempfile::tempdir().unwrap();
+
+    assert!(coverage::build_coverage_report(
+        &trie_dir,
+        storage_dir.path(),
+        *DEFAULT_MAX_DB_SIZE,
+        None,
+        false,
+    )
+    .is_err());
+    assert!(!storage_dir.path().join("storage.lmdb").exists());
+}
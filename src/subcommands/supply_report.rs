@@ -0,0 +1,165 @@
+mod report;
+#[cfg(test)]
+mod tests;
+
+use std::{io::Error as IoError, path::Path};
+
+use anyhow::Error as AnyError;
+use clap::{Arg, ArgMatches, Command};
+use serde_json::Error as JsonSerializationError;
+use thiserror::Error as ThisError;
+
+use casper_execution_engine::core::engine_state::Error as EngineStateError;
+use casper_hashing::Digest;
+use casper_types::{CLValueError, Key};
+
+use crate::common::trie_walk::Error as TrieWalkError;
+
+use super::{
+    dump_state::{self, StateRootError},
+    query_state::Error as QueryStateError,
+    trie_compact::DEFAULT_MAX_DB_SIZE,
+};
+
+pub const COMMAND_NAME: &str = "supply-report";
+const MAX_DB_SIZE: &str = "max-db-size";
+const OUTPUT: &str = "output";
+const OVERWRITE: &str = "overwrite";
+const SUM_BALANCES: &str = "sum-balances";
+const TRIE_STORE_PATH: &str = "trie";
+
+/// Errors encountered when building the supply report.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// The value under the given key isn't of the expected type.
+    #[error("Invalid value under {}: {1}", .0.to_formatted_string())]
+    InvalidValue(Key, CLValueError),
+    #[error("Error serializing output: {0}")]
+    JsonSerialize(#[from] JsonSerializationError),
+    /// The system contract with the given name isn't in the registry.
+    #[error("Missing system contract {0} in the registry")]
+    MissingSystemContract(String),
+    /// The value under the given key isn't a `CLValue`.
+    #[error("No CLValue under {}", .0.to_formatted_string())]
+    NotACLValue(Key),
+    /// Error opening the trie store.
+    #[error("Error opening the trie store: {0}")]
+    OpenTrie(AnyError),
+    #[error("Error writing output: {0}")]
+    Output(#[from] IoError),
+    /// Error querying a named key of the mint contract.
+    #[error("Error querying the mint contract: {0}")]
+    Query(#[from] QueryStateError),
+    /// Error reading the system contract registry.
+    #[error("Error reading the system contract registry: {0}")]
+    Registry(EngineStateError),
+    /// The state root isn't in the trie store.
+    #[error("State root {0} not found in the trie store")]
+    RootNotFound(Digest),
+    /// Error finding the state root to report on.
+    #[error("Error finding the state root: {0}")]
+    StateRoot(#[from] StateRootError),
+    /// Error walking the tries under the state root.
+    #[error("Error walking the global state: {0}")]
+    Walk(#[from] TrieWalkError),
+}
+
+enum DisplayOrder {
+    TriePath,
+    StateRoot,
+    SumBalances,
+    MaxDbSize,
+    Output,
+    Overwrite,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Outputs the total supply, the round seigniorage rate and the system contract \
+            hashes under a state root in JSON format.",
+        )
+        .arg(
+            Arg::new(TRIE_STORE_PATH)
+                .display_order(DisplayOrder::TriePath as usize)
+                .required(true)
+                .short('t')
+                .long(TRIE_STORE_PATH)
+                .takes_value(true)
+                .value_name("TRIE_STORE_DIR_PATH")
+                .help("Path of the directory with the `data.lmdb` file."),
+        )
+        .args(dump_state::state_root_args(
+            DisplayOrder::StateRoot as usize,
+        ))
+        .arg(
+            Arg::new(SUM_BALANCES)
+                .display_order(DisplayOrder::SumBalances as usize)
+                .required(false)
+                .short('a')
+                .long(SUM_BALANCES)
+                .takes_value(false)
+                .help(
+                    "Sum the balances of all purses under the state root to reconcile them \
+                    with the total supply.",
+                ),
+        )
+        .arg(
+            Arg::new(MAX_DB_SIZE)
+                .display_order(DisplayOrder::MaxDbSize as usize)
+                .required(false)
+                .short('m')
+                .long(MAX_DB_SIZE)
+                .takes_value(true)
+                .default_value(DEFAULT_MAX_DB_SIZE)
+                .value_name("MAX_DB_SIZE")
+                .help("Maximum size the DB files are allowed to be, in bytes."),
+        )
+        .arg(
+            Arg::new(OUTPUT)
+                .display_order(DisplayOrder::Output as usize)
+                .short('o')
+                .long(OUTPUT)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help(
+                    "Path to where the program will output the report. \
+                    If unspecified, defaults to standard output.",
+                ),
+        )
+        .arg(
+            Arg::new(OVERWRITE)
+                .display_order(DisplayOrder::Overwrite as usize)
+                .required(false)
+                .short('w')
+                .long(OVERWRITE)
+                .takes_value(false)
+                .requires(OUTPUT)
+                .help(
+                    "Overwrite an already existing output file in destination \
+                    directory.",
+                ),
+        )
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let trie_path = Path::new(matches.value_of(TRIE_STORE_PATH).unwrap());
+    let state_root_hash = dump_state::resolve_state_root(matches)?;
+    let sum_balances = matches.is_present(SUM_BALANCES);
+    let max_db_size = matches
+        .value_of(MAX_DB_SIZE)
+        .unwrap()
+        .parse()
+        .expect("Value of \"--max-db-size\" must be an integer.");
+    let output = matches.value_of(OUTPUT).map(Path::new);
+    let overwrite = matches.is_present(OVERWRITE);
+    report::supply_report(
+        trie_path,
+        max_db_size,
+        state_root_hash,
+        sum_balances,
+        output,
+        overwrite,
+    )
+}
@@ -0,0 +1,239 @@
+use std::{
+    fs::OpenOptions,
+    io::{self, Write},
+    path::Path,
+};
+
+use log::{info, warn};
+use num_rational::Ratio;
+use serde::Serialize;
+
+use casper_execution_engine::{
+    core::engine_state::{EngineState, Error as EngineStateError, SystemContractRegistry},
+    shared::newtypes::CorrelationId,
+    storage::{
+        global_state::lmdb::LmdbGlobalState,
+        transaction_source::{Transaction, TransactionSource},
+        trie::Trie,
+    },
+};
+use casper_hashing::Digest;
+use casper_types::{
+    bytesrepr::FromBytes,
+    system::{
+        mint::{ROUND_SEIGNIORAGE_RATE_KEY, TOTAL_SUPPLY_KEY},
+        MINT,
+    },
+    CLTyped, Key, StoredValue, U512,
+};
+
+use crate::{
+    common::trie_walk::{self, Error as TrieWalkError},
+    subcommands::{query_state, trie_compact::load_execution_engine},
+};
+
+use super::Error;
+
+/// Number of read balances between progress messages.
+const BALANCES_PER_PROGRESS_MESSAGE: usize = 100_000;
+
+/// The round seigniorage rate of the mint contract.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct SeigniorageRate {
+    pub(crate) numerator: U512,
+    pub(crate) denominator: U512,
+}
+
+impl From<Ratio<U512>> for SeigniorageRate {
+    fn from(ratio: Ratio<U512>) -> Self {
+        let (numerator, denominator) = ratio.into();
+        Self {
+            numerator,
+            denominator,
+        }
+    }
+}
+
+/// Sum of the balances of all purses under a state root.
+#[derive(Debug, Serialize)]
+pub(crate) struct BalancesSum {
+    pub(crate) purse_count: usize,
+    pub(crate) total: U512,
+    /// Whether the sum of the balances equals the total supply.
+    pub(crate) matches_total_supply: bool,
+}
+
+/// Supply related data of the system contracts under a state root.
+#[derive(Debug, Serialize)]
+pub(crate) struct SupplyReport {
+    pub(crate) state_root_hash: Digest,
+    pub(crate) system_contracts: SystemContractRegistry,
+    pub(crate) total_supply: U512,
+    pub(crate) round_seigniorage_rate: SeigniorageRate,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) balances: Option<BalancesSum>,
+}
+
+/// Returns the value of type `T` in the `CLValue` stored under `key`.
+fn cl_value_into_t<T: CLTyped + FromBytes>(key: Key, value: StoredValue) -> Result<T, Error> {
+    match value {
+        StoredValue::CLValue(cl_value) => cl_value
+            .into_t()
+            .map_err(|cl_value_err| Error::InvalidValue(key, cl_value_err)),
+        _ => Err(Error::NotACLValue(key)),
+    }
+}
+
+/// Returns the value of type `T` under the named key `name` of the mint
+/// contract.
+fn read_mint_value<T: CLTyped + FromBytes>(
+    engine_state: &EngineState<LmdbGlobalState>,
+    state_root_hash: Digest,
+    mint_key: Key,
+    name: &str,
+) -> Result<T, Error> {
+    let value = query_state::run_query(
+        engine_state,
+        state_root_hash,
+        mint_key,
+        vec![name.to_string()],
+    )?;
+    cl_value_into_t(mint_key, value)
+}
+
+/// Sums the balances of all purses in the global state at
+/// `state_root_hash`, reading the balance leaves in a single walk of the
+/// tries.
+fn sum_balances(
+    engine_state: &EngineState<LmdbGlobalState>,
+    state_root_hash: Digest,
+    total_supply: U512,
+    log_progress: bool,
+) -> Result<BalancesSum, Error> {
+    if log_progress {
+        info!("Summing the balances of all purses.");
+    }
+    let txn = engine_state
+        .get_state()
+        .environment()
+        .create_read_txn()
+        .map_err(TrieWalkError::from)?;
+    let db = engine_state.get_state().trie_store().get_db();
+    let mut purse_count = 0usize;
+    let mut total = U512::zero();
+    let mut balances_since_message = 0usize;
+    trie_walk::walk_tries_in_txn(&txn, db, state_root_hash, |trie, _depth| {
+        let (key, value) = match trie {
+            Trie::Leaf {
+                key: key @ Key::Balance(_),
+                value,
+            } => (key, value),
+            _ => return Ok(()),
+        };
+        let balance: U512 = cl_value_into_t(*key, value.clone())?;
+        total += balance;
+        purse_count += 1;
+        balances_since_message += 1;
+        if log_progress && balances_since_message == BALANCES_PER_PROGRESS_MESSAGE {
+            info!("Summed {} balances so far.", purse_count);
+            balances_since_message = 0;
+        }
+        Ok::<_, Error>(())
+    })?;
+    txn.commit().map_err(TrieWalkError::from)?;
+
+    let matches_total_supply = total == total_supply;
+    if !matches_total_supply {
+        warn!("Sum of the balances {total} differs from the total supply {total_supply}");
+    }
+    Ok(BalancesSum {
+        purse_count,
+        total,
+        matches_total_supply,
+    })
+}
+
+/// Builds the supply report of the global state at `state_root_hash`,
+/// summing the balances of all purses if `with_balances` is set.
+pub(crate) fn build_supply_report<P: AsRef<Path>>(
+    trie_path: P,
+    max_db_size: usize,
+    state_root_hash: Digest,
+    with_balances: bool,
+    log_progress: bool,
+) -> Result<SupplyReport, Error> {
+    let (engine_state, _env) =
+        load_execution_engine(trie_path, max_db_size, Digest::default(), true)
+            .map_err(Error::OpenTrie)?;
+
+    let system_contracts = engine_state
+        .get_system_contract_registry(CorrelationId::new(), state_root_hash)
+        .map_err(|engine_state_err| match engine_state_err {
+            EngineStateError::RootNotFound(_) => Error::RootNotFound(state_root_hash),
+            _ => Error::Registry(engine_state_err),
+        })?;
+    let mint_key = system_contracts
+        .get(MINT)
+        .map(|mint_hash| Key::from(*mint_hash))
+        .ok_or_else(|| Error::MissingSystemContract(MINT.to_string()))?;
+    let total_supply: U512 =
+        read_mint_value(&engine_state, state_root_hash, mint_key, TOTAL_SUPPLY_KEY)?;
+    let round_seigniorage_rate: Ratio<U512> = read_mint_value(
+        &engine_state,
+        state_root_hash,
+        mint_key,
+        ROUND_SEIGNIORAGE_RATE_KEY,
+    )?;
+    let balances = if with_balances {
+        Some(sum_balances(
+            &engine_state,
+            state_root_hash,
+            total_supply,
+            log_progress,
+        )?)
+    } else {
+        None
+    };
+
+    Ok(SupplyReport {
+        state_root_hash,
+        system_contracts,
+        total_supply,
+        round_seigniorage_rate: round_seigniorage_rate.into(),
+        balances,
+    })
+}
+
+pub fn supply_report<P1: AsRef<Path>, P2: AsRef<Path>>(
+    trie_path: P1,
+    max_db_size: usize,
+    state_root_hash: Digest,
+    with_balances: bool,
+    output: Option<P2>,
+    overwrite: bool,
+) -> Result<(), Error> {
+    // Validate the output file early so that, in case this fails
+    // we don't unnecessarily read the global state.
+    let mut log_progress = false;
+    let out_writer: Box<dyn Write> = if let Some(out_path) = output {
+        let file = OpenOptions::new()
+            .create_new(!overwrite)
+            .write(true)
+            .truncate(true)
+            .open(out_path)?;
+        log_progress = true;
+        Box::new(file)
+    } else {
+        Box::new(io::stdout())
+    };
+
+    let report = build_supply_report(
+        trie_path,
+        max_db_size,
+        state_root_hash,
+        with_balances,
+        log_progress,
+    )?;
+    serde_json::to_writer_pretty(out_writer, &report)?;
+    Ok(())
+}
@@ -0,0 +1,181 @@
+use std::collections::{BTreeMap, HashMap};
+
+use num_rational::Ratio;
+
+use casper_types::{
+    contracts::{ContractPackageHash, EntryPoints},
+    system::{
+        mint::{ROUND_SEIGNIORAGE_RATE_KEY, TOTAL_SUPPLY_KEY},
+        AUCTION, MINT,
+    },
+    AccessRights, CLValue, Contract, ContractHash, ContractWasmHash, Key, ProtocolVersion,
+    StoredValue, URef, U512,
+};
+
+use crate::subcommands::trie_compact::tests::{create_test_global_state, DEFAULT_MAX_DB_SIZE};
+
+use super::{
+    report::{self, SeigniorageRate},
+    Error,
+};
+
+const MINT_HASH: ContractHash = ContractHash::new([1u8; 32]);
+const AUCTION_HASH: ContractHash = ContractHash::new([2u8; 32]);
+const TOTAL_SUPPLY: u64 = 1_000_000;
+
+/// Creates the global state of a mint contract with a total supply of
+/// `TOTAL_SUPPLY` and a purse for each of `balances`.
+fn mint_stored_values(balances: &[u64]) -> HashMap<Key, StoredValue> {
+    let total_supply_uref = URef::new([3u8; 32], AccessRights::READ_ADD_WRITE);
+    let seigniorage_rate_uref = URef::new([4u8; 32], AccessRights::READ_ADD_WRITE);
+    let mut named_keys = BTreeMap::new();
+    named_keys.insert(TOTAL_SUPPLY_KEY.to_string(), Key::URef(total_supply_uref));
+    named_keys.insert(
+        ROUND_SEIGNIORAGE_RATE_KEY.to_string(),
+        Key::URef(seigniorage_rate_uref),
+    );
+    let mint_contract = Contract::new(
+        ContractPackageHash::new([5u8; 32]),
+        ContractWasmHash::new([6u8; 32]),
+        named_keys,
+        EntryPoints::new(),
+        ProtocolVersion::V1_0_0,
+    );
+    let mut registry = BTreeMap::new();
+    registry.insert(MINT.to_string(), MINT_HASH);
+    registry.insert(AUCTION.to_string(), AUCTION_HASH);
+
+    let mut stored_values = HashMap::new();
+    stored_values.insert(
+        Key::SystemContractRegistry,
+        StoredValue::CLValue(CLValue::from_t(registry).unwrap()),
+    );
+    stored_values.insert(
+        Key::Hash(MINT_HASH.value()),
+        StoredValue::Contract(mint_contract),
+    );
+    // URefs are stored in the global state without their access rights.
+    stored_values.insert(
+        Key::URef(total_supply_uref).normalize(),
+        StoredValue::CLValue(CLValue::from_t(U512::from(TOTAL_SUPPLY)).unwrap()),
+    );
+    stored_values.insert(
+        Key::URef(seigniorage_rate_uref).normalize(),
+        StoredValue::CLValue(
+            CLValue::from_t(Ratio::new_raw(U512::from(7), U512::from(1_000_000_000))).unwrap(),
+        ),
+    );
+    for (idx, balance) in balances.iter().enumerate() {
+        stored_values.insert(
+            Key::Balance([idx as u8; 32]),
+            StoredValue::CLValue(CLValue::from_t(U512::from(*balance)).unwrap()),
+        );
+    }
+    stored_values
+}
+
+#[test]
+fn supply_report_should_read_mint_values() {
+    let (trie_dir, state_root_hash) = create_test_global_state(mint_stored_values(&[]));
+
+    let report = report::build_supply_report(
+        trie_dir.path(),
+        *DEFAULT_MAX_DB_SIZE,
+        state_root_hash,
+        false,
+        false,
+    )
+    .unwrap();
+    assert_eq!(report.state_root_hash, state_root_hash);
+    assert_eq!(report.system_contracts.len(), 2);
+    assert_eq!(report.system_contracts.get(MINT), Some(&MINT_HASH));
+    assert_eq!(report.system_contracts.get(AUCTION), Some(&AUCTION_HASH));
+    assert_eq!(report.total_supply, U512::from(TOTAL_SUPPLY));
+    assert_eq!(
+        report.round_seigniorage_rate,
+        SeigniorageRate {
+            numerator: U512::from(7),
+            denominator: U512::from(1_000_000_000),
+        }
+    );
+    assert!(report.balances.is_none());
+}
+
+#[test]
+fn supply_report_should_sum_balances() {
+    // The balances add up to the total supply.
+    let (trie_dir, state_root_hash) =
+        create_test_global_state(mint_stored_values(&[600_000, 300_000, 100_000]));
+    let balances = report::build_supply_report(
+        trie_dir.path(),
+        *DEFAULT_MAX_DB_SIZE,
+        state_root_hash,
+        true,
+        false,
+    )
+    .unwrap()
+    .balances
+    .unwrap();
+    assert_eq!(balances.purse_count, 3);
+    assert_eq!(balances.total, U512::from(TOTAL_SUPPLY));
+    assert!(balances.matches_total_supply);
+
+    // The balances don't add up to the total supply.
+    let (trie_dir, state_root_hash) =
+        create_test_global_state(mint_stored_values(&[600_000, 300_000]));
+    let balances = report::build_supply_report(
+        trie_dir.path(),
+        *DEFAULT_MAX_DB_SIZE,
+        state_root_hash,
+        true,
+        false,
+    )
+    .unwrap()
+    .balances
+    .unwrap();
+    assert_eq!(balances.purse_count, 2);
+    assert_eq!(balances.total, U512::from(900_000));
+    assert!(!balances.matches_total_supply);
+}
+
+#[test]
+fn supply_report_should_fail_without_mint() {
+    let mut stored_values = mint_stored_values(&[]);
+    let mut registry = BTreeMap::new();
+    registry.insert(AUCTION.to_string(), AUCTION_HASH);
+    stored_values.insert(
+        Key::SystemContractRegistry,
+        StoredValue::CLValue(CLValue::from_t(registry).unwrap()),
+    );
+    let (trie_dir, state_root_hash) = create_test_global_state(stored_values);
+
+    match report::build_supply_report(
+        trie_dir.path(),
+        *DEFAULT_MAX_DB_SIZE,
+        state_root_hash,
+        false,
+        false,
+    ) {
+        Err(Error::MissingSystemContract(name)) => assert_eq!(name, MINT),
+        Err(err) => panic!("Unexpected error: {err}"),
+        Ok(_) => panic!("Unexpected success"),
+    }
+}
+
+#[test]
+fn supply_report_should_fail_on_missing_root() {
+    let (trie_dir, _) = create_test_global_state(mint_stored_values(&[]));
+
+    let missing_root = [9u8; 32].into();
+    match report::build_supply_report(
+        trie_dir.path(),
+        *DEFAULT_MAX_DB_SIZE,
+        missing_root,
+        false,
+        false,
+    ) {
+        Err(Error::RootNotFound(root)) => assert_eq!(root, missing_root),
+        Err(err) => panic!("Unexpected error: {err}"),
+        Ok(_) => panic!("Unexpected success"),
+    }
+}
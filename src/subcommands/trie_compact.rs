@@ -1,33 +1,54 @@
 mod compact;
+mod estimate;
 mod helpers;
+mod progress_log;
 #[cfg(test)]
 pub(crate) mod tests;
 // All code in the `utils` mod was copied from `casper-node` because it isn't available in the
 // public interface.
 mod utils;
+mod verify;
 
 use std::{io::Error as IoError, path::PathBuf};
 
 use anyhow::Error as AnyError;
 use clap::{Arg, ArgMatches, Command};
 use lmdb::Error as LmdbError;
+use serde_json::Error as JsonError;
 use thiserror::Error as ThisError;
 
-use casper_hashing::Digest;
+use casper_hashing::{Digest, Error as HashingError};
 use casper_node::storage::Error as StorageError;
 
-use compact::DestinationOptions;
-pub use helpers::copy_state_root;
-pub use utils::{create_execution_engine, load_execution_engine};
+pub(crate) use compact::{read_state_roots_file, selected_state_roots, BlockSelection};
+use compact::{CompactOptions, DestinationOptions, StateRootSource};
+pub(crate) use helpers::ReachableTries;
+pub use helpers::{copy_state_root, BatchLimits, DEFAULT_MAX_CACHE_BYTES};
+pub(crate) use utils::open_read_only_trie_store;
+pub use utils::{create_execution_engine, create_storage, load_execution_engine};
 
 pub const COMMAND_NAME: &str = "compact-trie";
 const APPEND: &str = "append";
+const BATCH_SIZE: &str = "batch-size";
 const DESTINATION_TRIE_STORE_PATH: &str = "dest-trie";
+const DRY_RUN: &str = "dry-run";
+const FROM_HEIGHT: &str = "from-height";
+const LAST_BLOCKS: &str = "last-blocks";
+const LAST_ERAS: &str = "last-eras";
 const OVERWRITE: &str = "overwrite";
+const RESUME: &str = "resume";
+const MAX_BATCH_BYTES: &str = "max-batch-bytes";
+const MAX_CACHE_BYTES: &str = "max-cache-bytes";
 const MAX_DB_SIZE: &str = "max-db-size";
 pub const DEFAULT_MAX_DB_SIZE: &str = "483183820800"; // 450 gb
+const SCRATCH_DIR: &str = "scratch-dir";
 const SOURCE_TRIE_STORE_PATH: &str = "src-trie";
+const STATE_ROOTS_FILE: &str = "state-roots-file";
 const STORAGE_PATH: &str = "storage-path";
+const THREADS: &str = "threads";
+const COMPARE_SOURCE: &str = "compare-source";
+const VERIFY: &str = "verify";
+const TO_HEIGHT: &str = "to-height";
 
 /// Possible errors caught while compacting the trie store.
 #[derive(Debug, ThisError)]
@@ -35,39 +56,190 @@ pub enum Error {
     /// Error copying the state root with a specific digest.
     #[error("Error copying state root {0}: {1}")]
     CopyStateRoot(Digest, AnyError),
+    /// Error creating the scratch set of reachable tries.
+    #[error("Error creating the scratch set of reachable tries: {0}")]
+    CreateScratch(AnyError),
     /// Error creating the execution engine for the destination trie.
     #[error("Error loading the execution engine: {0}")]
     CreateDestTrie(AnyError),
+    /// A trie reachable from a state root is missing from a trie store.
+    #[error("State root {1} isn't fully reachable in the {0} trie store: {2}")]
+    IncompleteTrie(&'static str, Digest, AnyError),
     /// Error working with the destination trie path.
     #[error("Invalid destination: {0}")]
     InvalidDest(String),
+    /// Invalid state root hash on a specific line of the state roots file.
+    #[error("Invalid state root hash on line {0} of the state roots file: {1}")]
+    InvalidStateRoot(usize, HashingError),
     /// Path cannot be created/resolved.
     #[error("Path {0} cannot be created/resolved: {1}")]
     InvalidPath(PathBuf, IoError),
     /// Error while operating on LMDB.
     #[error("Error while operating on LMDB: {0}")]
     LmdbOperation(LmdbError),
+    /// The lowest selected height is above the highest block in storage.
+    #[error("Lowest selected height {0} is above the highest block height {1}")]
+    LowestHeightAboveHighestBlock(u64, u64),
     /// A block of specific height is missing from the storage.
     #[error("Storage database is missing block {0}")]
     MissingBlock(u64),
+    /// Error reading or writing the progress log.
+    #[error("Error accessing progress log {0}: {1}")]
+    ProgressLog(PathBuf, IoError),
+    /// Error (de)serializing a progress log entry.
+    #[error("Error (de)serializing entry {0} of the progress log: {1}")]
+    ProgressLogEntry(usize, JsonError),
+    /// Error loading the execution engine for the destination trie.
+    #[error("Error loading the destination execution engine: {0}")]
+    OpenDestTrie(AnyError),
     /// Error creating the execution engine for the source trie.
     #[error("Error creating the execution engine: {0}")]
     OpenSourceTrie(AnyError),
     /// Error opening the block/deploys LMDB store.
     #[error("Error opening the block/deploy storage: {0}")]
     OpenStorage(AnyError),
+    /// Error reading the state roots file.
+    #[error("Error reading state roots file {0}: {1}")]
+    StateRootsFile(PathBuf, IoError),
     /// Error while getting a block of specific height from storage.
     #[error("Storage error while trying to retrieve block {0}: {1}")]
     Storage(u64, StorageError),
+    /// Error traversing the tries of a state root during a dry run.
+    #[error("Error traversing state root {0}: {1}")]
+    TraverseStateRoot(Digest, AnyError),
+    /// The tries reachable in the destination differ from the ones in the
+    /// source.
+    #[error(
+        "Destination holds {destination_tries} tries totaling {destination_bytes} bytes, but \
+        source holds {source_tries} tries totaling {source_bytes} bytes"
+    )]
+    VerificationMismatch {
+        source_tries: usize,
+        source_bytes: u64,
+        destination_tries: usize,
+        destination_bytes: u64,
+    },
 }
 
 enum DisplayOrder {
     SourcePath,
     DestinationPath,
     StoragePath,
+    StateRootsFile,
     Append,
     Overwrite,
+    Resume,
+    DryRun,
+    Verify,
+    CompareSource,
+    ScratchDir,
     MaxDbSize,
+    Threads,
+    BatchSize,
+    MaxBatchBytes,
+    MaxCacheBytes,
+    BlockSelection,
+}
+
+/// Returns the arguments selecting the blocks whose state roots are kept,
+/// starting at `display_order`.
+pub(crate) fn block_selection_args(display_order: usize) -> [Arg<'static>; 4] {
+    [
+        Arg::new(FROM_HEIGHT)
+            .display_order(display_order)
+            .required(false)
+            .long(FROM_HEIGHT)
+            .takes_value(true)
+            .value_name("BLOCK_HEIGHT")
+            .help("Height of the lowest block whose state root is kept. Defaults to 0."),
+        Arg::new(TO_HEIGHT)
+            .display_order(display_order + 1)
+            .required(false)
+            .long(TO_HEIGHT)
+            .takes_value(true)
+            .value_name("BLOCK_HEIGHT")
+            .help(
+                "Height of the highest block whose state root is kept. Defaults to the highest \
+                block in storage.",
+            ),
+        Arg::new(LAST_BLOCKS)
+            .display_order(display_order + 2)
+            .required(false)
+            .long(LAST_BLOCKS)
+            .takes_value(true)
+            .value_name("BLOCK_COUNT")
+            .conflicts_with_all(&[FROM_HEIGHT, TO_HEIGHT, LAST_ERAS])
+            .help("Only keep the state roots of this many highest blocks."),
+        Arg::new(LAST_ERAS)
+            .display_order(display_order + 3)
+            .required(false)
+            .long(LAST_ERAS)
+            .takes_value(true)
+            .value_name("ERA_COUNT")
+            .conflicts_with_all(&[FROM_HEIGHT, TO_HEIGHT, LAST_BLOCKS])
+            .help(
+                "Only keep the state roots of the blocks in this many highest eras, including \
+                the era of the highest block.",
+            ),
+    ]
+}
+
+/// Returns the argument setting the directory of the scratch set of
+/// reachable tries, at `display_order`.
+pub(crate) fn scratch_dir_arg(display_order: usize) -> Arg<'static> {
+    Arg::new(SCRATCH_DIR)
+        .display_order(display_order)
+        .required(false)
+        .long(SCRATCH_DIR)
+        .takes_value(true)
+        .value_name("DIR_PATH")
+        .help(
+            "Directory in which the reachable tries are recorded in a temporary database, \
+            removed once done. The database may grow as large as the trie store, so the \
+            directory should be on disk rather than in memory. Defaults to the directory of \
+            the trie store being written to, or to the system's temporary directory if none \
+            is.",
+        )
+}
+
+/// Parses the argument returned by [`scratch_dir_arg`].
+pub(crate) fn scratch_dir(matches: &ArgMatches) -> Option<PathBuf> {
+    matches.value_of(SCRATCH_DIR).map(PathBuf::from)
+}
+
+/// Parses the arguments returned by [`block_selection_args`].
+pub(crate) fn block_selection(matches: &ArgMatches) -> BlockSelection {
+    let parse_count = |arg_name| {
+        matches.value_of(arg_name).map(|count: &str| {
+            count
+                .parse()
+                .ok()
+                .filter(|count| *count > 0)
+                .unwrap_or_else(|| panic!("Value of \"--{arg_name}\" must be a positive integer."))
+        })
+    };
+    let parse_height = |arg_name| {
+        matches.value_of(arg_name).map(|height: &str| {
+            height
+                .parse()
+                .unwrap_or_else(|_| panic!("Value of \"--{arg_name}\" must be an integer."))
+        })
+    };
+    match (parse_count(LAST_BLOCKS), parse_count(LAST_ERAS)) {
+        (Some(block_count), _) => BlockSelection::LastBlocks(block_count),
+        (_, Some(era_count)) => BlockSelection::LastEras(era_count),
+        _ => match (parse_height(FROM_HEIGHT), parse_height(TO_HEIGHT)) {
+            (None, None) => BlockSelection::All,
+            (Some(lowest), Some(highest)) if lowest > highest => panic!(
+                "Value of \"--{FROM_HEIGHT}\" must not be greater than the value of \
+                \"--{TO_HEIGHT}\"."
+            ),
+            (lowest, highest) => BlockSelection::HeightRange {
+                lowest: lowest.unwrap_or(0),
+                highest,
+            },
+        },
+    }
 }
 
 pub fn command(display_order: usize) -> Command<'static> {
@@ -90,7 +262,7 @@ pub fn command(display_order: usize) -> Command<'static> {
         .arg(
             Arg::new(DESTINATION_TRIE_STORE_PATH)
                 .display_order(DisplayOrder::DestinationPath as usize)
-                .required(true)
+                .required_unless_present(DRY_RUN)
                 .short('d')
                 .long(DESTINATION_TRIE_STORE_PATH)
                 .takes_value(true)
@@ -100,7 +272,7 @@ pub fn command(display_order: usize) -> Command<'static> {
         .arg(
             Arg::new(STORAGE_PATH)
                 .display_order(DisplayOrder::StoragePath as usize)
-                .required(true)
+                .required_unless_present(STATE_ROOTS_FILE)
                 .short('b')
                 .long(STORAGE_PATH)
                 .takes_value(true)
@@ -110,6 +282,20 @@ pub fn command(display_order: usize) -> Command<'static> {
                     blocks' state root hashes.",
                 ),
         )
+        .arg(
+            Arg::new(STATE_ROOTS_FILE)
+                .display_order(DisplayOrder::StateRootsFile as usize)
+                .required(false)
+                .long(STATE_ROOTS_FILE)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .conflicts_with_all(&[STORAGE_PATH, FROM_HEIGHT, TO_HEIGHT, LAST_BLOCKS, LAST_ERAS])
+                .help(
+                    "Path of a file with a hex encoded state root hash on each line, copied \
+                    instead of the state roots of the blocks in storage. Empty lines and lines \
+                    starting with '#' are ignored.",
+                ),
+        )
         .arg(
             Arg::new(APPEND)
                 .display_order(DisplayOrder::Append as usize)
@@ -136,6 +322,56 @@ pub fn command(display_order: usize) -> Command<'static> {
                     directory.",
                 ),
         )
+        .arg(
+            Arg::new(RESUME)
+                .display_order(DisplayOrder::Resume as usize)
+                .required(false)
+                .short('r')
+                .long(RESUME)
+                .takes_value(false)
+                .conflicts_with_all(&[APPEND, OVERWRITE])
+                .help(
+                    "Continue an interrupted compaction into the output `data.lmdb` file in \
+                    destination directory, skipping the state roots it already copied.",
+                ),
+        )
+        .arg(
+            Arg::new(DRY_RUN)
+                .display_order(DisplayOrder::DryRun as usize)
+                .required(false)
+                .long(DRY_RUN)
+                .takes_value(false)
+                .conflicts_with_all(&[APPEND, OVERWRITE, RESUME])
+                .help(
+                    "Only estimate the size of the output `data.lmdb` file, without creating \
+                    it. The visited tries are remembered in a temporary file.",
+                ),
+        )
+        .arg(
+            Arg::new(VERIFY)
+                .display_order(DisplayOrder::Verify as usize)
+                .required(false)
+                .long(VERIFY)
+                .takes_value(false)
+                .conflicts_with(DRY_RUN)
+                .help(
+                    "After compacting, check that all the tries reachable from the selected \
+                    state roots are in the output `data.lmdb` file.",
+                ),
+        )
+        .arg(
+            Arg::new(COMPARE_SOURCE)
+                .display_order(DisplayOrder::CompareSource as usize)
+                .required(false)
+                .long(COMPARE_SOURCE)
+                .takes_value(false)
+                .requires(VERIFY)
+                .help(
+                    "When verifying, also traverse the selected state roots in the source and \
+                    fail unless the number and total size of the tries match.",
+                ),
+        )
+        .arg(scratch_dir_arg(DisplayOrder::ScratchDir as usize))
         .arg(
             Arg::new(MAX_DB_SIZE)
                 .display_order(DisplayOrder::MaxDbSize as usize)
@@ -147,16 +383,68 @@ pub fn command(display_order: usize) -> Command<'static> {
                 .value_name("MAX_DB_SIZE")
                 .help("Maximum size the DB files are allowed to be, in bytes."),
         )
+        .arg(
+            Arg::new(THREADS)
+                .display_order(DisplayOrder::Threads as usize)
+                .required(false)
+                .short('t')
+                .long(THREADS)
+                .takes_value(true)
+                .default_value("1")
+                .value_name("THREAD_COUNT")
+                .help(
+                    "Number of threads reading tries from the source concurrently. All writes \
+                    go through a single additional thread.",
+                ),
+        )
+        .arg(
+            Arg::new(BATCH_SIZE)
+                .display_order(DisplayOrder::BatchSize as usize)
+                .required(false)
+                .long(BATCH_SIZE)
+                .takes_value(true)
+                .value_name("TRIE_COUNT")
+                .help(
+                    "Maximum number of tries written to the destination in a single write \
+                    transaction. Defaults to 10000.",
+                ),
+        )
+        .arg(
+            Arg::new(MAX_BATCH_BYTES)
+                .display_order(DisplayOrder::MaxBatchBytes as usize)
+                .required(false)
+                .long(MAX_BATCH_BYTES)
+                .takes_value(true)
+                .value_name("BYTE_COUNT")
+                .help(
+                    "Maximum number of bytes written to the destination in a single write \
+                    transaction. Defaults to 512 MiB.",
+                ),
+        )
+        .arg(
+            Arg::new(MAX_CACHE_BYTES)
+                .display_order(DisplayOrder::MaxCacheBytes as usize)
+                .required(false)
+                .long(MAX_CACHE_BYTES)
+                .takes_value(true)
+                .value_name("BYTE_COUNT")
+                .help(
+                    "Approximate maximum memory used to remember the tries copied so far, \
+                    saving lookups in the destination and, when copying with several threads, \
+                    reads of tries not committed yet. Once reached, the oldest half of the \
+                    tries is forgotten. Defaults to 256 MiB.",
+                ),
+        )
+        .args(block_selection_args(DisplayOrder::BlockSelection as usize))
 }
 
 pub fn run(matches: &ArgMatches) -> Result<(), Error> {
-    let storage_path = matches.value_of(STORAGE_PATH).unwrap();
     let source_trie_path = matches.value_of(SOURCE_TRIE_STORE_PATH).unwrap();
-    let destination_trie_path = matches.value_of(DESTINATION_TRIE_STORE_PATH).unwrap();
     // Prettier than C style if/else.
     let dest_opt = match matches {
         _ if matches.is_present(APPEND) => DestinationOptions::Append,
         _ if matches.is_present(OVERWRITE) => DestinationOptions::Overwrite,
+        _ if matches.is_present(RESUME) => DestinationOptions::Resume,
         _ => DestinationOptions::New,
     };
     let max_db_size = matches
@@ -164,12 +452,85 @@ pub fn run(matches: &ArgMatches) -> Result<(), Error> {
         .unwrap()
         .parse()
         .expect("Value of \"--max-db-size\" must be an integer.");
+    let thread_count = matches
+        .value_of(THREADS)
+        .unwrap()
+        .parse()
+        .ok()
+        .filter(|thread_count| *thread_count > 0)
+        .expect("Value of \"--threads\" must be a positive integer.");
+    let default_limits = BatchLimits::default();
+    let batch_limits = BatchLimits {
+        max_tries: matches
+            .value_of(BATCH_SIZE)
+            .map(|batch_size| {
+                batch_size
+                    .parse()
+                    .ok()
+                    .filter(|batch_size| *batch_size > 0)
+                    .expect("Value of \"--batch-size\" must be a positive integer.")
+            })
+            .unwrap_or(default_limits.max_tries),
+        max_bytes: matches
+            .value_of(MAX_BATCH_BYTES)
+            .map(|max_bytes| {
+                max_bytes
+                    .parse()
+                    .ok()
+                    .filter(|max_bytes| *max_bytes > 0)
+                    .expect("Value of \"--max-batch-bytes\" must be a positive integer.")
+            })
+            .unwrap_or(default_limits.max_bytes),
+    };
+    let max_cache_bytes = matches
+        .value_of(MAX_CACHE_BYTES)
+        .map(|max_bytes| {
+            max_bytes
+                .parse()
+                .expect("Value of \"--max-cache-bytes\" must be an integer.")
+        })
+        .unwrap_or(DEFAULT_MAX_CACHE_BYTES);
+    let state_root_source = match matches.value_of(STATE_ROOTS_FILE) {
+        Some(state_roots_file) => {
+            StateRootSource::List(compact::read_state_roots_file(state_roots_file)?)
+        }
+        None => StateRootSource::storage(
+            matches.value_of(STORAGE_PATH).unwrap(),
+            block_selection(matches),
+        ),
+    };
 
+    if matches.is_present(DRY_RUN) {
+        return estimate::estimate_compaction(
+            &state_root_source,
+            source_trie_path,
+            max_db_size,
+            scratch_dir(matches).as_deref(),
+        )
+        .map(|_| ());
+    }
+    let destination_trie_path = matches.value_of(DESTINATION_TRIE_STORE_PATH).unwrap();
     compact::trie_compact(
-        storage_path,
+        &state_root_source,
         source_trie_path,
         destination_trie_path,
         dest_opt,
         max_db_size,
-    )
+        CompactOptions {
+            thread_count,
+            batch_limits,
+            max_cache_bytes,
+        },
+    )?;
+    if matches.is_present(VERIFY) {
+        verify::verify_compaction(
+            &state_root_source,
+            source_trie_path,
+            destination_trie_path,
+            max_db_size,
+            matches.is_present(COMPARE_SOURCE),
+            scratch_dir(matches).as_deref(),
+        )?;
+    }
+    Ok(())
 }
@@ -1,16 +1,20 @@
 use std::{
     collections::HashSet,
     fs::{self, File, OpenOptions},
-    path::Path,
+    path::{Path, PathBuf},
+    slice,
 };
 
 use log::info;
 
 use casper_hashing::Digest;
+use casper_node::{storage::Storage, types::BlockHeader};
 
 use crate::common::db::TRIE_STORE_FILE_NAME;
 
 use super::{
+    helpers::{self, BatchLimits, CopiedTries, DEFAULT_MAX_CACHE_BYTES},
+    progress_log::{self, ProgressEntry, ProgressLog, PROGRESS_LOG_FILE_NAME},
     utils::{create_execution_engine, create_storage, load_execution_engine},
     Error,
 };
@@ -24,6 +28,140 @@ pub enum DestinationOptions {
     Overwrite,
     /// `data.lmdb` must not exist in destination directory.
     New,
+    /// `data.lmdb` in destination directory will be appended, skipping the
+    /// state roots recorded as copied by a previous run.
+    Resume,
+}
+
+/// Selects the blocks whose state roots are copied to the destination.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BlockSelection {
+    /// All blocks, down to genesis.
+    #[default]
+    All,
+    /// Blocks with heights in the inclusive range. Without an upper bound,
+    /// the range extends to the highest block.
+    HeightRange { lowest: u64, highest: Option<u64> },
+    /// The given number of highest blocks.
+    LastBlocks(u64),
+    /// Blocks in the given number of highest eras, including the era of the
+    /// highest block.
+    LastEras(u64),
+}
+
+impl BlockSelection {
+    /// Returns the height of the highest selected block.
+    fn highest_height(&self, highest_header: &BlockHeader) -> u64 {
+        match self {
+            BlockSelection::HeightRange {
+                highest: Some(height),
+                ..
+            } => (*height).min(highest_header.height()),
+            _ => highest_header.height(),
+        }
+    }
+
+    /// Returns whether the block is selected, provided it isn't above the
+    /// highest selected block.
+    fn contains(&self, header: &BlockHeader, highest_header: &BlockHeader) -> bool {
+        match self {
+            BlockSelection::All => true,
+            BlockSelection::HeightRange { lowest, .. } => header.height() >= *lowest,
+            BlockSelection::LastBlocks(count) => {
+                header.height().saturating_add(*count) > highest_header.height()
+            }
+            BlockSelection::LastEras(count) => {
+                header.era_id().value().saturating_add(*count) > highest_header.era_id().value()
+            }
+        }
+    }
+}
+
+/// Origin of the state roots to copy.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StateRootSource {
+    /// The state roots of the selected blocks in the storage at `path`.
+    Storage {
+        path: PathBuf,
+        block_selection: BlockSelection,
+    },
+    /// The given state roots, without relying on any storage.
+    List(Vec<Digest>),
+}
+
+impl StateRootSource {
+    /// Creates a source reading the state roots of the blocks selected by
+    /// `block_selection` from the storage at `path`.
+    pub fn storage<P: AsRef<Path>>(path: P, block_selection: BlockSelection) -> Self {
+        StateRootSource::Storage {
+            path: path.as_ref().to_path_buf(),
+            block_selection,
+        }
+    }
+
+    /// Returns the state roots missing from `visited_roots`, adding them to
+    /// it. Blocks above `resume_height`, if any, aren't considered.
+    pub(crate) fn state_roots(
+        &self,
+        resume_height: Option<u64>,
+        visited_roots: &mut HashSet<Digest>,
+    ) -> Result<Vec<ProgressEntry>, Error> {
+        match self {
+            StateRootSource::Storage {
+                path,
+                block_selection,
+            } => {
+                let storage = create_storage(path).map_err(Error::OpenStorage)?;
+                selected_state_roots(&storage, *block_selection, resume_height, visited_roots)
+            }
+            StateRootSource::List(state_roots) => Ok(state_roots
+                .iter()
+                .filter(|state_root| visited_roots.insert(**state_root))
+                .map(|state_root| ProgressEntry {
+                    block_height: None,
+                    state_root: *state_root,
+                })
+                .collect()),
+        }
+    }
+}
+
+/// Reads a file with a hex encoded state root hash on each line. Empty
+/// lines and lines starting with `#` are ignored.
+pub fn read_state_roots_file<P: AsRef<Path>>(path: P) -> Result<Vec<Digest>, Error> {
+    let contents = fs::read_to_string(&path)
+        .map_err(|io_err| Error::StateRootsFile(path.as_ref().to_path_buf(), io_err))?;
+    contents
+        .lines()
+        .enumerate()
+        .map(|(idx, line)| (idx, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(idx, line)| {
+            Digest::from_hex(line)
+                .map_err(|hashing_err| Error::InvalidStateRoot(idx + 1, hashing_err))
+        })
+        .collect()
+}
+
+/// Settings of the compaction with sensible defaults.
+#[derive(Clone, Copy, Debug)]
+pub struct CompactOptions {
+    /// Number of threads reading tries from the source concurrently.
+    pub thread_count: usize,
+    /// Limits of the transactions writing to the destination.
+    pub batch_limits: BatchLimits,
+    /// Cap on the memory used to remember the tries copied so far.
+    pub max_cache_bytes: usize,
+}
+
+impl Default for CompactOptions {
+    fn default() -> Self {
+        Self {
+            thread_count: 1,
+            batch_limits: BatchLimits::default(),
+            max_cache_bytes: DEFAULT_MAX_CACHE_BYTES,
+        }
+    }
 }
 
 fn validate_trie_paths<P1: AsRef<Path>, P2: AsRef<Path>>(
@@ -51,6 +189,11 @@ fn validate_trie_paths<P1: AsRef<Path>, P2: AsRef<Path>>(
                         .to_string(),
                 ));
             }
+            DestinationOptions::Resume => {
+                return Err(Error::InvalidDest(
+                    "No destination trie to resume. Consider not using \"--resume\".".to_string(),
+                ));
+            }
         }
     } else {
         let dest_data_exists = destination_trie_path
@@ -83,6 +226,17 @@ fn validate_trie_paths<P1: AsRef<Path>, P2: AsRef<Path>>(
                     )));
                 }
             }
+            DestinationOptions::Resume => {
+                let progress_log_path = destination_trie_path.as_ref().join(PROGRESS_LOG_FILE_NAME);
+                if !dest_data_exists || !progress_log_path.exists() {
+                    return Err(Error::InvalidDest(format!(
+                        "Nothing to resume, output file \"data.lmdb\" or progress log \"{}\" \
+                        doesn't exist at destination \"{}\". Run the program without `--resume`",
+                        PROGRESS_LOG_FILE_NAME,
+                        destination_trie_path.as_ref().to_string_lossy()
+                    )));
+                }
+            }
             DestinationOptions::Overwrite => {
                 if dest_data_exists {
                     let _f: File = OpenOptions::new()
@@ -126,68 +280,178 @@ fn validate_trie_paths<P1: AsRef<Path>, P2: AsRef<Path>>(
     Ok(())
 }
 
+/// Walks the blocks selected by `block_selection` from highest to lowest and
+/// returns the state roots missing from `visited_roots`, along with the
+/// height of the highest block they belong to. The returned roots are added
+/// to `visited_roots`.
+///
+/// If `resume_height` is given, no block above it is considered.
+pub(crate) fn selected_state_roots(
+    storage: &Storage,
+    block_selection: BlockSelection,
+    resume_height: Option<u64>,
+    visited_roots: &mut HashSet<Digest>,
+) -> Result<Vec<ProgressEntry>, Error> {
+    let highest_header = match storage
+        .read_highest_block()
+        .map_err(|err| Error::Storage(0, err))?
+    {
+        Some(block) => block.take_header(),
+        None => {
+            info!("No blocks found in storage.");
+            return Ok(vec![]);
+        }
+    };
+    if let BlockSelection::HeightRange { lowest, .. } = block_selection {
+        if lowest > highest_header.height() {
+            return Err(Error::LowestHeightAboveHighestBlock(
+                lowest,
+                highest_header.height(),
+            ));
+        }
+    }
+    let mut block_height = block_selection.highest_height(&highest_header);
+    // Blocks above the last recorded one were already processed.
+    if let Some(height) = resume_height {
+        block_height = block_height.min(height);
+    }
+    let mut state_roots = vec![];
+
+    loop {
+        let header = storage
+            .read_block_by_height(block_height)
+            .map_err(|storage_err| Error::Storage(block_height, storage_err))?
+            .ok_or(Error::MissingBlock(block_height))?
+            .take_header();
+        if !block_selection.contains(&header, &highest_header) {
+            break;
+        }
+        let state_root = *header.state_root_hash();
+        if visited_roots.insert(state_root) {
+            state_roots.push(ProgressEntry {
+                block_height: Some(block_height),
+                state_root,
+            });
+        }
+        if block_height == 0 {
+            break;
+        }
+        block_height -= 1;
+    }
+    Ok(state_roots)
+}
+
 /// Compacts a source trie and outputs the result to the destination trie.
 ///
-/// The function first retrieves the highest block hash from storage and
-/// compacting starts from that state root hash. Each descendant of that
-/// block's hash is copied to the destination trie. This process is repeated
-/// for all the remaining blocks, from highest to lowest.
-pub fn trie_compact<P1: AsRef<Path>, P2: AsRef<Path>, P3: AsRef<Path>>(
-    storage_path: P1,
-    source_trie_path: P2,
-    destination_trie_path: P3,
+/// With a storage as `state_root_source`, the function first retrieves the
+/// highest block hash from storage and compacting starts from that state
+/// root hash. Each descendant of that block's hash is copied to the
+/// destination trie. This process is repeated for all the remaining blocks,
+/// from highest to lowest. Only the selected blocks are considered, so that
+/// the destination can hold the global state of recent blocks only. With a
+/// list as `state_root_source`, the state roots are copied in order.
+///
+/// With a `thread_count` option greater than 1, the state roots are copied
+/// concurrently, yielding the same destination contents. Tries are written
+/// to the destination in transactions bounded by the `batch_limits` option,
+/// children before their parent.
+///
+/// Every copied state root is recorded in a progress log in the destination
+/// directory once flushed, so that an interrupted compaction can continue
+/// where it stopped with [`DestinationOptions::Resume`].
+pub fn trie_compact<P1: AsRef<Path>, P2: AsRef<Path>>(
+    state_root_source: &StateRootSource,
+    source_trie_path: P1,
+    destination_trie_path: P2,
     dest_opt: DestinationOptions,
     max_db_size: usize,
+    options: CompactOptions,
 ) -> Result<(), Error> {
+    let CompactOptions {
+        thread_count,
+        batch_limits,
+        max_cache_bytes,
+    } = options;
     validate_trie_paths(&source_trie_path, &destination_trie_path, dest_opt)?;
 
     let (source_state, _env) =
         load_execution_engine(source_trie_path, max_db_size, Digest::default(), true)
             .map_err(Error::OpenSourceTrie)?;
 
+    let progress_log_path = destination_trie_path.as_ref().join(PROGRESS_LOG_FILE_NAME);
+    let resume = dest_opt == DestinationOptions::Resume;
+    let (completed_roots, resume_height) = if resume {
+        let entries = progress_log::read_progress_log(&progress_log_path)?;
+        let resume_height = entries.last().and_then(|entry| entry.block_height);
+        let completed_roots: HashSet<Digest> =
+            entries.into_iter().map(|entry| entry.state_root).collect();
+        (completed_roots, resume_height)
+    } else {
+        (HashSet::new(), None)
+    };
+
     let (destination_state, _env) =
-        create_execution_engine(destination_trie_path, max_db_size, true)
+        create_execution_engine(&destination_trie_path, max_db_size, true)
             .map_err(Error::CreateDestTrie)?;
+    let mut progress_log = ProgressLog::open(&progress_log_path, resume)?;
 
-    // Create a separate lmdb for block/deploy storage at chain_download_path.
-    let storage = create_storage(&storage_path).map_err(Error::OpenStorage)?;
-
-    let mut block = match storage
-        .read_highest_block()
-        .map_err(|err| Error::Storage(0, err))?
-    {
-        Some(block) => block,
-        None => {
-            info!("No blocks found in storage, exiting.");
-            return Ok(());
-        }
-    };
-    let mut visited_roots = HashSet::new();
-    let mut block_height;
+    let mut visited_roots = completed_roots;
+    let skipped_root_count = visited_roots.len();
+    let state_roots = state_root_source.state_roots(resume_height, &mut visited_roots)?;
 
+    if resume {
+        info!("Skipping {skipped_root_count} state roots copied before resuming.");
+    }
     info!("Copying state roots from source to destination.");
-    loop {
-        block_height = block.height();
-        let state_root = *block.take_header().state_root_hash();
-        if !visited_roots.contains(&state_root) {
-            super::helpers::copy_state_root(state_root, &source_state, &destination_state)
-                .map_err(|err| Error::CopyStateRoot(state_root, err))?;
+    if thread_count > 1 {
+        let roots: Vec<Digest> = state_roots.iter().map(|entry| entry.state_root).collect();
+        // Roots may be copied out of order, but the progress log only
+        // records the copied ones preceding the first root not copied yet,
+        // so that resuming from its last entry doesn't skip any root.
+        let mut copied = vec![false; state_roots.len()];
+        let mut logged_count = 0;
+        helpers::copy_state_roots_parallel(
+            &roots,
+            &source_state,
+            &destination_state,
+            thread_count,
+            batch_limits,
+            max_cache_bytes,
+            &mut |root_indices| {
+                for root_index in root_indices {
+                    copied[*root_index] = true;
+                }
+                let copied_count = copied[logged_count..]
+                    .iter()
+                    .take_while(|copied| **copied)
+                    .count();
+                if copied_count > 0 {
+                    progress_log.append(&state_roots[logged_count..logged_count + copied_count])?;
+                    logged_count += copied_count;
+                }
+                Ok(())
+            },
+        )?;
+    } else {
+        let mut copied_tries = CopiedTries::new(max_cache_bytes);
+        for entry in state_roots.iter() {
+            helpers::copy_state_root_batched(
+                entry.state_root,
+                &source_state,
+                &destination_state,
+                batch_limits,
+                &mut copied_tries,
+            )
+            .map_err(|err| Error::CopyStateRoot(entry.state_root, err))?;
             destination_state
                 .flush_environment()
                 .map_err(Error::LmdbOperation)?;
-            visited_roots.insert(state_root);
-        }
-        if block_height == 0 {
-            break;
+            progress_log.append(slice::from_ref(entry))?;
         }
-        block = storage
-            .read_block_by_height(block_height - 1)
-            .map_err(|storage_err| Error::Storage(block_height - 1, storage_err))?
-            .ok_or(Error::MissingBlock(block_height - 1))?;
     }
     info!(
         "Finished copying {} state roots to new database.",
-        visited_roots.len()
+        state_roots.len()
     );
 
     Ok(())
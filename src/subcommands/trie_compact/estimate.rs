@@ -0,0 +1,98 @@
+use std::{
+    collections::HashSet,
+    env, fs,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use log::info;
+
+use crate::common::db::TRIE_STORE_FILE_NAME;
+
+use super::{
+    compact::StateRootSource, helpers::ReachableTries, utils::open_read_only_trie_store, Error,
+};
+
+/// Estimated size of a compacted trie store.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SizeEstimate {
+    /// Number of distinct state roots traversed.
+    pub state_root_count: usize,
+    /// Number of distinct trie nodes reachable from the state roots.
+    pub node_count: usize,
+    /// Total size of the keys and values of the reachable trie nodes.
+    pub total_bytes: u64,
+    /// Size of the source `data.lmdb` file.
+    pub source_file_size: u64,
+}
+
+impl SizeEstimate {
+    /// Returns by how much, in percents, the source would shrink. LMDB page
+    /// overhead isn't accounted for, so the actual reduction is smaller.
+    pub fn reduction_percentage(&self) -> f64 {
+        if self.source_file_size == 0 {
+            return 0.0;
+        }
+        100.0 * (1.0 - self.total_bytes as f64 / self.source_file_size as f64)
+    }
+}
+
+/// Estimates the size of the trie store `trie_compact` would produce,
+/// without creating a destination.
+///
+/// The source is opened read-only, so that a running node's trie store can
+/// be estimated. The trie nodes reachable from the state roots of
+/// `state_root_source` are traversed once each, using a temporary file
+/// under `scratch_dir`, or the system's temporary directory if unset, to
+/// remember the visited ones.
+pub fn estimate_compaction<P: AsRef<Path>>(
+    state_root_source: &StateRootSource,
+    source_trie_path: P,
+    max_db_size: usize,
+    scratch_dir: Option<&Path>,
+) -> Result<SizeEstimate, Error> {
+    let source_file_path = source_trie_path.as_ref().join(TRIE_STORE_FILE_NAME);
+    let source_file_size = fs::metadata(&source_file_path)
+        .map_err(|io_err| Error::InvalidPath(source_file_path, io_err))?
+        .len();
+    let (source_env, source_db) =
+        open_read_only_trie_store(&source_trie_path).map_err(Error::OpenSourceTrie)?;
+    let state_roots = state_root_source.state_roots(None, &mut HashSet::new())?;
+
+    let scratch_dir = scratch_dir.map_or_else(env::temp_dir, Path::to_path_buf);
+    let reachable_tries =
+        ReachableTries::new(scratch_dir, max_db_size).map_err(Error::CreateScratch)?;
+    let mut estimate = SizeEstimate {
+        state_root_count: state_roots.len(),
+        source_file_size,
+        ..Default::default()
+    };
+    info!("Traversing {} state roots.", state_roots.len());
+    let start = Instant::now();
+    for entry in state_roots.iter() {
+        let (node_count, total_bytes) = reachable_tries
+            .mark_state_root(entry.state_root, &source_env, source_db)
+            .map_err(|err| Error::TraverseStateRoot(entry.state_root, err))?;
+        estimate.node_count += node_count;
+        estimate.total_bytes += total_bytes;
+    }
+    log_estimate(&estimate, start.elapsed());
+    Ok(estimate)
+}
+
+fn log_estimate(estimate: &SizeEstimate, elapsed: Duration) {
+    info!(
+        "Found {} trie nodes totaling {} bytes under {} state roots.",
+        estimate.node_count, estimate.total_bytes, estimate.state_root_count
+    );
+    info!(
+        "The source file is {} bytes, compaction would shrink it by about {:.2}%.",
+        estimate.source_file_size,
+        estimate.reduction_percentage()
+    );
+    info!(
+        "Traversal took {:.2?}. Compaction reads the same tries and also writes them, so it \
+        takes at least as long.",
+        elapsed
+    );
+}
@@ -1,6 +1,21 @@
-use std::time::{Duration, Instant};
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    fs, mem,
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Receiver, SyncSender},
+        Arc, Condvar, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
 
-use lmdb::{RwTransaction, Transaction};
+use lmdb::{
+    Database, DatabaseFlags, Environment, EnvironmentFlags, Error as LmdbError, RoTransaction,
+    RwTransaction, Transaction, WriteFlags,
+};
 use log::{info, warn};
 
 use casper_execution_engine::{
@@ -8,84 +23,197 @@ use casper_execution_engine::{
     storage::{
         global_state::lmdb::LmdbGlobalState,
         transaction_source::{Readable, TransactionSource, Writable},
-        trie::{Pointer, Trie},
+        trie::Trie,
         trie_store::lmdb::LmdbTrieStore,
     },
 };
 use casper_hashing::Digest;
 use casper_types::{
-    bytesrepr::{self, Bytes, ToBytes},
+    bytesrepr::{self, ToBytes},
     Key, StoredValue,
 };
 
-fn memoized_find_missing_descendants(
-    value_bytes: Bytes,
-    trie_store: &LmdbTrieStore,
-    txn: &RwTransaction<'_>,
-    missing_trie_keys: &mut Vec<Digest>,
-    time_in_missing_trie_keys: &mut Duration,
-) -> Result<(), anyhow::Error> {
-    // A first bytes of `0` indicates a leaf. We short-circuit the function here to speed things up.
-    if let Some(0u8) = value_bytes.first() {
-        return Ok(());
+use super::Error;
+
+/// Number of tries each reader thread may have read ahead of the writer.
+const COPIED_TRIES_PER_THREAD: usize = 1024;
+
+/// Number of tries a reader thread reads before beginning a new transaction
+/// in the destination.
+const READS_PER_DESTINATION_TXN: usize = 10_000;
+
+/// Approximate memory taken by a key in the set of copied tries: the key
+/// itself and the control byte of the hash set, at the lowest load factor
+/// of 7/16 reached right after the set grew.
+pub(super) const COPIED_TRIE_BYTES: usize = (Digest::LENGTH + 1) * 16 / 7;
+
+/// Default cap on the memory used to remember the tries copied in a run.
+pub const DEFAULT_MAX_CACHE_BYTES: usize = 256 * 1024 * 1024;
+
+/// Number of reachable tries recorded in a single write transaction of the
+/// scratch set.
+const REACHABLE_TRIES_PER_TXN: usize = 100_000;
+
+/// Distinguishes the scratch sets of concurrent traversals in this process.
+static SCRATCH_SET_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Prefix of the directories holding the sets of reachable tries, left
+/// behind if the process is killed.
+const SCRATCH_DIR_PREFIX: &str = "reachable_tries_";
+
+/// Limits on the amount of work done in a single write transaction when
+/// copying tries.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BatchLimits {
+    /// Maximum number of tries written in a single transaction.
+    pub max_tries: usize,
+    /// Maximum number of bytes written in a single transaction.
+    pub max_bytes: usize,
+}
+
+impl Default for BatchLimits {
+    fn default() -> Self {
+        Self {
+            max_tries: 10_000,
+            max_bytes: 512 * 1024 * 1024,
+        }
     }
-    let start_trie_keys = Instant::now();
-    let trie: Trie<Key, StoredValue> = bytesrepr::deserialize(value_bytes.into())
-        .map_err(|err| anyhow::anyhow!("couldn't deserialize trie: {:?}", err))?;
-    match trie {
-        Trie::Leaf { .. } => {
-            // If `bytesrepr` is functioning correctly, this should never be reached (see
-            // optimization above), but it is still correct do nothing here.
-            warn!("did not expect to see a trie leaf in `find_missing_descendents` after shortcut");
-        }
-        Trie::Node { pointer_block } => {
-            for (_index, ptr) in pointer_block.as_indexed_pointers() {
-                find_missing_trie_keys(ptr, missing_trie_keys, trie_store, txn)?;
-            }
+}
+
+/// Keys of the tries copied to the destination during this run, saving
+/// lookups in the destination.
+///
+/// The keys are kept in two generations of bounded size. Once the recent
+/// one is full, the older one is forgotten, so that only the oldest half of
+/// the keys is evicted and the destination is looked up for them instead.
+#[derive(Debug)]
+pub struct CopiedTries {
+    recent: HashSet<Digest>,
+    older: HashSet<Digest>,
+    /// Maximum number of keys in a generation.
+    generation_len: usize,
+}
+
+impl CopiedTries {
+    /// Creates an empty set whose memory use is capped to roughly
+    /// `max_bytes`.
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            recent: HashSet::new(),
+            older: HashSet::new(),
+            generation_len: max_bytes / 2 / COPIED_TRIE_BYTES,
         }
-        Trie::Extension { affix: _, pointer } => {
-            find_missing_trie_keys(pointer, missing_trie_keys, trie_store, txn)?;
+    }
+
+    pub(super) fn contains(&self, trie_key: &Digest) -> bool {
+        self.recent.contains(trie_key) || self.older.contains(trie_key)
+    }
+
+    /// Records a copied trie, forgetting the older generation if the recent
+    /// one is full.
+    pub(super) fn insert(&mut self, trie_key: Digest) {
+        if self.generation_len == 0 {
+            return;
+        }
+        if self.recent.len() >= self.generation_len {
+            self.older = mem::take(&mut self.recent);
         }
+        self.recent.insert(trie_key);
     }
-    *time_in_missing_trie_keys += start_trie_keys.elapsed();
-    Ok(())
 }
 
-fn find_missing_trie_keys(
-    ptr: Pointer,
-    missing_trie_keys: &mut Vec<Digest>,
-    handle: &LmdbTrieStore,
+impl Default for CopiedTries {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_CACHE_BYTES)
+    }
+}
+
+/// Returns whether the trie with the given key was copied to the
+/// destination, looking it up through the destination's write transaction
+/// unless it's recorded in `copied_tries`.
+fn is_copied(
+    trie_key: &Digest,
+    trie_store: &LmdbTrieStore,
     txn: &RwTransaction<'_>,
-) -> Result<(), anyhow::Error> {
-    let ptr = match ptr {
-        Pointer::LeafPointer(pointer) | Pointer::NodePointer(pointer) => pointer,
-    };
+    copied_tries: &CopiedTries,
+) -> Result<bool, anyhow::Error> {
+    if copied_tries.contains(trie_key) {
+        return Ok(true);
+    }
     let existing = txn.read(
-        handle.get_db(),
-        &ptr.to_bytes()
+        trie_store.get_db(),
+        &trie_key
+            .to_bytes()
             .map_err(|err| anyhow::anyhow!("couldn't serialize trie pointer: {:?}", err))?,
     )?;
-    if existing.is_none() {
-        missing_trie_keys.push(ptr);
-    }
-    Ok(())
+    Ok(existing.is_some())
 }
 
+/// Copies a state root and all its descendants missing from the destination.
 pub fn copy_state_root(
     state_root: Digest,
     source: &EngineState<LmdbGlobalState>,
     destination: &EngineState<LmdbGlobalState>,
 ) -> Result<(), anyhow::Error> {
-    let mut missing_trie_keys = vec![state_root];
+    copy_state_root_batched(
+        state_root,
+        source,
+        destination,
+        BatchLimits::default(),
+        &mut CopiedTries::default(),
+    )
+}
+
+/// Trie node read from the source, written to the destination once all its
+/// children are.
+struct UnwrittenTrie {
+    trie_key: Digest,
+    key_bytes: Vec<u8>,
+    value_bytes: Vec<u8>,
+    /// Children not looked up in the destination yet.
+    children: Vec<Digest>,
+}
+
+/// Copies a state root and all its descendants missing from the destination,
+/// writing the tries in transactions bounded by `limits`.
+///
+/// Children are written before their parent, so that any trie in the
+/// destination has its whole subtree there as well, even if the copy is
+/// interrupted between two transactions. Tries found in the destination
+/// are therefore skipped along with their descendants.
+///
+/// The tries recorded in `copied_tries` are assumed to be in the destination
+/// already, and the newly copied ones are added to it.
+pub fn copy_state_root_batched(
+    state_root: Digest,
+    source: &EngineState<LmdbGlobalState>,
+    destination: &EngineState<LmdbGlobalState>,
+    limits: BatchLimits,
+    copied_tries: &mut CopiedTries,
+) -> Result<(), anyhow::Error> {
     let start_time = Instant::now();
     let mut heartbeat_interval = Instant::now();
 
     let mut total_tries: u64 = 0;
     let mut total_bytes: u64 = 0;
+    let mut batch_tries: usize = 0;
+    let mut batch_bytes: usize = 0;
 
     let mut time_searching_for_trie_keys = Duration::from_secs(0);
 
-    while let Some(next_trie_key) = missing_trie_keys.pop() {
+    let source_store = source.get_state().trie_store();
+    let destination_store = destination.get_state().trie_store();
+    // The source isn't written to, so a single read transaction is enough.
+    let read_txn = source.get_state().environment().create_read_txn()?;
+    let mut write_txn = destination
+        .get_state()
+        .environment()
+        .create_read_write_txn()?;
+
+    let mut unwritten_tries: Vec<UnwrittenTrie> = vec![];
+    let mut next_trie_key = (!is_copied(&state_root, destination_store, &write_txn, copied_tries)?)
+        .then_some(state_root);
+    loop {
         // For user feedback, update on progress if this takes longer than 10 seconds.
         if heartbeat_interval.elapsed().as_secs() > 10 {
             info!(
@@ -95,48 +223,65 @@ pub fn copy_state_root(
             heartbeat_interval = Instant::now();
         }
 
-        let source_store = source.get_state().trie_store();
-        let destination_store = destination.get_state().trie_store();
-        let trie_key_bytes = next_trie_key
-            .to_bytes()
-            .map_err(|err| anyhow::anyhow!("couldn't serialize trie key: {:?}", err))?;
+        if let Some(trie_key) = next_trie_key.take() {
+            let key_bytes = trie_key
+                .to_bytes()
+                .map_err(|err| anyhow::anyhow!("couldn't serialize trie key: {:?}", err))?;
+            let value_bytes = read_txn
+                .read(source_store.get_db(), &key_bytes)?
+                .ok_or_else(|| {
+                    anyhow::anyhow!("error migrating state root {} {}, ", state_root, trie_key)
+                })?
+                .to_vec();
+            let mut children = trie_children(&value_bytes)?;
+            // Children are popped, so reverse them to copy them in order.
+            children.reverse();
+            unwritten_tries.push(UnwrittenTrie {
+                trie_key,
+                key_bytes,
+                value_bytes,
+                children,
+            });
+        }
 
-        let read_txn = source.get_state().environment().create_read_txn()?;
-        let mut write_txn = destination
-            .get_state()
-            .environment()
-            .create_read_write_txn()?;
-
-        match read_txn.read(source_store.get_db(), &trie_key_bytes)? {
-            Some(value_bytes) => {
-                let key_bytes = next_trie_key
-                    .to_bytes()
-                    .map_err(|err| anyhow::anyhow!("couldn't serialize trie key: {:?}", err))?;
-                let read_bytes = key_bytes.len() as u64 + value_bytes.len() as u64;
-                total_bytes += read_bytes;
-                total_tries += 1;
-
-                write_txn.write(destination_store.get_db(), &key_bytes, &value_bytes)?;
-
-                memoized_find_missing_descendants(
-                    value_bytes,
-                    destination_store,
-                    &write_txn,
-                    &mut missing_trie_keys,
-                    &mut time_searching_for_trie_keys,
-                )?;
-            }
-            None => {
-                return Err(anyhow::anyhow!(
-                    "error migrating state root {} {}, ",
-                    state_root,
-                    next_trie_key
-                ));
+        let parent = match unwritten_tries.last_mut() {
+            Some(parent) => parent,
+            None => break,
+        };
+        if let Some(child) = parent.children.pop() {
+            let start_trie_keys = Instant::now();
+            if !is_copied(&child, destination_store, &write_txn, copied_tries)? {
+                next_trie_key = Some(child);
             }
+            time_searching_for_trie_keys += start_trie_keys.elapsed();
+            continue;
+        }
+
+        // All the children of the trie were written.
+        let trie = unwritten_tries.pop().expect("should have a trie to write");
+        write_txn.write(
+            destination_store.get_db(),
+            &trie.key_bytes,
+            &trie.value_bytes,
+        )?;
+        copied_tries.insert(trie.trie_key);
+        let written_bytes = trie.key_bytes.len() + trie.value_bytes.len();
+        total_bytes += written_bytes as u64;
+        total_tries += 1;
+        batch_tries += 1;
+        batch_bytes += written_bytes;
+        if batch_tries >= limits.max_tries || batch_bytes >= limits.max_bytes {
+            write_txn.commit()?;
+            write_txn = destination
+                .get_state()
+                .environment()
+                .create_read_write_txn()?;
+            batch_tries = 0;
+            batch_bytes = 0;
         }
-        read_txn.commit()?;
-        write_txn.commit()?;
     }
+    read_txn.commit()?;
+    write_txn.commit()?;
 
     info!(
         "Trie migration complete\nTotal bytes: {}\n\
@@ -149,3 +294,676 @@ pub fn copy_state_root(
     );
     Ok(())
 }
+
+/// Callback receiving the indices of the state roots fully copied to the
+/// destination, once the tries under them are committed and flushed.
+pub type RootsCopiedFn<'a> = dyn FnMut(&[usize]) -> Result<(), Error> + 'a;
+
+/// Trie node read from the source, along with the state root it was
+/// reached from.
+struct CopiedTrie {
+    state_root: Digest,
+    /// Indices of the state roots this trie is, if it's one of them.
+    root_indices: Vec<usize>,
+    key_bytes: Vec<u8>,
+    value_bytes: Vec<u8>,
+}
+
+/// Trie node claimed by a reader, handed to the writer once it's read and
+/// all its children missing from the destination were.
+struct ReadTrie {
+    state_root: Digest,
+    root_indices: Vec<usize>,
+    trie_key: Digest,
+    /// Serialized key and value, set once read from the source and taken by
+    /// the thread handing the trie to the writer.
+    bytes: Mutex<Option<(Vec<u8>, Vec<u8>)>>,
+    /// Number of children not handed to the writer yet, plus one until the
+    /// trie itself is read.
+    unsent_count: AtomicUsize,
+}
+
+impl ReadTrie {
+    fn new(state_root: Digest, root_indices: Vec<usize>, trie_key: Digest) -> Self {
+        Self {
+            state_root,
+            root_indices,
+            trie_key,
+            bytes: Mutex::new(None),
+            unsent_count: AtomicUsize::new(1),
+        }
+    }
+}
+
+/// Tries claimed by the readers, so that a trie reached from several
+/// parents is only read once.
+struct ClaimedTries {
+    /// Tries not handed to the writer yet, along with the parents waiting
+    /// for them.
+    unsent: HashMap<Digest, Vec<Arc<ReadTrie>>>,
+    /// Tries handed to the writer, which may not be committed yet.
+    sent: CopiedTries,
+}
+
+impl ClaimedTries {
+    /// Makes `parent` wait for a child missing from the destination, and
+    /// returns the child if it wasn't claimed before and must be read.
+    fn claim_child(&mut self, parent: &Arc<ReadTrie>, trie_key: Digest) -> Option<Arc<ReadTrie>> {
+        if self.sent.contains(&trie_key) {
+            return None;
+        }
+        parent.unsent_count.fetch_add(1, Ordering::AcqRel);
+        match self.unsent.entry(trie_key) {
+            Entry::Occupied(mut entry) => {
+                entry.get_mut().push(Arc::clone(parent));
+                None
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(vec![Arc::clone(parent)]);
+                Some(Arc::new(ReadTrie::new(parent.state_root, vec![], trie_key)))
+            }
+        }
+    }
+}
+
+/// Work shared by the threads reading from the source.
+#[derive(Default)]
+struct TrieQueue {
+    pending: Vec<Arc<ReadTrie>>,
+    /// Number of nodes being read, whose children aren't queued yet.
+    in_progress: usize,
+    failed: bool,
+}
+
+/// Returns the keys of the children of a serialized trie node.
+pub(super) fn trie_children(value_bytes: &[u8]) -> Result<Vec<Digest>, anyhow::Error> {
+    // A first bytes of `0` indicates a leaf, which has no children.
+    if let Some(0u8) = value_bytes.first() {
+        return Ok(vec![]);
+    }
+    let trie: Trie<Key, StoredValue> = bytesrepr::deserialize(value_bytes.to_vec())
+        .map_err(|err| anyhow::anyhow!("couldn't deserialize trie: {:?}", err))?;
+    let children = match trie {
+        Trie::Leaf { .. } => vec![],
+        Trie::Node { pointer_block } => pointer_block
+            .as_indexed_pointers()
+            .map(|(_index, ptr)| *ptr.hash())
+            .collect(),
+        Trie::Extension { affix: _, pointer } => vec![*pointer.hash()],
+    };
+    Ok(children)
+}
+
+/// Returns whether the destination holds the trie with the given key, as
+/// seen by `txn`.
+fn destination_has_trie(
+    txn: &RoTransaction<'_>,
+    db: Database,
+    trie_key: &Digest,
+) -> Result<bool, anyhow::Error> {
+    let trie_key_bytes = trie_key
+        .to_bytes()
+        .map_err(|err| anyhow::anyhow!("couldn't serialize trie key: {:?}", err))?;
+    Ok(txn.read(db, &trie_key_bytes)?.is_some())
+}
+
+/// Hands a trie whose children were all handed over to the writer, then
+/// does the same for its parents whose last unsent child it was.
+///
+/// A trie is sent before it's recorded as such, so a parent either waits
+/// for it or is sent after it. The writer therefore always receives a
+/// parent after all its children.
+fn send_trie(
+    trie: Arc<ReadTrie>,
+    claimed: &Mutex<ClaimedTries>,
+    sender: &SyncSender<CopiedTrie>,
+) -> Result<(), anyhow::Error> {
+    let mut ready = vec![trie];
+    while let Some(trie) = ready.pop() {
+        let (key_bytes, value_bytes) = trie
+            .bytes
+            .lock()
+            .expect("read trie lock poisoned")
+            .take()
+            .expect("trie should be sent once");
+        sender
+            .send(CopiedTrie {
+                state_root: trie.state_root,
+                root_indices: trie.root_indices.clone(),
+                key_bytes,
+                value_bytes,
+            })
+            .map_err(|_| anyhow::anyhow!("trie writer stopped"))?;
+        let parents = {
+            let mut claimed = claimed.lock().expect("claimed tries lock poisoned");
+            claimed.sent.insert(trie.trie_key);
+            claimed
+                .unsent
+                .remove(&trie.trie_key)
+                .expect("sent trie should be claimed")
+        };
+        ready.extend(
+            parents
+                .into_iter()
+                .filter(|parent| parent.unsent_count.fetch_sub(1, Ordering::AcqRel) == 1),
+        );
+    }
+    Ok(())
+}
+
+/// Transactions and databases a reader thread reads the tries through.
+struct ReaderTxns<'a> {
+    source_txn: RoTransaction<'a>,
+    source_db: Database,
+    destination: &'a EngineState<LmdbGlobalState>,
+    destination_txn: RoTransaction<'a>,
+    destination_db: Database,
+    /// Number of tries read since `destination_txn` began.
+    reads_in_destination_txn: usize,
+}
+
+impl<'a> ReaderTxns<'a> {
+    fn new(
+        source: &'a EngineState<LmdbGlobalState>,
+        destination: &'a EngineState<LmdbGlobalState>,
+    ) -> Result<Self, LmdbError> {
+        Ok(Self {
+            source_txn: source.get_state().environment().create_read_txn()?,
+            source_db: source.get_state().trie_store().get_db(),
+            destination,
+            destination_txn: destination.get_state().environment().create_read_txn()?,
+            destination_db: destination.get_state().trie_store().get_db(),
+            reads_in_destination_txn: 0,
+        })
+    }
+
+    /// Begins a new destination transaction every
+    /// `READS_PER_DESTINATION_TXN` reads, so that the tries committed in
+    /// the meantime are seen and the destination's old pages are released.
+    fn renew_destination_txn(&mut self) -> Result<(), LmdbError> {
+        self.reads_in_destination_txn += 1;
+        if self.reads_in_destination_txn >= READS_PER_DESTINATION_TXN {
+            self.destination_txn = self
+                .destination
+                .get_state()
+                .environment()
+                .create_read_txn()?;
+            self.reads_in_destination_txn = 0;
+        }
+        Ok(())
+    }
+}
+
+/// Reads a trie node from the source and returns its children missing from
+/// the destination which weren't claimed yet. If the trie has no unsent
+/// children, it's handed to the writer.
+fn read_pending_trie(
+    trie: Arc<ReadTrie>,
+    txns: &mut ReaderTxns,
+    claimed: &Mutex<ClaimedTries>,
+    sender: &SyncSender<CopiedTrie>,
+) -> Result<Vec<Arc<ReadTrie>>, anyhow::Error> {
+    txns.renew_destination_txn()?;
+    let key_bytes = trie
+        .trie_key
+        .to_bytes()
+        .map_err(|err| anyhow::anyhow!("couldn't serialize trie key: {:?}", err))?;
+    let value_bytes = txns
+        .source_txn
+        .read(txns.source_db, &key_bytes)?
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "error migrating state root {} {}, ",
+                trie.state_root,
+                trie.trie_key
+            )
+        })?
+        .to_vec();
+    let children = trie_children(&value_bytes)?;
+    *trie.bytes.lock().expect("read trie lock poisoned") = Some((key_bytes, value_bytes));
+
+    let mut claimed_children = vec![];
+    for child in children {
+        if destination_has_trie(&txns.destination_txn, txns.destination_db, &child)? {
+            continue;
+        }
+        claimed_children.extend(
+            claimed
+                .lock()
+                .expect("claimed tries lock poisoned")
+                .claim_child(&trie, child),
+        );
+    }
+    // The trie is read, only its unsent children may hold it back.
+    if trie.unsent_count.fetch_sub(1, Ordering::AcqRel) == 1 {
+        send_trie(trie, claimed, sender)?;
+    }
+    Ok(claimed_children)
+}
+
+/// Takes pending tries from the queue until every reachable node was read
+/// or another thread failed.
+fn read_tries(
+    queue: &Mutex<TrieQueue>,
+    queue_update: &Condvar,
+    claimed: &Mutex<ClaimedTries>,
+    source: &EngineState<LmdbGlobalState>,
+    destination: &EngineState<LmdbGlobalState>,
+    sender: SyncSender<CopiedTrie>,
+) -> Result<(), Error> {
+    let mut txns = match ReaderTxns::new(source, destination) {
+        Ok(txns) => txns,
+        Err(lmdb_err) => {
+            queue.lock().expect("trie queue lock poisoned").failed = true;
+            queue_update.notify_all();
+            return Err(Error::LmdbOperation(lmdb_err));
+        }
+    };
+    loop {
+        let trie = {
+            let mut queue = queue.lock().expect("trie queue lock poisoned");
+            loop {
+                if queue.failed {
+                    return Ok(());
+                }
+                if let Some(trie) = queue.pending.pop() {
+                    queue.in_progress += 1;
+                    break trie;
+                }
+                if queue.in_progress == 0 {
+                    return Ok(());
+                }
+                queue = queue_update.wait(queue).expect("trie queue lock poisoned");
+            }
+        };
+        let state_root = trie.state_root;
+        let result = read_pending_trie(trie, &mut txns, claimed, &sender);
+        let mut queue = queue.lock().expect("trie queue lock poisoned");
+        queue.in_progress -= 1;
+        queue_update.notify_all();
+        match result {
+            Ok(claimed_children) => queue.pending.extend(claimed_children),
+            Err(err) => {
+                queue.failed = true;
+                return Err(Error::CopyStateRoot(state_root, err));
+            }
+        }
+    }
+}
+
+/// Writes the tries read from the source to the destination until all the
+/// readers are done.
+///
+/// Once a transaction holding state roots is committed, the destination is
+/// flushed and `on_roots_copied` is called with their indices.
+fn write_tries(
+    destination: &EngineState<LmdbGlobalState>,
+    receiver: Receiver<CopiedTrie>,
+    limits: BatchLimits,
+    on_roots_copied: &mut RootsCopiedFn,
+) -> Result<u64, Error> {
+    let destination_store = destination.get_state().trie_store();
+    let mut total_tries: u64 = 0;
+    let mut total_bytes: u64 = 0;
+    let mut batch_tries: usize = 0;
+    let mut batch_bytes: usize = 0;
+    let mut batch_roots = vec![];
+    let mut heartbeat_interval = Instant::now();
+    let begin_write_txn = || {
+        destination
+            .get_state()
+            .environment()
+            .create_read_write_txn()
+            .map_err(Error::LmdbOperation)
+    };
+    let mut commit = |write_txn: RwTransaction, batch_roots: &mut Vec<usize>| {
+        write_txn.commit().map_err(Error::LmdbOperation)?;
+        if !batch_roots.is_empty() {
+            destination
+                .flush_environment()
+                .map_err(Error::LmdbOperation)?;
+            on_roots_copied(batch_roots)?;
+            batch_roots.clear();
+        }
+        Ok::<_, Error>(())
+    };
+    let mut write_txn = begin_write_txn()?;
+    for copied in receiver {
+        write_txn
+            .write(
+                destination_store.get_db(),
+                &copied.key_bytes,
+                &copied.value_bytes,
+            )
+            .map_err(|err| Error::CopyStateRoot(copied.state_root, err.into()))?;
+        batch_roots.extend(copied.root_indices);
+        let written_bytes = copied.key_bytes.len() + copied.value_bytes.len();
+        total_bytes += written_bytes as u64;
+        total_tries += 1;
+        batch_tries += 1;
+        batch_bytes += written_bytes;
+        if batch_tries >= limits.max_tries || batch_bytes >= limits.max_bytes {
+            commit(write_txn, &mut batch_roots)?;
+            write_txn = begin_write_txn()?;
+            batch_tries = 0;
+            batch_bytes = 0;
+        }
+        // For user feedback, update on progress if this takes longer than 10 seconds.
+        if heartbeat_interval.elapsed().as_secs() > 10 {
+            info!(
+                "trie migration progress: bytes copied {}, tries copied {}",
+                total_bytes, total_tries,
+            );
+            heartbeat_interval = Instant::now();
+        }
+    }
+    commit(write_txn, &mut batch_roots)?;
+    Ok(total_tries)
+}
+
+/// Copies the given state roots and all their descendants missing from the
+/// destination using `thread_count` threads reading from the source and a
+/// single thread writing to the destination in transactions bounded by
+/// `limits`. Returns the number of tries written.
+///
+/// As with [`copy_state_root_batched`], children are written before their
+/// parent, so the tries found in the destination are skipped along with
+/// their descendants. `on_roots_copied` is called with the indices of the
+/// state roots as they are fully copied, in no particular order, including
+/// the ones already in the destination.
+///
+/// Each trie is read once, even when reached from several state roots. The
+/// tries handed to the writer are remembered in a [`CopiedTries`] capped to
+/// roughly `max_cache_bytes`, as they may not be committed yet. Forgotten
+/// ones are read again only if the reader doesn't see them in the
+/// destination yet.
+///
+/// The destination ends up with the same contents as when copying the state
+/// roots one by one with [`copy_state_root`].
+pub fn copy_state_roots_parallel(
+    state_roots: &[Digest],
+    source: &EngineState<LmdbGlobalState>,
+    destination: &EngineState<LmdbGlobalState>,
+    thread_count: usize,
+    limits: BatchLimits,
+    max_cache_bytes: usize,
+    on_roots_copied: &mut RootsCopiedFn,
+) -> Result<u64, Error> {
+    let start_time = Instant::now();
+    let mut root_indices: HashMap<Digest, Vec<usize>> = HashMap::new();
+    let mut copied_roots = vec![];
+    {
+        let txn = destination
+            .get_state()
+            .environment()
+            .create_read_txn()
+            .map_err(Error::LmdbOperation)?;
+        let db = destination.get_state().trie_store().get_db();
+        for (root_index, state_root) in state_roots.iter().enumerate() {
+            if destination_has_trie(&txn, db, state_root)
+                .map_err(|err| Error::CopyStateRoot(*state_root, err))?
+            {
+                copied_roots.push(root_index);
+            } else {
+                root_indices
+                    .entry(*state_root)
+                    .or_default()
+                    .push(root_index);
+            }
+        }
+        txn.commit().map_err(Error::LmdbOperation)?;
+    }
+    if !copied_roots.is_empty() {
+        on_roots_copied(&copied_roots)?;
+    }
+    let mut queue = TrieQueue::default();
+    let mut claimed = ClaimedTries {
+        unsent: HashMap::new(),
+        sent: CopiedTries::new(max_cache_bytes),
+    };
+    for state_root in state_roots {
+        if let Some(indices) = root_indices.remove(state_root) {
+            claimed.unsent.insert(*state_root, vec![]);
+            queue
+                .pending
+                .push(Arc::new(ReadTrie::new(*state_root, indices, *state_root)));
+        }
+    }
+    // The first state roots are popped first.
+    queue.pending.reverse();
+    let queue = Mutex::new(queue);
+    let queue_update = Condvar::new();
+    let claimed = Mutex::new(claimed);
+    let (sender, receiver) = mpsc::sync_channel(thread_count * COPIED_TRIES_PER_THREAD);
+
+    let (total_tries, reader_results) = thread::scope(|scope| {
+        let reader_handles: Vec<_> = (0..thread_count)
+            .map(|_| {
+                let sender = sender.clone();
+                scope.spawn(|| {
+                    read_tries(&queue, &queue_update, &claimed, source, destination, sender)
+                })
+            })
+            .collect();
+        // The writer stops once all the readers dropped their sender.
+        drop(sender);
+        let total_tries = write_tries(destination, receiver, limits, on_roots_copied);
+        let reader_results: Vec<_> = reader_handles
+            .into_iter()
+            .map(|handle| handle.join().expect("trie reader thread panicked"))
+            .collect();
+        (total_tries, reader_results)
+    });
+    // A failed writer makes the readers fail as well, report its error
+    // first.
+    let total_tries = total_tries?;
+    reader_results.into_iter().collect::<Result<(), Error>>()?;
+
+    info!(
+        "Trie migration complete\nTotal tries: {}\nMigration duration (us): {}",
+        total_tries,
+        start_time.elapsed().as_micros(),
+    );
+    Ok(total_tries)
+}
+
+/// Set of the keys of the tries reachable from some state roots, backed by
+/// a temporary LMDB file so that it doesn't have to fit in memory.
+pub(crate) struct ReachableTries {
+    path: PathBuf,
+    env: Environment,
+    db: Database,
+}
+
+impl ReachableTries {
+    /// Creates an empty set in a new directory under `scratch_dir`, removed
+    /// once dropped.
+    ///
+    /// The set may grow as large as the trie store it's filled from, so
+    /// `scratch_dir` should be on a disk rather than in memory.
+    pub(crate) fn new<P: AsRef<Path>>(
+        scratch_dir: P,
+        max_db_size: usize,
+    ) -> Result<Self, anyhow::Error> {
+        let path = scratch_dir.as_ref().join(format!(
+            "{}{}_{}",
+            SCRATCH_DIR_PREFIX,
+            process::id(),
+            SCRATCH_SET_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&path).map_err(|io_err| {
+            anyhow::anyhow!("couldn't create directory {}: {}", path.display(), io_err)
+        })?;
+        let open_set = || {
+            let env = Environment::new()
+                .set_flags(
+                    EnvironmentFlags::NO_TLS
+                        | EnvironmentFlags::NO_SYNC
+                        | EnvironmentFlags::NO_META_SYNC,
+                )
+                .set_map_size(max_db_size)
+                .open(&path)?;
+            let db = env.create_db(None, DatabaseFlags::empty())?;
+            Ok::<_, LmdbError>((env, db))
+        };
+        let (env, db) = match open_set() {
+            Ok(set) => set,
+            Err(lmdb_err) => {
+                let _ = fs::remove_dir_all(&path);
+                return Err(lmdb_err.into());
+            }
+        };
+        Ok(Self { path, env, db })
+    }
+
+    /// Adds the tries reachable from `state_root` in the trie store
+    /// `source_db` opened in `source_env` to the set. Returns the number of tries added, along with the total size of their
+    /// keys and values.
+    pub(crate) fn mark_state_root(
+        &self,
+        state_root: Digest,
+        source_env: &Environment,
+        source_db: Database,
+    ) -> Result<(usize, u64), anyhow::Error> {
+        self.mark_tries(state_root, source_env, source_db, |trie_key| {
+            Err(anyhow::anyhow!("missing trie key {}", trie_key))
+        })
+    }
+
+    /// Same as [`Self::mark_state_root`], passing the tries missing from the
+    /// source to `on_missing`. They are skipped along with their descendants
+    /// unless it fails.
+    pub(crate) fn mark_tries<F>(
+        &self,
+        state_root: Digest,
+        source_env: &Environment,
+        source_db: Database,
+        mut on_missing: F,
+    ) -> Result<(usize, u64), anyhow::Error>
+    where
+        F: FnMut(Digest) -> Result<(), anyhow::Error>,
+    {
+        let source_txn = source_env.begin_ro_txn()?;
+        let mut txn = self.env.begin_rw_txn()?;
+        let mut pending_writes = 0;
+        let mut marked_tries = 0;
+        let mut marked_bytes = 0;
+        let mut trie_keys = vec![state_root];
+        while let Some(trie_key) = trie_keys.pop() {
+            let trie_key_bytes = trie_key
+                .to_bytes()
+                .map_err(|err| anyhow::anyhow!("couldn't serialize trie key: {:?}", err))?;
+            if self.contains(&txn, &trie_key_bytes)? {
+                continue;
+            }
+            let value_bytes = match source_txn.get(source_db, &trie_key_bytes) {
+                Ok(value_bytes) => value_bytes,
+                Err(LmdbError::NotFound) => {
+                    on_missing(trie_key)?;
+                    continue;
+                }
+                Err(lmdb_err) => return Err(lmdb_err.into()),
+            };
+            txn.put(self.db, &trie_key_bytes, &[], WriteFlags::empty())?;
+            marked_tries += 1;
+            marked_bytes += (trie_key_bytes.len() + value_bytes.len()) as u64;
+            trie_keys.extend(trie_children(value_bytes)?);
+
+            pending_writes += 1;
+            if pending_writes == REACHABLE_TRIES_PER_TXN {
+                txn.commit()?;
+                txn = self.env.begin_rw_txn()?;
+                pending_writes = 0;
+            }
+        }
+        txn.commit()?;
+        source_txn.commit()?;
+        Ok((marked_tries, marked_bytes))
+    }
+
+    /// Adds the tries under `state_root` whose whole subtree is in the
+    /// source to the set. Returns the key of the first trie found missing
+    /// from the source, if any.
+    ///
+    /// A trie is only added once every trie below it was found, so a set
+    /// only filled by this function holds complete subtrees, which don't
+    /// have to be walked again for the next state roots.
+    pub(crate) fn mark_complete_tries(
+        &self,
+        state_root: Digest,
+        source: &EngineState<LmdbGlobalState>,
+    ) -> Result<Option<Digest>, anyhow::Error> {
+        let source_txn = source.get_state().environment().create_read_txn()?;
+        let source_db = source.get_state().trie_store().get_db();
+        let mut txn = self.env.begin_rw_txn()?;
+        let mut pending_writes = 0;
+        let mut missing_trie = None;
+        // Each trie is pushed a second time, below its children, to be
+        // marked once they're all complete.
+        let mut trie_keys = vec![(state_root, false)];
+        while let Some((trie_key, children_complete)) = trie_keys.pop() {
+            let trie_key_bytes = trie_key
+                .to_bytes()
+                .map_err(|err| anyhow::anyhow!("couldn't serialize trie key: {:?}", err))?;
+            if children_complete {
+                txn.put(self.db, &trie_key_bytes, &[], WriteFlags::empty())?;
+                pending_writes += 1;
+                if pending_writes == REACHABLE_TRIES_PER_TXN {
+                    txn.commit()?;
+                    txn = self.env.begin_rw_txn()?;
+                    pending_writes = 0;
+                }
+                continue;
+            }
+            if self.contains(&txn, &trie_key_bytes)? {
+                continue;
+            }
+            match source_txn.read(source_db, &trie_key_bytes)? {
+                Some(value_bytes) => {
+                    trie_keys.push((trie_key, true));
+                    trie_keys.extend(
+                        trie_children(&value_bytes)?
+                            .into_iter()
+                            .map(|child| (child, false)),
+                    );
+                }
+                None => {
+                    missing_trie = Some(trie_key);
+                    break;
+                }
+            }
+        }
+        txn.commit()?;
+        source_txn.commit()?;
+        Ok(missing_trie)
+    }
+
+    /// Begins a transaction to look up tries in the set.
+    pub(crate) fn begin_ro_txn(&self) -> Result<RoTransaction<'_>, LmdbError> {
+        self.env.begin_ro_txn()
+    }
+
+    /// Returns whether the serialized trie key is in the set.
+    pub(crate) fn contains<T: Transaction>(
+        &self,
+        txn: &T,
+        trie_key_bytes: &[u8],
+    ) -> Result<bool, LmdbError> {
+        match txn.get(self.db, &trie_key_bytes) {
+            Ok(_) => Ok(true),
+            Err(LmdbError::NotFound) => Ok(false),
+            Err(lmdb_err) => Err(lmdb_err),
+        }
+    }
+}
+
+impl Drop for ReachableTries {
+    fn drop(&mut self) {
+        if let Err(io_err) = fs::remove_dir_all(&self.path) {
+            warn!(
+                "Couldn't remove scratch directory {}: {io_err}",
+                self.path.display()
+            );
+        }
+    }
+}
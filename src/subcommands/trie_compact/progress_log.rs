@@ -0,0 +1,115 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Error as IoError, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use casper_hashing::Digest;
+
+use super::Error;
+
+/// Name of the file in the destination directory recording the progress of
+/// the compaction.
+pub(crate) const PROGRESS_LOG_FILE_NAME: &str = "compact_trie_progress.log";
+
+/// Record of a state root fully copied to the destination.
+#[derive(Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub(crate) struct ProgressEntry {
+    /// Height of the highest block with this state root, unless the state
+    /// roots were given as a list.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) block_height: Option<u64>,
+    pub(crate) state_root: Digest,
+}
+
+/// Append-only log of the copied state roots, one JSON object per line.
+pub(crate) struct ProgressLog {
+    path: PathBuf,
+    writer: BufWriter<File>,
+}
+
+impl ProgressLog {
+    /// Opens the progress log at `path`, creating it if it doesn't exist.
+    /// Unless `append` is set, previously recorded progress is discarded.
+    pub(crate) fn open<P: AsRef<Path>>(path: P, append: bool) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .write(true)
+            .append(append)
+            .truncate(!append)
+            .open(&path)
+            .map_err(|io_err| Error::ProgressLog(path.clone(), io_err))?;
+        // Drop the last entry if it was cut short, new entries go after the
+        // last complete one.
+        if append {
+            truncate_incomplete_entry(&file)
+                .map_err(|io_err| Error::ProgressLog(path.clone(), io_err))?;
+        }
+        Ok(Self {
+            path,
+            writer: BufWriter::new(file),
+        })
+    }
+
+    /// Appends the entries to the log and makes sure they reach the disk.
+    pub(crate) fn append(&mut self, entries: &[ProgressEntry]) -> Result<(), Error> {
+        for (idx, entry) in entries.iter().enumerate() {
+            serde_json::to_writer(&mut self.writer, entry)
+                .map_err(|json_err| Error::ProgressLogEntry(idx, json_err))?;
+            self.writer
+                .write_all(b"\n")
+                .map_err(|io_err| Error::ProgressLog(self.path.clone(), io_err))?;
+        }
+        self.writer
+            .flush()
+            .and_then(|_| self.writer.get_ref().sync_data())
+            .map_err(|io_err| Error::ProgressLog(self.path.clone(), io_err))
+    }
+}
+
+/// Truncates the file right after its last newline.
+fn truncate_incomplete_entry(mut file: &File) -> Result<(), IoError> {
+    let mut contents = vec![];
+    file.seek(SeekFrom::Start(0))?;
+    file.read_to_end(&mut contents)?;
+    let complete_len = contents
+        .iter()
+        .rposition(|byte| *byte == b'\n')
+        .map_or(0, |newline_pos| newline_pos + 1);
+    if complete_len < contents.len() {
+        file.set_len(complete_len as u64)?;
+    }
+    Ok(())
+}
+
+/// Reads all the entries recorded in the progress log at `path`.
+///
+/// A last line which fails to parse is assumed to have been cut short when
+/// the compaction was interrupted, and is ignored.
+pub(crate) fn read_progress_log<P: AsRef<Path>>(path: P) -> Result<Vec<ProgressEntry>, Error> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|io_err| Error::ProgressLog(path.to_path_buf(), io_err))?;
+    let lines = BufReader::new(file)
+        .lines()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|io_err| Error::ProgressLog(path.to_path_buf(), io_err))?;
+    let mut entries = vec![];
+    for (idx, line) in lines.iter().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(line) {
+            Ok(entry) => entries.push(entry),
+            Err(json_err) if idx + 1 == lines.len() => {
+                warn!("Ignoring incomplete last entry of the progress log: {json_err}");
+            }
+            Err(json_err) => return Err(Error::ProgressLogEntry(idx, json_err)),
+        }
+    }
+    Ok(entries)
+}
@@ -1,27 +1,49 @@
-use std::fs::{self, File};
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    path::Path,
+};
 
-use lmdb::DatabaseFlags;
+use lmdb::{Cursor, DatabaseFlags};
 use once_cell::sync::Lazy;
 use tempfile::{tempdir, TempDir};
 
-use casper_execution_engine::storage::{
-    store::StoreExt,
-    transaction_source::{lmdb::LmdbEnvironment, Transaction, TransactionSource},
-    trie::{Pointer, PointerBlock, Trie},
-    trie_store::lmdb::LmdbTrieStore,
+use casper_execution_engine::{
+    shared::newtypes::CorrelationId,
+    storage::{
+        global_state::StateProvider,
+        store::StoreExt,
+        transaction_source::{lmdb::LmdbEnvironment, Transaction, TransactionSource},
+        trie::{Pointer, PointerBlock, Trie},
+        trie_store::lmdb::LmdbTrieStore,
+    },
 };
 use casper_hashing::Digest;
-use casper_node::storage::Storage;
-use casper_types::bytesrepr::{Bytes, ToBytes};
+use casper_node::{
+    storage::Storage,
+    types::{BlockBody, BlockHash, BlockHeader, HashingAlgorithmVersion},
+};
+use casper_types::{
+    bytesrepr::{Bytes, ToBytes},
+    EraId, Key, StoredValue,
+};
 
-static DEFAULT_MAX_DB_SIZE: Lazy<usize> = Lazy::new(|| super::DEFAULT_MAX_DB_SIZE.parse().unwrap());
+pub(crate) static DEFAULT_MAX_DB_SIZE: Lazy<usize> =
+    Lazy::new(|| super::DEFAULT_MAX_DB_SIZE.parse().unwrap());
 
-use crate::common::db::TRIE_STORE_FILE_NAME;
+use crate::{
+    common::db::{self, BlockBodyDatabase, BlockHeaderDatabase, Database, TRIE_STORE_FILE_NAME},
+    subcommands::execution_results_summary::block_body::BlockBody as MockBlockBody,
+    test_utils::MockBlockHeader,
+};
 
 use super::{
-    compact::{self, DestinationOptions},
+    compact::{self, BlockSelection, CompactOptions, DestinationOptions, StateRootSource},
+    estimate,
+    helpers::{BatchLimits, CopiedTries, COPIED_TRIE_BYTES, DEFAULT_MAX_CACHE_BYTES},
+    progress_log::{self, ProgressEntry, ProgressLog, PROGRESS_LOG_FILE_NAME},
     utils::{create_execution_engine, create_storage, load_execution_engine},
-    Error,
+    verify, Error,
 };
 
 #[derive(Clone, Debug, PartialEq)]
@@ -93,7 +115,7 @@ pub(crate) fn create_data() -> Vec<TestData<Bytes, Bytes>> {
     ]
 }
 
-fn create_test_trie_store() -> (TempDir, Vec<TestData<Bytes, Bytes>>) {
+pub(crate) fn create_test_trie_store() -> (TempDir, Vec<TestData<Bytes, Bytes>>) {
     let tmp_dir = tempdir().unwrap();
     let env = LmdbEnvironment::new(tmp_dir.path(), *DEFAULT_MAX_DB_SIZE, 512, true).unwrap();
     let store = LmdbTrieStore::new(&env, None, DatabaseFlags::empty()).unwrap();
@@ -110,12 +132,104 @@ fn create_test_trie_store() -> (TempDir, Vec<TestData<Bytes, Bytes>>) {
     (tmp_dir, data)
 }
 
-fn create_empty_test_storage() -> (TempDir, Storage) {
+/// Returns all the entries of the trie store in the given directory.
+pub(crate) fn trie_store_entries<P: AsRef<Path>>(path: P) -> Vec<(Vec<u8>, Vec<u8>)> {
+    let env = LmdbEnvironment::new(path, *DEFAULT_MAX_DB_SIZE, 512, true).unwrap();
+    let store = LmdbTrieStore::open(&env, None).unwrap();
+    let txn = env.create_read_txn().unwrap();
+    let entries = lmdb::Transaction::open_ro_cursor(&txn, store.get_db())
+        .unwrap()
+        .iter()
+        .map(|(key, value)| (key.to_vec(), value.to_vec()))
+        .collect();
+    txn.commit().unwrap();
+    entries
+}
+
+/// Creates a storage with a chain of blocks having the given state roots,
+/// starting from height 0.
+pub(crate) fn create_test_storage(state_roots: &[Digest]) -> TempDir {
+    let blocks: Vec<(Digest, u64)> = state_roots
+        .iter()
+        .map(|state_root| (*state_root, 0))
+        .collect();
+    create_test_storage_with_eras(&blocks)
+}
+
+/// Creates a storage with a block for each state root and era id pair,
+/// starting at height 0.
+fn create_test_storage_with_eras(blocks: &[(Digest, u64)]) -> TempDir {
+    let (tmp_dir, storage) = create_empty_test_storage();
+    // The storage must be closed before writing to its database directly.
+    drop(storage);
+
+    let env = db::db_env(tmp_dir.path().join("casper/storage.lmdb")).unwrap();
+    let header_db = env.open_db(Some(BlockHeaderDatabase::db_name())).unwrap();
+    let body_db = env.open_db(Some(BlockBodyDatabase::db_name())).unwrap();
+    let body: BlockBody =
+        bincode::deserialize(&bincode::serialize(&MockBlockBody::new(vec![])).unwrap()).unwrap();
+    let body_hash = body.hash(HashingAlgorithmVersion::V1);
+    let mut txn = env.begin_rw_txn().unwrap();
+    lmdb::Transaction::commit({
+        txn.put(
+            body_db,
+            &body_hash,
+            &bincode::serialize(&body).unwrap(),
+            lmdb::WriteFlags::empty(),
+        )
+        .unwrap();
+        let mut parent_hash = BlockHash::default();
+        for (height, (state_root, era_id)) in blocks.iter().enumerate() {
+            let mock_header = MockBlockHeader {
+                parent_hash,
+                state_root_hash: *state_root,
+                body_hash,
+                era_id: EraId::new(*era_id),
+                height: height as u64,
+                ..Default::default()
+            };
+            let header: BlockHeader =
+                bincode::deserialize(&bincode::serialize(&mock_header).unwrap()).unwrap();
+            parent_hash = header.hash();
+            txn.put(
+                header_db,
+                &parent_hash,
+                &bincode::serialize(&header).unwrap(),
+                lmdb::WriteFlags::empty(),
+            )
+            .unwrap();
+        }
+        txn
+    })
+    .unwrap();
+    tmp_dir
+}
+
+pub(crate) fn create_empty_test_storage() -> (TempDir, Storage) {
     let tmp_dir = tempdir().unwrap();
     let storage = create_storage(tmp_dir.as_ref()).unwrap();
     (tmp_dir, storage)
 }
 
+/// Creates a trie store holding a global state with the given entries and
+/// returns its state root hash.
+pub(crate) fn create_test_global_state(
+    stored_values: HashMap<Key, StoredValue>,
+) -> (TempDir, Digest) {
+    let tmp_dir = tempdir().unwrap();
+    let (engine_state, _env) =
+        create_execution_engine(tmp_dir.path(), *DEFAULT_MAX_DB_SIZE, true).unwrap();
+    let state_root_hash = engine_state
+        .get_state()
+        .put_stored_values(
+            CorrelationId::new(),
+            engine_state.get_state().empty_root(),
+            stored_values,
+        )
+        .unwrap();
+    (tmp_dir, state_root_hash)
+}
+
 #[test]
 fn copy_state_root_roundtrip() {
     let src_tmp_dir = tempdir().unwrap();
@@ -163,7 +277,7 @@ fn copy_state_root_roundtrip() {
                     // Hashes should be equal.
                     assert_eq!(
                         trie_in_data.unwrap().0,
-                        Digest::hash(&trie.to_bytes().unwrap())
+                        Digest::hash(trie.to_bytes().unwrap())
                     );
                 }
                 None => panic!(),
@@ -252,11 +366,12 @@ fn check_no_extra_tries() {
 #[test]
 fn missing_source_trie() {
     match compact::trie_compact(
-        "",
+        &StateRootSource::storage("", BlockSelection::All),
         "bogus_path",
         "",
         DestinationOptions::New,
         *DEFAULT_MAX_DB_SIZE,
+        CompactOptions::default(),
     ) {
         Err(Error::InvalidPath(..)) => {}
         Err(err) => panic!("Unexpected error: {err}"),
@@ -269,11 +384,12 @@ fn missing_storage() {
     let (src_dir, _) = create_test_trie_store();
     let dst_dir = tempdir().unwrap();
     match compact::trie_compact(
-        "bogus_path",
+        &StateRootSource::storage("bogus_path", BlockSelection::All),
         src_dir,
         dst_dir,
         DestinationOptions::New,
         *DEFAULT_MAX_DB_SIZE,
+        CompactOptions::default(),
     ) {
         Err(Error::OpenStorage(_)) => {}
         Err(err) => panic!("Unexpected error: {err}"),
@@ -287,11 +403,12 @@ fn valid_empty_dst_with_destination_options() {
     let dst_dir = tempdir().unwrap();
     let (storage_dir, _store) = create_empty_test_storage();
     match compact::trie_compact(
-        &storage_dir,
+        &StateRootSource::storage(&storage_dir, BlockSelection::All),
         &src_dir,
         &dst_dir,
         DestinationOptions::New,
         *DEFAULT_MAX_DB_SIZE,
+        CompactOptions::default(),
     ) {
         Ok(_) => {}
         Err(err) => panic!("Unexpected error: {err}"),
@@ -299,11 +416,12 @@ fn valid_empty_dst_with_destination_options() {
     fs::remove_file(dst_dir.path().join(TRIE_STORE_FILE_NAME)).unwrap();
 
     match compact::trie_compact(
-        &storage_dir,
+        &StateRootSource::storage(&storage_dir, BlockSelection::All),
         &src_dir,
         &dst_dir,
         DestinationOptions::Append,
         *DEFAULT_MAX_DB_SIZE,
+        CompactOptions::default(),
     ) {
         Err(Error::InvalidDest(_)) => {}
         Err(err) => panic!("Unexpected error: {err}"),
@@ -311,11 +429,12 @@ fn valid_empty_dst_with_destination_options() {
     }
 
     match compact::trie_compact(
-        &storage_dir,
+        &StateRootSource::storage(&storage_dir, BlockSelection::All),
         &src_dir,
         &dst_dir,
         DestinationOptions::Overwrite,
         *DEFAULT_MAX_DB_SIZE,
+        CompactOptions::default(),
     ) {
         Err(Error::InvalidDest(_)) => {}
         Err(err) => panic!("Unexpected error: {err}"),
@@ -334,11 +453,12 @@ fn valid_existing_dst_with_destination_options() {
 
     let (storage_dir, _store) = create_empty_test_storage();
     match compact::trie_compact(
-        &storage_dir,
+        &StateRootSource::storage(&storage_dir, BlockSelection::All),
         &src_dir,
         &dst_dir,
         DestinationOptions::New,
         *DEFAULT_MAX_DB_SIZE,
+        CompactOptions::default(),
     ) {
         Err(Error::InvalidDest(_)) => {}
         Err(err) => panic!("Unexpected error: {err}"),
@@ -346,11 +466,12 @@ fn valid_existing_dst_with_destination_options() {
     }
 
     match compact::trie_compact(
-        &storage_dir,
+        &StateRootSource::storage(&storage_dir, BlockSelection::All),
         &src_dir,
         &dst_dir,
         DestinationOptions::Append,
         *DEFAULT_MAX_DB_SIZE,
+        CompactOptions::default(),
     ) {
         Ok(_) => {}
         Err(err) => panic!("Unexpected error: {err}"),
@@ -358,11 +479,12 @@ fn valid_existing_dst_with_destination_options() {
 
     assert!(dst_dir.path().join(TRIE_STORE_FILE_NAME).exists());
     match compact::trie_compact(
-        &storage_dir,
+        &StateRootSource::storage(&storage_dir, BlockSelection::All),
         &src_dir,
         &dst_dir,
         DestinationOptions::Overwrite,
         *DEFAULT_MAX_DB_SIZE,
+        CompactOptions::default(),
     ) {
         Ok(_) => {}
         Err(err) => panic!("Unexpected error: {err}"),
@@ -379,11 +501,12 @@ fn missing_dst_with_destination_options() {
     let dst_dir = root_dst_dir.path().join("extra_dir");
     let (storage_dir, _store) = create_empty_test_storage();
     match compact::trie_compact(
-        &storage_dir,
+        &StateRootSource::storage(&storage_dir, BlockSelection::All),
         &src_dir,
         &dst_dir,
         DestinationOptions::New,
         *DEFAULT_MAX_DB_SIZE,
+        CompactOptions::default(),
     ) {
         Ok(_) => {}
         Err(err) => panic!("Unexpected error: {err}"),
@@ -391,11 +514,12 @@ fn missing_dst_with_destination_options() {
     fs::remove_dir_all(dst_dir.as_path()).unwrap();
 
     match compact::trie_compact(
-        &storage_dir,
+        &StateRootSource::storage(&storage_dir, BlockSelection::All),
         &src_dir,
         &dst_dir,
         DestinationOptions::Append,
         *DEFAULT_MAX_DB_SIZE,
+        CompactOptions::default(),
     ) {
         Err(Error::InvalidDest(_)) => {}
         Err(err) => panic!("Unexpected error: {err}"),
@@ -403,14 +527,737 @@ fn missing_dst_with_destination_options() {
     }
 
     match compact::trie_compact(
-        &storage_dir,
+        &StateRootSource::storage(&storage_dir, BlockSelection::All),
         &src_dir,
         &dst_dir,
         DestinationOptions::Overwrite,
         *DEFAULT_MAX_DB_SIZE,
+        CompactOptions::default(),
     ) {
         Err(Error::InvalidDest(_)) => {}
         Err(err) => panic!("Unexpected error: {err}"),
         Ok(_) => panic!("Unexpected successful trie compact"),
     }
 }
+
+#[test]
+fn parallel_copy_should_match_serial_copy() {
+    let (src_dir, data) = create_test_trie_store();
+    let serial_dst_dir = tempdir().unwrap();
+    let parallel_dst_dir = tempdir().unwrap();
+    let (source_state, _env) =
+        load_execution_engine(&src_dir, *DEFAULT_MAX_DB_SIZE, Digest::default(), true).unwrap();
+
+    // `node2` is also a descendant of `node1`.
+    let state_roots = [data[4].0, data[3].0];
+    {
+        let (destination_state, _env) =
+            create_execution_engine(&serial_dst_dir, *DEFAULT_MAX_DB_SIZE, true).unwrap();
+        for state_root in state_roots {
+            super::helpers::copy_state_root(state_root, &source_state, &destination_state).unwrap();
+        }
+    }
+    {
+        let (destination_state, _env) =
+            create_execution_engine(&parallel_dst_dir, *DEFAULT_MAX_DB_SIZE, true).unwrap();
+        for expected_tries in [data.len() as u64, 0] {
+            // Copying again finds everything in the destination already.
+            let mut copied_roots = vec![];
+            let copied_tries = super::helpers::copy_state_roots_parallel(
+                &state_roots,
+                &source_state,
+                &destination_state,
+                4,
+                BatchLimits::default(),
+                DEFAULT_MAX_CACHE_BYTES,
+                &mut |root_indices| {
+                    copied_roots.extend_from_slice(root_indices);
+                    Ok(())
+                },
+            )
+            .unwrap();
+            assert_eq!(copied_tries, expected_tries);
+            copied_roots.sort_unstable();
+            assert_eq!(copied_roots, vec![0, 1]);
+        }
+    }
+
+    let serial_entries = trie_store_entries(&serial_dst_dir);
+    // New destinations also hold the empty trie.
+    assert_eq!(serial_entries.len(), data.len() + 1);
+    assert_eq!(trie_store_entries(&parallel_dst_dir), serial_entries);
+}
+
+#[test]
+fn parallel_copy_should_read_shared_subtrees_once() {
+    let (src_dir, data) = create_test_trie_store();
+    // A second state root sharing `leaf1` and the subtree of `node2` with
+    // `node1`.
+    let node_3: Trie<Bytes, Bytes> = {
+        let mut pointer_block = PointerBlock::new();
+        pointer_block[0] = Some(Pointer::LeafPointer(data[0].0));
+        pointer_block[2] = Some(Pointer::NodePointer(data[4].0));
+        Trie::Node {
+            pointer_block: Box::new(pointer_block),
+        }
+    };
+    let node_3 = TestData(Digest::hash(node_3.to_bytes().unwrap()), node_3);
+    {
+        let env = LmdbEnvironment::new(&src_dir, *DEFAULT_MAX_DB_SIZE, 512, true).unwrap();
+        let store = LmdbTrieStore::open(&env, None).unwrap();
+        let mut txn = env.create_read_write_txn().unwrap();
+        store
+            .put_many(&mut txn, [&node_3].into_iter().map(Into::into))
+            .unwrap();
+        txn.commit().unwrap();
+    }
+    let (source_state, _env) =
+        load_execution_engine(&src_dir, *DEFAULT_MAX_DB_SIZE, Digest::default(), true).unwrap();
+
+    for thread_count in [2, 4, 8] {
+        let dst_dir = tempdir().unwrap();
+        let (destination_state, _env) =
+            create_execution_engine(&dst_dir, *DEFAULT_MAX_DB_SIZE, true).unwrap();
+        let copied_tries = super::helpers::copy_state_roots_parallel(
+            &[data[3].0, node_3.0],
+            &source_state,
+            &destination_state,
+            thread_count,
+            BatchLimits::default(),
+            DEFAULT_MAX_CACHE_BYTES,
+            &mut |_| Ok(()),
+        )
+        .unwrap();
+        // Every trie is read and written once.
+        assert_eq!(copied_tries, data.len() as u64 + 1);
+    }
+}
+
+#[test]
+fn parallel_copy_of_missing_trie_should_fail() {
+    let (src_dir, data) = create_test_trie_store();
+    let dst_dir = tempdir().unwrap();
+    let (source_state, _env) =
+        load_execution_engine(&src_dir, *DEFAULT_MAX_DB_SIZE, Digest::default(), true).unwrap();
+    let (destination_state, _env) =
+        create_execution_engine(&dst_dir, *DEFAULT_MAX_DB_SIZE, true).unwrap();
+
+    let missing_root = Digest::hash([1u8; 8]);
+    match super::helpers::copy_state_roots_parallel(
+        &[data[3].0, missing_root],
+        &source_state,
+        &destination_state,
+        2,
+        BatchLimits::default(),
+        DEFAULT_MAX_CACHE_BYTES,
+        &mut |_| Ok(()),
+    ) {
+        Err(Error::CopyStateRoot(state_root, _)) => assert_eq!(state_root, missing_root),
+        Err(err) => panic!("Unexpected error: {err}"),
+        Ok(_) => panic!("Unexpected successful trie copy"),
+    }
+}
+
+#[test]
+fn batched_copy_should_match_unbatched_copy() {
+    let (src_dir, data) = create_test_trie_store();
+    let (source_state, _env) =
+        load_execution_engine(&src_dir, *DEFAULT_MAX_DB_SIZE, Digest::default(), true).unwrap();
+    let state_roots = [data[4].0, data[3].0];
+
+    let reference_dst_dir = tempdir().unwrap();
+    {
+        let (destination_state, _env) =
+            create_execution_engine(&reference_dst_dir, *DEFAULT_MAX_DB_SIZE, true).unwrap();
+        for state_root in state_roots {
+            super::helpers::copy_state_root(state_root, &source_state, &destination_state).unwrap();
+        }
+    }
+    let reference_entries = trie_store_entries(&reference_dst_dir);
+
+    let default_limits = BatchLimits::default();
+    for (limits, max_cache_bytes) in [
+        (
+            BatchLimits {
+                max_tries: 1,
+                ..default_limits
+            },
+            DEFAULT_MAX_CACHE_BYTES,
+        ),
+        (
+            BatchLimits {
+                max_tries: 4,
+                ..default_limits
+            },
+            DEFAULT_MAX_CACHE_BYTES,
+        ),
+        (
+            BatchLimits {
+                max_tries: default_limits.max_tries,
+                max_bytes: Digest::LENGTH,
+            },
+            DEFAULT_MAX_CACHE_BYTES,
+        ),
+        // A single copied trie per generation.
+        (default_limits, 2 * COPIED_TRIE_BYTES),
+        // No copied tries remembered at all.
+        (default_limits, 0),
+    ] {
+        let dst_dir = tempdir().unwrap();
+        {
+            let (destination_state, _env) =
+                create_execution_engine(&dst_dir, *DEFAULT_MAX_DB_SIZE, true).unwrap();
+            let mut copied_tries = CopiedTries::new(max_cache_bytes);
+            for state_root in state_roots {
+                super::helpers::copy_state_root_batched(
+                    state_root,
+                    &source_state,
+                    &destination_state,
+                    limits,
+                    &mut copied_tries,
+                )
+                .unwrap();
+            }
+        }
+        assert_eq!(trie_store_entries(&dst_dir), reference_entries);
+    }
+}
+
+#[test]
+fn copied_tries_should_forget_oldest_generation() {
+    let keys: Vec<Digest> = (0u8..5).map(|idx| Digest::hash([idx])).collect();
+    let mut copied_tries = CopiedTries::new(2 * 2 * COPIED_TRIE_BYTES);
+    for key in keys.iter().take(4) {
+        copied_tries.insert(*key);
+    }
+    assert!(keys.iter().take(4).all(|key| copied_tries.contains(key)));
+    // The first two keys are evicted, the last two are kept.
+    copied_tries.insert(keys[4]);
+    assert!(!copied_tries.contains(&keys[0]));
+    assert!(!copied_tries.contains(&keys[1]));
+    assert!(keys.iter().skip(2).all(|key| copied_tries.contains(key)));
+
+    let mut copied_tries = CopiedTries::new(0);
+    copied_tries.insert(keys[0]);
+    assert!(!copied_tries.contains(&keys[0]));
+}
+
+#[test]
+fn compaction_should_record_progress() {
+    let (src_dir, data) = create_test_trie_store();
+    let dst_dir = tempdir().unwrap();
+    // `leaf1`, `node2`, then `node1` twice.
+    let storage_dir = create_test_storage(&[data[0].0, data[4].0, data[3].0, data[3].0]);
+    compact::trie_compact(
+        &StateRootSource::storage(&storage_dir, BlockSelection::All),
+        &src_dir,
+        &dst_dir,
+        DestinationOptions::New,
+        *DEFAULT_MAX_DB_SIZE,
+        CompactOptions::default(),
+    )
+    .unwrap();
+
+    let expected_entries = vec![
+        ProgressEntry {
+            block_height: Some(3),
+            state_root: data[3].0,
+        },
+        ProgressEntry {
+            block_height: Some(1),
+            state_root: data[4].0,
+        },
+        ProgressEntry {
+            block_height: Some(0),
+            state_root: data[0].0,
+        },
+    ];
+    assert_eq!(
+        progress_log::read_progress_log(dst_dir.path().join(PROGRESS_LOG_FILE_NAME)).unwrap(),
+        expected_entries
+    );
+    // New destinations also hold the empty trie.
+    assert_eq!(trie_store_entries(&dst_dir).len(), data.len() + 1);
+}
+
+#[test]
+fn compaction_should_resume() {
+    let (src_dir, data) = create_test_trie_store();
+    let dst_dir = tempdir().unwrap();
+    let storage_dir = create_test_storage(&[data[0].0, data[4].0, data[3].0, data[3].0]);
+
+    // Nothing to resume yet.
+    match compact::trie_compact(
+        &StateRootSource::storage(&storage_dir, BlockSelection::All),
+        &src_dir,
+        &dst_dir,
+        DestinationOptions::Resume,
+        *DEFAULT_MAX_DB_SIZE,
+        CompactOptions::default(),
+    ) {
+        Err(Error::InvalidDest(_)) => {}
+        Err(err) => panic!("Unexpected error: {err}"),
+        Ok(_) => panic!("Unexpected successful trie compact"),
+    }
+
+    // Record `node1` as copied, with the last entry cut short, without
+    // actually copying it.
+    drop(create_execution_engine(&dst_dir, *DEFAULT_MAX_DB_SIZE, true).unwrap());
+    let progress_log_path = dst_dir.path().join(PROGRESS_LOG_FILE_NAME);
+    ProgressLog::open(&progress_log_path, false)
+        .unwrap()
+        .append(&[ProgressEntry {
+            block_height: Some(3),
+            state_root: data[3].0,
+        }])
+        .unwrap();
+    let mut log_contents = fs::read_to_string(&progress_log_path).unwrap();
+    log_contents.push_str("{\"block_height\":1,");
+    fs::write(&progress_log_path, log_contents).unwrap();
+
+    compact::trie_compact(
+        &StateRootSource::storage(&storage_dir, BlockSelection::All),
+        &src_dir,
+        &dst_dir,
+        DestinationOptions::Resume,
+        *DEFAULT_MAX_DB_SIZE,
+        CompactOptions::default(),
+    )
+    .unwrap();
+
+    // Only `node2` and `leaf1` were copied, along with their descendants.
+    let entries = trie_store_entries(&dst_dir);
+    for (idx, test_data) in data.iter().enumerate() {
+        let is_copied = entries
+            .iter()
+            .any(|(key, _)| key == &test_data.0.to_bytes().unwrap());
+        assert_eq!(is_copied, [0, 1, 2, 4].contains(&idx));
+    }
+    let recorded_roots: Vec<Digest> = progress_log::read_progress_log(&progress_log_path)
+        .unwrap()
+        .into_iter()
+        .map(|entry| entry.state_root)
+        .collect();
+    assert_eq!(recorded_roots, vec![data[3].0, data[4].0, data[0].0]);
+}
+
+#[test]
+fn compaction_should_resume_after_failing_between_batches() {
+    let (src_dir, data) = create_test_trie_store();
+    let reference_dir = tempdir().unwrap();
+    compact::trie_compact(
+        &StateRootSource::List(vec![data[3].0]),
+        &src_dir,
+        &reference_dir,
+        DestinationOptions::New,
+        *DEFAULT_MAX_DB_SIZE,
+        CompactOptions::default(),
+    )
+    .unwrap();
+    let reference_entries = trie_store_entries(&reference_dir);
+
+    for thread_count in [1, 2] {
+        let (src_dir, data) = create_test_trie_store();
+        let dst_dir = tempdir().unwrap();
+        let options = CompactOptions {
+            thread_count,
+            batch_limits: BatchLimits {
+                max_tries: 1,
+                ..BatchLimits::default()
+            },
+            ..CompactOptions::default()
+        };
+        // Without `leaf3`, the copy of `node1` fails after some of its
+        // descendants were committed.
+        replace_trie(&src_dir, data[2].0, None);
+        assert!(compact::trie_compact(
+            &StateRootSource::List(vec![data[3].0]),
+            &src_dir,
+            &dst_dir,
+            DestinationOptions::New,
+            *DEFAULT_MAX_DB_SIZE,
+            options,
+        )
+        .is_err());
+
+        replace_trie(&src_dir, data[2].0, Some(data[2].1.clone()));
+        compact::trie_compact(
+            &StateRootSource::List(vec![data[3].0]),
+            &src_dir,
+            &dst_dir,
+            DestinationOptions::Resume,
+            *DEFAULT_MAX_DB_SIZE,
+            options,
+        )
+        .unwrap();
+        assert_eq!(trie_store_entries(&dst_dir), reference_entries);
+    }
+}
+
+#[test]
+fn compaction_should_copy_selected_blocks_only() {
+    let (src_dir, data) = create_test_trie_store();
+    // `node1`, `leaf1`, `node2`, `leaf2` and `leaf3` over three eras.
+    let storage_dir = create_test_storage_with_eras(&[
+        (data[3].0, 0),
+        (data[0].0, 0),
+        (data[4].0, 1),
+        (data[1].0, 1),
+        (data[2].0, 2),
+    ]);
+
+    let expected_all_roots = vec![data[2].0, data[1].0, data[4].0, data[0].0, data[3].0];
+    for (block_selection, expected_roots) in [
+        (BlockSelection::LastBlocks(2), vec![data[2].0, data[1].0]),
+        (
+            BlockSelection::LastEras(2),
+            vec![data[2].0, data[1].0, data[4].0],
+        ),
+        (
+            BlockSelection::HeightRange {
+                lowest: 1,
+                highest: Some(2),
+            },
+            vec![data[4].0, data[0].0],
+        ),
+        // The upper bound is capped at the highest block.
+        (
+            BlockSelection::HeightRange {
+                lowest: 3,
+                highest: Some(10),
+            },
+            vec![data[2].0, data[1].0],
+        ),
+        (
+            BlockSelection::LastBlocks(u64::MAX),
+            expected_all_roots.clone(),
+        ),
+        (BlockSelection::LastEras(u64::MAX), expected_all_roots),
+    ] {
+        let dst_dir = tempdir().unwrap();
+        compact::trie_compact(
+            &StateRootSource::storage(&storage_dir, block_selection),
+            &src_dir,
+            &dst_dir,
+            DestinationOptions::New,
+            *DEFAULT_MAX_DB_SIZE,
+            CompactOptions::default(),
+        )
+        .unwrap();
+
+        let recorded_roots: Vec<Digest> =
+            progress_log::read_progress_log(dst_dir.path().join(PROGRESS_LOG_FILE_NAME))
+                .unwrap()
+                .into_iter()
+                .map(|entry| entry.state_root)
+                .collect();
+        assert_eq!(recorded_roots, expected_roots);
+        // Only the tries under the selected roots were copied, along with
+        // the empty trie.
+        let entries = trie_store_entries(&dst_dir);
+        for test_data in data.iter() {
+            let is_copied = entries
+                .iter()
+                .any(|(key, _)| key == &test_data.0.to_bytes().unwrap());
+            let is_selected = match test_data.0 {
+                root if root == data[3].0 || root == data[5].0 => {
+                    expected_roots.contains(&data[3].0)
+                }
+                root if root == data[4].0 => expected_roots.contains(&root),
+                root if root == data[1].0 || root == data[2].0 => {
+                    expected_roots.contains(&root) || expected_roots.contains(&data[4].0)
+                }
+                root => expected_roots.contains(&root),
+            };
+            assert_eq!(is_copied, is_selected);
+        }
+    }
+
+    // Selecting only blocks above the highest one is most likely a mistake.
+    match compact::trie_compact(
+        &StateRootSource::storage(
+            &storage_dir,
+            BlockSelection::HeightRange {
+                lowest: 5,
+                highest: None,
+            },
+        ),
+        &src_dir,
+        tempdir().unwrap(),
+        DestinationOptions::New,
+        *DEFAULT_MAX_DB_SIZE,
+        CompactOptions::default(),
+    ) {
+        Err(Error::LowestHeightAboveHighestBlock(5, 4)) => {}
+        Err(err) => panic!("Unexpected error: {err}"),
+        Ok(_) => panic!("Unexpected successful trie compact"),
+    }
+}
+
+#[test]
+fn dry_run_should_estimate_compacted_size() {
+    let (src_dir, data) = create_test_trie_store();
+    // `node2` is reachable from `node1`, so its tries are only counted once.
+    let storage_dir = create_test_storage(&[data[0].0, data[4].0, data[3].0]);
+    let dst_dir = tempdir().unwrap();
+
+    for block_selection in [BlockSelection::All, BlockSelection::LastBlocks(1)] {
+        let estimate = estimate::estimate_compaction(
+            &StateRootSource::storage(&storage_dir, block_selection),
+            &src_dir,
+            *DEFAULT_MAX_DB_SIZE,
+            None,
+        )
+        .unwrap();
+        let compacted_dir = dst_dir.path().join(format!("{block_selection:?}"));
+        compact::trie_compact(
+            &StateRootSource::storage(&storage_dir, block_selection),
+            &src_dir,
+            &compacted_dir,
+            DestinationOptions::New,
+            *DEFAULT_MAX_DB_SIZE,
+            CompactOptions::default(),
+        )
+        .unwrap();
+
+        // The compacted store also holds the empty trie, which isn't part of
+        // the test data.
+        let compacted_entries = trie_store_entries(&compacted_dir);
+        let expected_entries: Vec<_> = compacted_entries
+            .iter()
+            .filter(|(key, _)| {
+                data.iter()
+                    .any(|test_data| key == &test_data.0.to_bytes().unwrap())
+            })
+            .collect();
+        assert_eq!(estimate.node_count, expected_entries.len());
+        assert_eq!(
+            estimate.total_bytes,
+            expected_entries
+                .iter()
+                .map(|(key, value)| (key.len() + value.len()) as u64)
+                .sum::<u64>()
+        );
+        assert_eq!(
+            estimate.source_file_size,
+            fs::metadata(src_dir.path().join(TRIE_STORE_FILE_NAME))
+                .unwrap()
+                .len()
+        );
+    }
+
+    // A dry run doesn't need a destination.
+    let estimate = estimate::estimate_compaction(
+        &StateRootSource::storage(&storage_dir, BlockSelection::All),
+        &src_dir,
+        *DEFAULT_MAX_DB_SIZE,
+        None,
+    )
+    .unwrap();
+    assert_eq!(estimate.state_root_count, 3);
+    assert_eq!(estimate.node_count, data.len());
+}
+
+/// Overwrites or, without a value, deletes a trie in the trie store.
+fn replace_trie<P: AsRef<Path>>(path: P, trie_key: Digest, maybe_trie: Option<Trie<Bytes, Bytes>>) {
+    let env = LmdbEnvironment::new(path, *DEFAULT_MAX_DB_SIZE, 512, true).unwrap();
+    let store = LmdbTrieStore::open(&env, None).unwrap();
+    let mut txn = env.create_read_write_txn().unwrap();
+    let key_bytes = trie_key.to_bytes().unwrap();
+    match maybe_trie {
+        Some(trie) => txn
+            .put(
+                store.get_db(),
+                &key_bytes,
+                &trie.to_bytes().unwrap(),
+                lmdb::WriteFlags::empty(),
+            )
+            .unwrap(),
+        None => txn.del(store.get_db(), &key_bytes, None).unwrap(),
+    }
+    lmdb::Transaction::commit(txn).unwrap();
+}
+
+#[test]
+fn verification_should_check_compacted_tries() {
+    let (src_dir, data) = create_test_trie_store();
+    let storage_dir = create_test_storage(&[data[0].0, data[3].0, data[4].0]);
+    let dst_dir = tempdir().unwrap();
+    let state_root_source = StateRootSource::storage(&storage_dir, BlockSelection::LastBlocks(2));
+    compact::trie_compact(
+        &state_root_source,
+        &src_dir,
+        &dst_dir,
+        DestinationOptions::New,
+        *DEFAULT_MAX_DB_SIZE,
+        CompactOptions::default(),
+    )
+    .unwrap();
+    let scratch_dir = tempdir().unwrap();
+    let verify = |compare_source| {
+        verify::verify_compaction(
+            &state_root_source,
+            &src_dir,
+            &dst_dir,
+            *DEFAULT_MAX_DB_SIZE,
+            compare_source,
+            Some(scratch_dir.path()),
+        )
+    };
+
+    // All the tries are reachable from `node1`.
+    let totals = verify(true).unwrap();
+    assert_eq!(totals.node_count, data.len());
+    // The scratch sets were removed.
+    assert_eq!(fs::read_dir(scratch_dir.path()).unwrap().count(), 0);
+
+    // A leaf of a different size is still reachable, but doesn't match the
+    // source.
+    replace_trie(
+        &dst_dir,
+        data[1].0,
+        Some(Trie::Leaf {
+            key: Bytes::from(vec![1u8, 0, 0]),
+            value: Bytes::from(b"other_val_2".to_vec()),
+        }),
+    );
+    assert!(verify(false).is_ok());
+    match verify(true) {
+        Err(Error::VerificationMismatch {
+            source_tries,
+            destination_tries,
+            ..
+        }) => assert_eq!(source_tries, destination_tries),
+        Err(err) => panic!("Unexpected error: {err}"),
+        Ok(_) => panic!("Unexpected successful verification"),
+    }
+
+    // `leaf3` is only reachable from `node2`, the highest state root.
+    replace_trie(&dst_dir, data[2].0, None);
+    match verify(false) {
+        Err(Error::IncompleteTrie("destination", state_root, _)) => {
+            assert_eq!(state_root, data[4].0)
+        }
+        Err(err) => panic!("Unexpected error: {err}"),
+        Ok(_) => panic!("Unexpected successful verification"),
+    }
+}
+
+#[test]
+fn compaction_should_copy_listed_state_roots() {
+    let (src_dir, data) = create_test_trie_store();
+    let dst_dir = tempdir().unwrap();
+    let state_roots_path = dst_dir.path().join("state_roots.txt");
+    fs::write(
+        &state_roots_path,
+        format!(
+            "# From another node.\n{}\n\n  {}  \n{}\n",
+            hex::encode(data[4].0),
+            hex::encode(data[0].0),
+            hex::encode(data[4].0)
+        ),
+    )
+    .unwrap();
+    let state_roots = compact::read_state_roots_file(&state_roots_path).unwrap();
+    assert_eq!(state_roots, vec![data[4].0, data[0].0, data[4].0]);
+
+    // No storage is needed.
+    let trie_dir = dst_dir.path().join("trie");
+    compact::trie_compact(
+        &StateRootSource::List(state_roots),
+        &src_dir,
+        &trie_dir,
+        DestinationOptions::New,
+        *DEFAULT_MAX_DB_SIZE,
+        CompactOptions::default(),
+    )
+    .unwrap();
+    let expected_entries = vec![
+        ProgressEntry {
+            block_height: None,
+            state_root: data[4].0,
+        },
+        ProgressEntry {
+            block_height: None,
+            state_root: data[0].0,
+        },
+    ];
+    let progress_log_path = trie_dir.join(PROGRESS_LOG_FILE_NAME);
+    assert_eq!(
+        progress_log::read_progress_log(&progress_log_path).unwrap(),
+        expected_entries
+    );
+    let entries = trie_store_entries(&trie_dir);
+    for (idx, test_data) in data.iter().enumerate() {
+        let is_copied = entries
+            .iter()
+            .any(|(key, _)| key == &test_data.0.to_bytes().unwrap());
+        assert_eq!(is_copied, [0, 1, 2, 4].contains(&idx));
+    }
+
+    // Resuming skips the state roots already copied.
+    compact::trie_compact(
+        &StateRootSource::List(vec![data[0].0, data[3].0]),
+        &src_dir,
+        &trie_dir,
+        DestinationOptions::Resume,
+        *DEFAULT_MAX_DB_SIZE,
+        CompactOptions::default(),
+    )
+    .unwrap();
+    let recorded_roots: Vec<Digest> = progress_log::read_progress_log(&progress_log_path)
+        .unwrap()
+        .into_iter()
+        .map(|entry| entry.state_root)
+        .collect();
+    assert_eq!(recorded_roots, vec![data[4].0, data[0].0, data[3].0]);
+
+    fs::write(
+        &state_roots_path,
+        format!("{}\nnot_a_hash\n", hex::encode(data[4].0)),
+    )
+    .unwrap();
+    match compact::read_state_roots_file(&state_roots_path) {
+        Err(Error::InvalidStateRoot(2, _)) => {}
+        Err(err) => panic!("Unexpected error: {err}"),
+        Ok(_) => panic!("Unexpected successful parsing"),
+    }
+}
+
+/// Parses the block selection of `compact-trie` with the given extra
+/// arguments.
+fn parse_block_selection(args: &[&str]) -> BlockSelection {
+    let matches = super::command(0)
+        .try_get_matches_from(
+            ["compact-trie", "-s", "src", "-d", "dst", "-b", "storage"]
+                .iter()
+                .chain(args),
+        )
+        .unwrap();
+    super::block_selection(&matches)
+}
+
+#[test]
+fn block_selection_should_be_parsed() {
+    assert_eq!(parse_block_selection(&[]), BlockSelection::All);
+    assert_eq!(
+        parse_block_selection(&["--from-height", "100", "--to-height", "100"]),
+        BlockSelection::HeightRange {
+            lowest: 100,
+            highest: Some(100),
+        }
+    );
+    assert_eq!(
+        parse_block_selection(&["--to-height", "100"]),
+        BlockSelection::HeightRange {
+            lowest: 0,
+            highest: Some(100),
+        }
+    );
+}
+
+#[test]
+#[should_panic(expected = "must not be greater than")]
+fn block_selection_should_reject_empty_height_range() {
+    parse_block_selection(&["--from-height", "500", "--to-height", "100"]);
+}
@@ -16,15 +16,18 @@ use casper_execution_engine::{
 use casper_hashing::Digest;
 use casper_node::{storage::Storage, StorageConfig, WithDir};
 use casper_types::ProtocolVersion;
-use lmdb::DatabaseFlags;
+use lmdb::{Database, DatabaseFlags, Environment};
 
-use crate::common::db::TRIE_STORE_FILE_NAME;
+use crate::common::db::{self, TRIE_STORE_FILE_NAME};
 
 /// LMDB max readers
 ///
 /// The default value is chosen to be the same as the node itself.
 const DEFAULT_MAX_READERS: u32 = 512;
 
+/// Name of the trie store database, as set by the execution engine.
+const TRIE_STORE_DB_NAME: &str = "TRIE_STORE";
+
 /// Loads an existing execution engine.
 pub fn load_execution_engine(
     ee_lmdb_path: impl AsRef<Path>,
@@ -53,6 +56,23 @@ pub fn load_execution_engine(
     ))
 }
 
+/// Opens an existing trie store read-only, without going through the
+/// execution engine, which opens it for writing.
+pub(crate) fn open_read_only_trie_store(
+    ee_lmdb_path: impl AsRef<Path>,
+) -> Result<(Environment, Database), anyhow::Error> {
+    let lmdb_data_file = ee_lmdb_path.as_ref().join(TRIE_STORE_FILE_NAME);
+    if !lmdb_data_file.exists() {
+        return Err(anyhow::anyhow!(
+            "lmdb data file not found at: {}",
+            lmdb_data_file.display()
+        ));
+    }
+    let env = db::read_only_db_env(&lmdb_data_file)?;
+    let db = env.open_db(Some(TRIE_STORE_DB_NAME))?;
+    Ok((env, db))
+}
+
 /// Create an lmdb environment at a given path.
 fn create_lmdb_environment(
     lmdb_path: impl AsRef<Path>,
@@ -0,0 +1,120 @@
+use std::{collections::HashSet, path::Path};
+
+use log::info;
+
+use casper_execution_engine::{
+    core::engine_state::EngineState, storage::global_state::lmdb::LmdbGlobalState,
+};
+use casper_hashing::Digest;
+
+use super::{
+    compact::StateRootSource, helpers::ReachableTries, utils::load_execution_engine, Error,
+};
+
+/// Tries reachable from a set of state roots.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct TrieTotals {
+    /// Number of distinct reachable tries.
+    pub node_count: usize,
+    /// Total size of the keys and values of the reachable tries.
+    pub total_bytes: u64,
+}
+
+/// Traverses the tries reachable from `state_roots` in the trie store of
+/// `engine_state`, failing on the first missing one. `store_name` describes
+/// the trie store in errors.
+fn traverse(
+    store_name: &'static str,
+    state_roots: &[Digest],
+    engine_state: &EngineState<LmdbGlobalState>,
+    scratch_dir: &Path,
+    max_db_size: usize,
+) -> Result<TrieTotals, Error> {
+    let reachable_tries =
+        ReachableTries::new(scratch_dir, max_db_size).map_err(Error::CreateScratch)?;
+    let mut totals = TrieTotals::default();
+    for state_root in state_roots {
+        let (node_count, total_bytes) = reachable_tries
+            .mark_state_root(
+                *state_root,
+                engine_state.get_state().environment().env(),
+                engine_state.get_state().trie_store().get_db(),
+            )
+            .map_err(|err| Error::IncompleteTrie(store_name, *state_root, err))?;
+        totals.node_count += node_count;
+        totals.total_bytes += total_bytes;
+    }
+    Ok(totals)
+}
+
+/// Checks that every trie reachable from the state roots of
+/// `state_root_source` is in the destination trie store.
+///
+/// If `compare_source` is set, the same tries are traversed in the source,
+/// and the verification fails unless their count and total size match the
+/// ones in the destination.
+///
+/// The traversed tries are recorded under `scratch_dir`, or the destination
+/// directory if unset.
+pub fn verify_compaction<P1: AsRef<Path>, P2: AsRef<Path>>(
+    state_root_source: &StateRootSource,
+    source_trie_path: P1,
+    destination_trie_path: P2,
+    max_db_size: usize,
+    compare_source: bool,
+    scratch_dir: Option<&Path>,
+) -> Result<TrieTotals, Error> {
+    let scratch_dir = scratch_dir.unwrap_or_else(|| destination_trie_path.as_ref());
+    let state_roots: Vec<Digest> = state_root_source
+        .state_roots(None, &mut HashSet::new())?
+        .into_iter()
+        .map(|entry| entry.state_root)
+        .collect();
+    if state_roots.is_empty() {
+        info!("No state roots to verify.");
+        return Ok(TrieTotals::default());
+    }
+
+    info!(
+        "Verifying {} state roots in the destination.",
+        state_roots.len()
+    );
+    let (destination_state, _env) =
+        load_execution_engine(&destination_trie_path, max_db_size, Digest::default(), true)
+            .map_err(Error::OpenDestTrie)?;
+    let destination_totals = traverse(
+        "destination",
+        &state_roots,
+        &destination_state,
+        scratch_dir,
+        max_db_size,
+    )?;
+    info!(
+        "Destination holds {} tries totaling {} bytes under the verified state roots.",
+        destination_totals.node_count, destination_totals.total_bytes
+    );
+
+    if compare_source {
+        info!("Traversing the same state roots in the source.");
+        let (source_state, _env) =
+            load_execution_engine(source_trie_path, max_db_size, Digest::default(), true)
+                .map_err(Error::OpenSourceTrie)?;
+        let source_totals = traverse(
+            "source",
+            &state_roots,
+            &source_state,
+            scratch_dir,
+            max_db_size,
+        )?;
+        if source_totals != destination_totals {
+            return Err(Error::VerificationMismatch {
+                source_tries: source_totals.node_count,
+                source_bytes: source_totals.total_bytes,
+                destination_tries: destination_totals.node_count,
+                destination_bytes: destination_totals.total_bytes,
+            });
+        }
+    }
+    info!("Verification succeeded.");
+    Ok(destination_totals)
+}
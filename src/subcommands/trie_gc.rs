@@ -0,0 +1,205 @@
+mod gc;
+#[cfg(test)]
+mod tests;
+
+use std::{io::Error as IoError, path::PathBuf};
+
+use anyhow::Error as AnyError;
+use clap::{Arg, ArgMatches, Command};
+use lmdb::Error as LmdbError;
+use thiserror::Error as ThisError;
+
+use casper_hashing::Digest;
+
+use gc::GcOptions;
+
+use super::trie_compact::{self, Error as TrieCompactError, DEFAULT_MAX_DB_SIZE};
+
+pub const COMMAND_NAME: &str = "gc-trie";
+const BATCH_SIZE: &str = "batch-size";
+const DRY_RUN: &str = "dry-run";
+const MAX_DB_SIZE: &str = "max-db-size";
+const STATE_ROOTS_FILE: &str = "state-roots-file";
+const STORAGE_PATH: &str = "storage-path";
+const TRIE_STORE_PATH: &str = "trie";
+
+/// Number of trie store entries scanned in a single write transaction by
+/// default.
+const DEFAULT_BATCH_SIZE: &str = "10000";
+
+/// Possible errors caught while collecting the garbage of the trie store.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Error walking the blocks in storage.
+    #[error("Error finding the retained state roots: {0}")]
+    BlockWalk(TrieCompactError),
+    /// Error creating the scratch set of reachable tries.
+    #[error("Error creating the scratch set of reachable tries: {0}")]
+    CreateScratch(AnyError),
+    /// Error while operating on LMDB.
+    #[error("Error while operating on LMDB: {0}")]
+    Lmdb(#[from] LmdbError),
+    /// Error checking whether another process has the trie store open.
+    #[error("Error checking the lock file {0}: {1}")]
+    LockFile(PathBuf, IoError),
+    /// Error marking the tries reachable from a state root.
+    #[error("Error marking state root {0}: {1}")]
+    MarkStateRoot(Digest, AnyError),
+    /// No block selected, which would delete every trie.
+    #[error("No state roots to retain, refusing to delete every trie")]
+    NoStateRoots,
+    /// Error opening the block/deploys LMDB store.
+    #[error("Error opening the block/deploy storage: {0}")]
+    OpenStorage(AnyError),
+    /// Error opening the trie store.
+    #[error("Error opening the trie store: {0}")]
+    OpenTrie(AnyError),
+    /// Error reading the extra state roots to retain.
+    #[error("Error reading the extra state roots: {0}")]
+    StateRootsFile(TrieCompactError),
+    /// Another process, most likely the node, has the trie store open.
+    #[error(
+        "The trie store is open in another process, stop the node before collecting the \
+        garbage"
+    )]
+    TrieStoreInUse,
+}
+
+enum DisplayOrder {
+    TriePath,
+    StoragePath,
+    MaxDbSize,
+    BatchSize,
+    DryRun,
+    ScratchDir,
+    StateRootsFile,
+    BlockSelection,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Deletes the tries unreachable from the state roots of the blocks in storage from \
+            the trie store, in place. The node must be stopped, which is checked before \
+            deleting anything. Run `unsparse` afterwards to reclaim the disk space. With \
+            `--dry-run`, only reports how many tries are unreachable and their size.",
+        )
+        .arg(
+            Arg::new(TRIE_STORE_PATH)
+                .display_order(DisplayOrder::TriePath as usize)
+                .required(true)
+                .short('t')
+                .long(TRIE_STORE_PATH)
+                .takes_value(true)
+                .value_name("TRIE_STORE_DIR_PATH")
+                .help("Path of the directory with the `data.lmdb` file."),
+        )
+        .arg(
+            Arg::new(STORAGE_PATH)
+                .display_order(DisplayOrder::StoragePath as usize)
+                .required(true)
+                .short('b')
+                .long(STORAGE_PATH)
+                .takes_value(true)
+                .value_name("STORAGE_DIR_PATH")
+                .help(
+                    "Path of the directory with the `storage.lmdb` file. Used to find all \
+                    blocks' state root hashes.",
+                ),
+        )
+        .arg(
+            Arg::new(MAX_DB_SIZE)
+                .display_order(DisplayOrder::MaxDbSize as usize)
+                .required(false)
+                .short('m')
+                .long(MAX_DB_SIZE)
+                .takes_value(true)
+                .default_value(DEFAULT_MAX_DB_SIZE)
+                .value_name("MAX_DB_SIZE")
+                .help("Maximum size the DB files are allowed to be, in bytes."),
+        )
+        .arg(
+            Arg::new(BATCH_SIZE)
+                .display_order(DisplayOrder::BatchSize as usize)
+                .required(false)
+                .long(BATCH_SIZE)
+                .takes_value(true)
+                .default_value(DEFAULT_BATCH_SIZE)
+                .value_name("TRIE_COUNT")
+                .help(
+                    "Maximum number of tries scanned, and possibly deleted, in a single write \
+                    transaction.",
+                ),
+        )
+        .arg(
+            Arg::new(DRY_RUN)
+                .display_order(DisplayOrder::DryRun as usize)
+                .required(false)
+                .long(DRY_RUN)
+                .takes_value(false)
+                .help(
+                    "Only count the unreachable tries and their size, without deleting them. \
+                    The trie store is opened read-only, and the tries missing under the \
+                    retained state roots are logged and counted instead of stopping the run. \
+                    The reachable tries are remembered in a temporary file.",
+                ),
+        )
+        .arg(trie_compact::scratch_dir_arg(
+            DisplayOrder::ScratchDir as usize,
+        ))
+        .arg(
+            Arg::new(STATE_ROOTS_FILE)
+                .display_order(DisplayOrder::StateRootsFile as usize)
+                .required(false)
+                .long(STATE_ROOTS_FILE)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help(
+                    "Path of a file with extra state root hashes to retain, one hex encoded \
+                    hash per line, such as a global state root after an upgrade no block \
+                    refers to. Empty lines and lines starting with `#` are ignored.",
+                ),
+        )
+        .args(trie_compact::block_selection_args(
+            DisplayOrder::BlockSelection as usize,
+        ))
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let trie_path = matches.value_of(TRIE_STORE_PATH).unwrap();
+    let storage_path = matches.value_of(STORAGE_PATH).unwrap();
+    let max_db_size = matches
+        .value_of(MAX_DB_SIZE)
+        .unwrap()
+        .parse()
+        .expect("Value of \"--max-db-size\" must be an integer.");
+    let batch_size = matches
+        .value_of(BATCH_SIZE)
+        .unwrap()
+        .parse()
+        .ok()
+        .filter(|batch_size| *batch_size > 0)
+        .expect("Value of \"--batch-size\" must be a positive integer.");
+    let block_selection = trie_compact::block_selection(matches);
+    let extra_state_roots = match matches.value_of(STATE_ROOTS_FILE) {
+        Some(state_roots_file) => {
+            trie_compact::read_state_roots_file(state_roots_file).map_err(Error::StateRootsFile)?
+        }
+        None => vec![],
+    };
+    let options = GcOptions {
+        batch_size,
+        dry_run: matches.is_present(DRY_RUN),
+        scratch_dir: trie_compact::scratch_dir(matches),
+        extra_state_roots,
+    };
+    gc::trie_gc(
+        storage_path,
+        trie_path,
+        max_db_size,
+        block_selection,
+        options,
+    )
+    .map(|_| ())
+}
@@ -0,0 +1,276 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use lmdb::{Cursor, Database, Environment, Error as LmdbError, Transaction};
+use log::{info, warn};
+
+use casper_hashing::Digest;
+
+use crate::{
+    common::{db::TRIE_STORE_FILE_NAME, lmdb_utils},
+    subcommands::trie_compact::{
+        self, create_storage, load_execution_engine, BlockSelection, ReachableTries,
+    },
+};
+
+use super::Error;
+
+/// Number of trie store entries scanned between progress messages of a dry
+/// run.
+const SCANNED_TRIES_PER_PROGRESS_MESSAGE: usize = 1_000_000;
+
+/// Settings of the garbage collection.
+#[derive(Clone, Debug)]
+pub(crate) struct GcOptions {
+    /// Maximum number of entries scanned in a single write transaction.
+    pub(crate) batch_size: usize,
+    /// Only count the unreachable tries, leaving the trie store untouched.
+    pub(crate) dry_run: bool,
+    /// Directory in which the reachable tries are recorded. Defaults to the
+    /// trie store directory.
+    pub(crate) scratch_dir: Option<PathBuf>,
+    /// State roots retained along with the ones of the selected blocks, such
+    /// as a global state root no block refers to.
+    pub(crate) extra_state_roots: Vec<Digest>,
+}
+
+/// Outcome of a garbage collection of the trie store.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct GcSummary {
+    /// Number of distinct state roots retained.
+    pub(crate) state_root_count: usize,
+    /// Number of tries reachable from the retained state roots.
+    pub(crate) reachable_tries: usize,
+    /// Total size of the keys and values of the reachable tries.
+    pub(crate) reachable_bytes: u64,
+    /// Number of distinct tries missing under the retained state roots,
+    /// which are only tolerated in a dry run.
+    pub(crate) missing_tries: usize,
+    /// Number of unreachable tries, deleted unless in a dry run.
+    pub(crate) unreachable_tries: usize,
+    /// Total size of the keys and values of the unreachable tries.
+    pub(crate) unreachable_bytes: u64,
+}
+
+impl GcSummary {
+    /// Returns the share, in percents, of the trie store entries' size taken
+    /// by unreachable tries.
+    pub(crate) fn unreachable_percentage(&self) -> f64 {
+        let total_bytes = self.reachable_bytes + self.unreachable_bytes;
+        if total_bytes == 0 {
+            return 0.0;
+        }
+        100.0 * self.unreachable_bytes as f64 / total_bytes as f64
+    }
+}
+
+/// Deletes the entries of `db` missing from `reachable_tries`, scanning at
+/// most `batch_size` entries in each write transaction.
+fn sweep(
+    env: &Environment,
+    db: Database,
+    reachable_tries: &ReachableTries,
+    batch_size: usize,
+    summary: &mut GcSummary,
+) -> Result<(), LmdbError> {
+    // Key of the first entry the next transaction scans. It's left untouched
+    // by the previous transaction, so it's still there to start from.
+    let mut next_key: Option<Vec<u8>> = None;
+    loop {
+        let mut txn = env.begin_rw_txn()?;
+        let reachable_txn = reachable_tries.begin_ro_txn()?;
+        let mut unreachable_keys = vec![];
+        {
+            let mut cursor = txn.open_ro_cursor(db)?;
+            let iter = match next_key.take() {
+                Some(key) => cursor.iter_from(key),
+                None => cursor.iter_start(),
+            };
+            for (scanned_entries, (key, value)) in iter.enumerate() {
+                if scanned_entries == batch_size {
+                    next_key = Some(key.to_vec());
+                    break;
+                }
+                if !reachable_tries.contains(&reachable_txn, key)? {
+                    summary.unreachable_bytes += (key.len() + value.len()) as u64;
+                    unreachable_keys.push(key.to_vec());
+                }
+            }
+        }
+        reachable_txn.commit()?;
+        for key in unreachable_keys.iter() {
+            txn.del(db, key, None)?;
+        }
+        txn.commit()?;
+        summary.unreachable_tries += unreachable_keys.len();
+        if next_key.is_none() {
+            return Ok(());
+        }
+        info!(
+            "Deleted {} unreachable tries so far.",
+            summary.unreachable_tries
+        );
+    }
+}
+
+/// Counts the entries of `db` missing from `reachable_tries`, without
+/// deleting them.
+fn count_unreachable(
+    env: &Environment,
+    db: Database,
+    reachable_tries: &ReachableTries,
+    summary: &mut GcSummary,
+) -> Result<(), LmdbError> {
+    let txn = env.begin_ro_txn()?;
+    let reachable_txn = reachable_tries.begin_ro_txn()?;
+    {
+        let mut cursor = txn.open_ro_cursor(db)?;
+        let mut tries_since_message = 0usize;
+        for (scanned_entries, (key, value)) in cursor.iter_start().enumerate() {
+            if !reachable_tries.contains(&reachable_txn, key)? {
+                summary.unreachable_tries += 1;
+                summary.unreachable_bytes += (key.len() + value.len()) as u64;
+            }
+            tries_since_message += 1;
+            if tries_since_message == SCANNED_TRIES_PER_PROGRESS_MESSAGE {
+                info!(
+                    "Scanned {} tries, found {} unreachable so far.",
+                    scanned_entries + 1,
+                    summary.unreachable_tries
+                );
+                tries_since_message = 0;
+            }
+        }
+    }
+    reachable_txn.commit()?;
+    txn.commit()?;
+    Ok(())
+}
+
+/// Deletes the tries unreachable from the state roots of the blocks selected
+/// by `block_selection` and the extra state roots of `options` from the trie
+/// store at `trie_path`.
+///
+/// The trie store is modified in place, so this refuses to run while another
+/// process, such as the node, has it open.
+///
+/// All reachable tries are marked first, in a temporary file, so nothing is
+/// deleted if any of them is missing. The unreachable tries are then deleted
+/// in write transactions scanning at most `batch_size` entries each. LMDB
+/// reuses the freed pages, but the file only shrinks through `unsparse`.
+///
+/// With the `dry_run` option, the trie store is opened read-only and the
+/// unreachable tries are only counted. The missing tries are then logged and
+/// counted instead of aborting the run.
+pub(crate) fn trie_gc<P1: AsRef<Path>, P2: AsRef<Path>>(
+    storage_path: P1,
+    trie_path: P2,
+    max_db_size: usize,
+    block_selection: BlockSelection,
+    options: GcOptions,
+) -> Result<GcSummary, Error> {
+    let GcOptions {
+        batch_size,
+        dry_run,
+        scratch_dir,
+        extra_state_roots,
+    } = options;
+    if !dry_run {
+        let mut lock_path = trie_path
+            .as_ref()
+            .join(TRIE_STORE_FILE_NAME)
+            .into_os_string();
+        lock_path.push("-lock");
+        if lmdb_utils::is_open_elsewhere(&lock_path)
+            .map_err(|io_err| Error::LockFile(PathBuf::from(&lock_path), io_err))?
+        {
+            return Err(Error::TrieStoreInUse);
+        }
+    }
+
+    let read_only_store;
+    let engine;
+    let (trie_env, db) = if dry_run {
+        read_only_store =
+            trie_compact::open_read_only_trie_store(&trie_path).map_err(Error::OpenTrie)?;
+        (&read_only_store.0, read_only_store.1)
+    } else {
+        engine = load_execution_engine(&trie_path, max_db_size, Digest::default(), true)
+            .map_err(Error::OpenTrie)?;
+        (engine.1.env(), engine.0.get_state().trie_store().get_db())
+    };
+    let storage = create_storage(&storage_path).map_err(Error::OpenStorage)?;
+    let mut visited_roots = HashSet::new();
+    let mut state_roots: Vec<Digest> =
+        trie_compact::selected_state_roots(&storage, block_selection, None, &mut visited_roots)
+            .map_err(Error::BlockWalk)?
+            .into_iter()
+            .map(|entry| entry.state_root)
+            .collect();
+    state_roots.extend(
+        extra_state_roots
+            .into_iter()
+            .filter(|state_root| visited_roots.insert(*state_root)),
+    );
+    if state_roots.is_empty() {
+        return Err(Error::NoStateRoots);
+    }
+
+    let mut summary = GcSummary {
+        state_root_count: state_roots.len(),
+        ..Default::default()
+    };
+    info!(
+        "Marking the tries reachable from {} state roots.",
+        state_roots.len()
+    );
+    let scratch_dir = scratch_dir.unwrap_or_else(|| trie_path.as_ref().to_path_buf());
+    let reachable_tries =
+        ReachableTries::new(scratch_dir, max_db_size).map_err(Error::CreateScratch)?;
+    // A missing trie may be reached from several state roots, but is only
+    // reported once.
+    let mut missing_tries = HashSet::new();
+    for state_root in state_roots {
+        let (marked_tries, marked_bytes) = reachable_tries
+            .mark_tries(state_root, trie_env, db, |trie_key| {
+                if !dry_run {
+                    return Err(anyhow::anyhow!("missing trie key {}", trie_key));
+                }
+                if missing_tries.insert(trie_key) {
+                    warn!("Missing trie {} under state root {}.", trie_key, state_root);
+                }
+                Ok(())
+            })
+            .map_err(|err| Error::MarkStateRoot(state_root, err))?;
+        summary.reachable_tries += marked_tries;
+        summary.reachable_bytes += marked_bytes;
+    }
+    summary.missing_tries = missing_tries.len();
+
+    if dry_run {
+        info!("Counting the unreachable tries.");
+        count_unreachable(trie_env, db, &reachable_tries, &mut summary)?;
+        info!(
+            "Found {} reachable tries totaling {} bytes, {} missing tries and {} unreachable \
+            tries totaling {} bytes, {:.2}% of the trie store entries' size.",
+            summary.reachable_tries,
+            summary.reachable_bytes,
+            summary.missing_tries,
+            summary.unreachable_tries,
+            summary.unreachable_bytes,
+            summary.unreachable_percentage()
+        );
+        return Ok(summary);
+    }
+
+    info!("Deleting the unreachable tries.");
+    sweep(trie_env, db, &reachable_tries, batch_size, &mut summary)?;
+    trie_env.sync(true)?;
+    info!(
+        "Kept {} reachable tries, deleted {} unreachable tries totaling {} bytes.",
+        summary.reachable_tries, summary.unreachable_tries, summary.unreachable_bytes
+    );
+    Ok(summary)
+}
@@ -0,0 +1,303 @@
+use std::fs;
+
+use tempfile::tempdir;
+
+use lmdb::{Transaction, WriteFlags};
+
+use casper_execution_engine::storage::{
+    transaction_source::{lmdb::LmdbEnvironment, TransactionSource},
+    trie::{Pointer, PointerBlock, Trie},
+    trie_store::lmdb::LmdbTrieStore,
+};
+use casper_hashing::Digest;
+use casper_types::bytesrepr::{Bytes, ToBytes};
+
+use crate::subcommands::trie_compact::{
+    tests::{
+        create_empty_test_storage, create_test_storage, create_test_trie_store, trie_store_entries,
+        TestData, DEFAULT_MAX_DB_SIZE,
+    },
+    BlockSelection,
+};
+
+use super::{
+    gc::{self, GcOptions, GcSummary},
+    Error,
+};
+
+fn options(batch_size: usize, dry_run: bool) -> GcOptions {
+    GcOptions {
+        batch_size,
+        dry_run,
+        scratch_dir: None,
+        extra_state_roots: vec![],
+    }
+}
+
+fn remaining_keys(entries: &[(Vec<u8>, Vec<u8>)]) -> Vec<Vec<u8>> {
+    entries.iter().map(|(key, _)| key.clone()).collect()
+}
+
+/// Returns the total size of the entries holding the tries of `data` at the
+/// given indices.
+fn entries_bytes(
+    entries: &[(Vec<u8>, Vec<u8>)],
+    data: &[TestData<Bytes, Bytes>],
+    indices: &[usize],
+) -> u64 {
+    entries
+        .iter()
+        .filter(|(key, _)| {
+            indices
+                .iter()
+                .any(|idx| key == &data[*idx].0.to_bytes().unwrap())
+        })
+        .map(|(key, value)| (key.len() + value.len()) as u64)
+        .sum()
+}
+
+#[test]
+fn gc_should_delete_unreachable_tries() {
+    for batch_size in [1, 2, 10_000] {
+        let (trie_dir, data) = create_test_trie_store();
+        // `node1` is in storage, but only `node2` is retained.
+        let storage_dir = create_test_storage(&[data[3].0, data[4].0]);
+        let entries_before = trie_store_entries(&trie_dir);
+
+        let summary = gc::trie_gc(
+            &storage_dir,
+            &trie_dir,
+            *DEFAULT_MAX_DB_SIZE,
+            BlockSelection::LastBlocks(1),
+            options(batch_size, false),
+        )
+        .unwrap();
+
+        // `node2`, `leaf2` and `leaf3` are kept.
+        assert_eq!(
+            summary,
+            GcSummary {
+                state_root_count: 1,
+                reachable_tries: 3,
+                reachable_bytes: entries_bytes(&entries_before, &data, &[1, 2, 4]),
+                missing_tries: 0,
+                unreachable_tries: 3,
+                unreachable_bytes: entries_bytes(&entries_before, &data, &[0, 3, 5]),
+            }
+        );
+        let mut expected_keys: Vec<Vec<u8>> = [1, 2, 4]
+            .iter()
+            .map(|idx| data[*idx].0.to_bytes().unwrap())
+            .collect();
+        expected_keys.sort();
+        assert_eq!(
+            remaining_keys(&trie_store_entries(&trie_dir)),
+            expected_keys
+        );
+    }
+}
+
+#[test]
+fn gc_should_keep_all_reachable_tries() {
+    let (trie_dir, data) = create_test_trie_store();
+    let storage_dir = create_test_storage(&[data[3].0, data[4].0]);
+    let entries_before = trie_store_entries(&trie_dir);
+
+    let summary = gc::trie_gc(
+        &storage_dir,
+        &trie_dir,
+        *DEFAULT_MAX_DB_SIZE,
+        BlockSelection::All,
+        options(1, false),
+    )
+    .unwrap();
+    assert_eq!(summary.reachable_tries, data.len());
+    assert_eq!(summary.unreachable_tries, 0);
+    assert_eq!(trie_store_entries(&trie_dir), entries_before);
+    // The scratch set, next to the trie store by default, was removed.
+    assert!(fs::read_dir(trie_dir.path())
+        .unwrap()
+        .all(|entry| !entry.unwrap().path().is_dir()));
+}
+
+#[test]
+fn gc_should_keep_extra_state_roots() {
+    let (trie_dir, data) = create_test_trie_store();
+    let storage_dir = create_test_storage(&[data[3].0, data[4].0]);
+    let scratch_dir = tempdir().unwrap();
+
+    // `leaf1` isn't the state root of any block.
+    let summary = gc::trie_gc(
+        &storage_dir,
+        &trie_dir,
+        *DEFAULT_MAX_DB_SIZE,
+        BlockSelection::LastBlocks(1),
+        GcOptions {
+            scratch_dir: Some(scratch_dir.path().to_path_buf()),
+            extra_state_roots: vec![data[0].0, data[4].0],
+            ..options(10, false)
+        },
+    )
+    .unwrap();
+    assert_eq!(summary.state_root_count, 2);
+    assert_eq!(summary.reachable_tries, 4);
+    let mut expected_keys: Vec<Vec<u8>> = [0, 1, 2, 4]
+        .iter()
+        .map(|idx| data[*idx].0.to_bytes().unwrap())
+        .collect();
+    expected_keys.sort();
+    assert_eq!(
+        remaining_keys(&trie_store_entries(&trie_dir)),
+        expected_keys
+    );
+    // The scratch set was removed.
+    assert_eq!(fs::read_dir(scratch_dir.path()).unwrap().count(), 0);
+}
+
+#[test]
+fn gc_dry_run_should_count_unreachable_tries() {
+    let (trie_dir, data) = create_test_trie_store();
+    let storage_dir = create_test_storage(&[data[3].0, data[4].0]);
+    let entries_before = trie_store_entries(&trie_dir);
+
+    let summary = gc::trie_gc(
+        &storage_dir,
+        &trie_dir,
+        *DEFAULT_MAX_DB_SIZE,
+        BlockSelection::LastBlocks(1),
+        options(1, true),
+    )
+    .unwrap();
+
+    // Only `node2`, `leaf2` and `leaf3` are reachable, but nothing is
+    // deleted.
+    let unreachable_bytes = entries_bytes(&entries_before, &data, &[0, 3, 5]);
+    assert_eq!(
+        summary,
+        GcSummary {
+            state_root_count: 1,
+            reachable_tries: 3,
+            reachable_bytes: entries_bytes(&entries_before, &data, &[1, 2, 4]),
+            missing_tries: 0,
+            unreachable_tries: 3,
+            unreachable_bytes,
+        }
+    );
+    let total_bytes: u64 = entries_before
+        .iter()
+        .map(|(key, value)| (key.len() + value.len()) as u64)
+        .sum();
+    assert_eq!(
+        summary.unreachable_percentage(),
+        100.0 * unreachable_bytes as f64 / total_bytes as f64
+    );
+    assert_eq!(trie_store_entries(&trie_dir), entries_before);
+}
+
+#[test]
+fn gc_dry_run_should_count_missing_tries() {
+    let (trie_dir, data) = create_test_trie_store();
+    let missing_root = Digest::hash(b"missing");
+    let storage_dir = create_test_storage(&[data[4].0, missing_root]);
+    let entries_before = trie_store_entries(&trie_dir);
+
+    let summary = gc::trie_gc(
+        &storage_dir,
+        &trie_dir,
+        *DEFAULT_MAX_DB_SIZE,
+        BlockSelection::All,
+        options(10, true),
+    )
+    .unwrap();
+    assert_eq!(summary.state_root_count, 2);
+    assert_eq!(summary.reachable_tries, 3);
+    assert_eq!(summary.missing_tries, 1);
+    assert_eq!(summary.unreachable_tries, 3);
+    assert_eq!(trie_store_entries(&trie_dir), entries_before);
+}
+
+#[test]
+fn gc_dry_run_should_count_shared_missing_tries_once() {
+    let (trie_dir, data) = create_test_trie_store();
+    // A second state root sharing `leaf1` with `node1`, which is missing.
+    let node_3: Trie<Bytes, Bytes> = {
+        let mut pointer_block = PointerBlock::new();
+        pointer_block[0] = Some(Pointer::LeafPointer(data[0].0));
+        pointer_block[2] = Some(Pointer::LeafPointer(data[2].0));
+        Trie::Node {
+            pointer_block: Box::new(pointer_block),
+        }
+    };
+    let node_3_hash = Digest::hash(node_3.to_bytes().unwrap());
+    {
+        let env = LmdbEnvironment::new(&trie_dir, *DEFAULT_MAX_DB_SIZE, 512, true).unwrap();
+        let store = LmdbTrieStore::open(&env, None).unwrap();
+        let mut txn = env.create_read_write_txn().unwrap();
+        txn.put(
+            store.get_db(),
+            &node_3_hash.to_bytes().unwrap(),
+            &node_3.to_bytes().unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        txn.del(store.get_db(), &data[0].0.to_bytes().unwrap(), None)
+            .unwrap();
+        txn.commit().unwrap();
+    }
+    let storage_dir = create_test_storage(&[data[3].0, node_3_hash]);
+
+    let summary = gc::trie_gc(
+        &storage_dir,
+        &trie_dir,
+        *DEFAULT_MAX_DB_SIZE,
+        BlockSelection::All,
+        options(10, true),
+    )
+    .unwrap();
+    assert_eq!(summary.state_root_count, 2);
+    assert_eq!(summary.missing_tries, 1);
+    assert_eq!(summary.reachable_tries, data.len());
+    assert_eq!(summary.unreachable_tries, 0);
+}
+
+#[test]
+fn gc_should_not_delete_anything_on_missing_trie() {
+    let (trie_dir, data) = create_test_trie_store();
+    let missing_root = Digest::hash(b"missing");
+    let storage_dir = create_test_storage(&[data[4].0, missing_root]);
+    let entries_before = trie_store_entries(&trie_dir);
+
+    match gc::trie_gc(
+        &storage_dir,
+        &trie_dir,
+        *DEFAULT_MAX_DB_SIZE,
+        BlockSelection::All,
+        options(10, false),
+    ) {
+        Err(Error::MarkStateRoot(state_root, _)) => assert_eq!(state_root, missing_root),
+        Err(err) => panic!("Unexpected error: {err}"),
+        Ok(_) => panic!("Unexpected successful trie gc"),
+    }
+    assert_eq!(trie_store_entries(&trie_dir), entries_before);
+}
+
+#[test]
+fn gc_should_not_run_without_state_roots() {
+    let (trie_dir, _data) = create_test_trie_store();
+    let (storage_dir, storage) = create_empty_test_storage();
+    drop(storage);
+    let entries_before = trie_store_entries(&trie_dir);
+
+    match gc::trie_gc(
+        &storage_dir,
+        &trie_dir,
+        *DEFAULT_MAX_DB_SIZE,
+        BlockSelection::All,
+        options(10, false),
+    ) {
+        Err(Error::NoStateRoots) => {}
+        Err(err) => panic!("Unexpected error: {err}"),
+        Ok(_) => panic!("Unexpected successful trie gc"),
+    }
+    assert_eq!(trie_store_entries(&trie_dir), entries_before);
+}
@@ -0,0 +1,130 @@
+mod read_trie;
+mod stats;
+#[cfg(test)]
+mod tests;
+
+use std::{io::Error as IoError, path::Path};
+
+use anyhow::Error as AnyError;
+use clap::{Arg, ArgMatches, Command};
+use serde_json::Error as JsonSerializationError;
+use thiserror::Error as ThisError;
+
+use casper_hashing::Digest;
+
+use super::trie_compact::DEFAULT_MAX_DB_SIZE;
+use crate::common::trie_walk::Error as TrieWalkError;
+
+pub const COMMAND_NAME: &str = "trie-stats";
+const MAX_DB_SIZE: &str = "max-db-size";
+const OUTPUT: &str = "output";
+const OVERWRITE: &str = "overwrite";
+const STATE_ROOT_HASH: &str = "state-root-hash";
+const TRIE_STORE_PATH: &str = "trie";
+
+/// Errors encountered when walking the trie store.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error("Error serializing output: {0}")]
+    JsonSerialize(#[from] JsonSerializationError),
+    /// Error opening the trie store.
+    #[error("Error opening the trie store: {0}")]
+    OpenTrie(AnyError),
+    #[error("Error writing output: {0}")]
+    Output(#[from] IoError),
+    /// Error walking the tries under the state root.
+    #[error("Error walking the tries: {0}")]
+    Walk(#[from] TrieWalkError),
+}
+
+enum DisplayOrder {
+    TriePath,
+    StateRootHash,
+    MaxDbSize,
+    Output,
+    Overwrite,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Outputs statistics about the structure of the tries under a state root in JSON \
+            format.",
+        )
+        .arg(
+            Arg::new(TRIE_STORE_PATH)
+                .display_order(DisplayOrder::TriePath as usize)
+                .required(true)
+                .short('t')
+                .long(TRIE_STORE_PATH)
+                .takes_value(true)
+                .value_name("TRIE_STORE_DIR_PATH")
+                .help("Path of the directory with the `data.lmdb` file."),
+        )
+        .arg(
+            Arg::new(STATE_ROOT_HASH)
+                .display_order(DisplayOrder::StateRootHash as usize)
+                .required(true)
+                .short('s')
+                .long(STATE_ROOT_HASH)
+                .takes_value(true)
+                .value_name("STATE_ROOT_HASH")
+                .help("Hex encoded state root hash of the tries to walk."),
+        )
+        .arg(
+            Arg::new(MAX_DB_SIZE)
+                .display_order(DisplayOrder::MaxDbSize as usize)
+                .required(false)
+                .short('m')
+                .long(MAX_DB_SIZE)
+                .takes_value(true)
+                .default_value(DEFAULT_MAX_DB_SIZE)
+                .value_name("MAX_DB_SIZE")
+                .help("Maximum size the DB files are allowed to be, in bytes."),
+        )
+        .arg(
+            Arg::new(OUTPUT)
+                .display_order(DisplayOrder::Output as usize)
+                .short('o')
+                .long(OUTPUT)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help(
+                    "Path to where the program will output the statistics. \
+                    If unspecified, defaults to standard output.",
+                ),
+        )
+        .arg(
+            Arg::new(OVERWRITE)
+                .display_order(DisplayOrder::Overwrite as usize)
+                .required(false)
+                .short('w')
+                .long(OVERWRITE)
+                .takes_value(false)
+                .requires(OUTPUT)
+                .help(
+                    "Overwrite an already existing output file in destination \
+                    directory.",
+                ),
+        )
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let trie_path = Path::new(matches.value_of(TRIE_STORE_PATH).unwrap());
+    let state_root_hash = matches
+        .value_of(STATE_ROOT_HASH)
+        .map(|state_root_hash_str| {
+            Digest::from_hex(state_root_hash_str)
+                .expect("should parse state root hash to hex format")
+        })
+        .expect("should have state-root-hash arg");
+    let max_db_size = matches
+        .value_of(MAX_DB_SIZE)
+        .unwrap()
+        .parse()
+        .expect("Value of \"--max-db-size\" must be an integer.");
+    let output = matches.value_of(OUTPUT).map(Path::new);
+    let overwrite = matches.is_present(OVERWRITE);
+    read_trie::trie_stats(trie_path, max_db_size, state_root_hash, output, overwrite)
+}
@@ -0,0 +1,84 @@
+use std::{
+    fs::OpenOptions,
+    io::{self, Write},
+    path::Path,
+};
+
+use log::info;
+use serde_json::Error as JsonSerializationError;
+
+use casper_hashing::Digest;
+
+use crate::{common::trie_walk, subcommands::trie_compact::load_execution_engine};
+
+use super::{
+    stats::{TrieStats, TrieStatsSummary},
+    Error,
+};
+
+/// Number of visited tries between progress messages.
+const TRIES_PER_PROGRESS_MESSAGE: usize = 1_000_000;
+
+/// Walks every trie under `state_root_hash` in the trie store at
+/// `trie_path`, accounting for each of them in the returned statistics.
+pub(crate) fn get_trie_stats<P: AsRef<Path>>(
+    trie_path: P,
+    max_db_size: usize,
+    state_root_hash: Digest,
+    log_progress: bool,
+) -> Result<TrieStats, Error> {
+    let (engine_state, _env) =
+        load_execution_engine(trie_path, max_db_size, Digest::default(), true)
+            .map_err(Error::OpenTrie)?;
+
+    let mut stats = TrieStats::default();
+    let mut visited_tries = 0usize;
+    let mut tries_since_message = 0usize;
+    trie_walk::walk_tries(&engine_state, state_root_hash, |trie, depth| {
+        stats.feed(trie, depth);
+        visited_tries += 1;
+        tries_since_message += 1;
+        if log_progress && tries_since_message == TRIES_PER_PROGRESS_MESSAGE {
+            info!("Visited {} tries so far.", visited_tries);
+            tries_since_message = 0;
+        }
+        Ok::<_, Error>(())
+    })?;
+    Ok(stats)
+}
+
+pub(crate) fn dump_trie_stats_summary<W: Write + ?Sized>(
+    summary: &TrieStatsSummary,
+    out_writer: Box<W>,
+) -> Result<(), JsonSerializationError> {
+    serde_json::to_writer_pretty(out_writer, summary)
+}
+
+pub fn trie_stats<P1: AsRef<Path>, P2: AsRef<Path>>(
+    trie_path: P1,
+    max_db_size: usize,
+    state_root_hash: Digest,
+    output: Option<P2>,
+    overwrite: bool,
+) -> Result<(), Error> {
+    // Validate the output file early so that, in case this fails
+    // we don't unnecessarily walk the whole trie.
+    let mut log_progress = false;
+    let out_writer: Box<dyn Write> = if let Some(out_path) = output {
+        let file = OpenOptions::new()
+            .create_new(!overwrite)
+            .write(true)
+            .truncate(true)
+            .open(out_path)?;
+        log_progress = true;
+        Box::new(file)
+    } else {
+        Box::new(io::stdout())
+    };
+
+    let stats = get_trie_stats(trie_path, max_db_size, state_root_hash, log_progress)?;
+    let summary = TrieStatsSummary::new(state_root_hash, stats);
+    dump_trie_stats_summary(&summary, out_writer)?;
+
+    Ok(())
+}
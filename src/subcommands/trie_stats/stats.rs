@@ -0,0 +1,163 @@
+use std::collections::BTreeMap;
+
+use casper_execution_engine::storage::trie::Trie;
+use casper_hashing::Digest;
+use casper_types::{bytesrepr::ToBytes, Key, StoredValue};
+use serde::{Deserialize, Serialize};
+
+use crate::subcommands::execution_results_summary::summary::{summarize_map, CollectionStatistics};
+
+/// Returns the name of the variant of `key`.
+fn key_variant(key: &Key) -> &'static str {
+    match key {
+        Key::Account(_) => "Account",
+        Key::Hash(_) => "Hash",
+        Key::URef(_) => "URef",
+        Key::Transfer(_) => "Transfer",
+        Key::DeployInfo(_) => "DeployInfo",
+        Key::EraInfo(_) => "EraInfo",
+        Key::Balance(_) => "Balance",
+        Key::Bid(_) => "Bid",
+        Key::Withdraw(_) => "Withdraw",
+        Key::Dictionary(_) => "Dictionary",
+        Key::SystemContractRegistry => "SystemContractRegistry",
+        Key::EraSummary => "EraSummary",
+    }
+}
+
+/// Returns the name of the variant of `value`.
+fn stored_value_variant(value: &StoredValue) -> &'static str {
+    match value {
+        StoredValue::CLValue(_) => "CLValue",
+        StoredValue::Account(_) => "Account",
+        StoredValue::ContractWasm(_) => "ContractWasm",
+        StoredValue::Contract(_) => "Contract",
+        StoredValue::ContractPackage(_) => "ContractPackage",
+        StoredValue::Transfer(_) => "Transfer",
+        StoredValue::DeployInfo(_) => "DeployInfo",
+        StoredValue::EraInfo(_) => "EraInfo",
+        StoredValue::Bid(_) => "Bid",
+        StoredValue::Withdraw(_) => "Withdraw",
+    }
+}
+
+/// Increments the frequency of `key` in `map`.
+fn increment<K: Ord>(map: &mut BTreeMap<K, usize>, key: K) {
+    *map.entry(key).or_default() += 1;
+}
+
+/// Holds the statistics of the tries under a state root.
+#[derive(Debug, Default)]
+pub struct TrieStats {
+    /// Number of `Trie::Leaf`s.
+    pub leaf_count: usize,
+    /// Number of `Trie::Node`s.
+    pub node_count: usize,
+    /// Number of `Trie::Extension`s.
+    pub extension_count: usize,
+    /// Ordered frequency list of leaf depths, i.e. the number of tries
+    /// between the state root and the leaf, extensions included.
+    pub leaf_depths: BTreeMap<usize, usize>,
+    /// Ordered frequency list of the number of children of nodes.
+    pub branching_factors: BTreeMap<usize, usize>,
+    /// Ordered frequency lists of the sizes of the bytesrepr encoded leaf
+    /// values, by `StoredValue` variant.
+    pub value_sizes: BTreeMap<&'static str, BTreeMap<usize, usize>>,
+    /// Number of leaves by `Key` variant.
+    pub key_counts: BTreeMap<&'static str, usize>,
+}
+
+impl TrieStats {
+    /// Accounts for `trie`, found `depth` tries below the state root.
+    pub fn feed(&mut self, trie: &Trie<Key, StoredValue>, depth: usize) {
+        match trie {
+            Trie::Leaf { key, value } => {
+                self.leaf_count += 1;
+                increment(&mut self.leaf_depths, depth);
+                increment(&mut self.key_counts, key_variant(key));
+                increment(
+                    self.value_sizes
+                        .entry(stored_value_variant(value))
+                        .or_default(),
+                    value.serialized_length(),
+                );
+            }
+            Trie::Node { pointer_block } => {
+                self.node_count += 1;
+                increment(
+                    &mut self.branching_factors,
+                    pointer_block.as_indexed_pointers().count(),
+                );
+            }
+            Trie::Extension { .. } => self.extension_count += 1,
+        }
+    }
+}
+
+/// Statistics of the sizes of the leaf values of a `StoredValue` variant.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub(crate) struct ValueSizeSummary {
+    /// Number of leaves holding this variant.
+    pub(crate) count: usize,
+    /// Statistics of the bytesrepr encoded sizes of the values, in bytes.
+    pub(crate) size: CollectionStatistics,
+}
+
+/// Summary of a [`TrieStats`].
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub(crate) struct TrieStatsSummary {
+    /// State root the tries were walked from.
+    pub(crate) state_root_hash: Digest,
+    /// Number of `Trie::Leaf`s.
+    pub(crate) leaf_count: usize,
+    /// Number of `Trie::Node`s.
+    pub(crate) node_count: usize,
+    /// Number of `Trie::Extension`s.
+    pub(crate) extension_count: usize,
+    /// Statistics of the leaf depths.
+    pub(crate) leaf_depth: CollectionStatistics,
+    /// Number of leaves by depth.
+    pub(crate) leaf_depth_histogram: BTreeMap<usize, usize>,
+    /// Statistics of the number of children of nodes.
+    pub(crate) branching_factor: CollectionStatistics,
+    /// Number of nodes by number of children.
+    pub(crate) branching_factor_histogram: BTreeMap<usize, usize>,
+    /// Statistics of the leaf value sizes by `StoredValue` variant.
+    pub(crate) value_sizes: BTreeMap<String, ValueSizeSummary>,
+    /// Number of leaves by `Key` variant.
+    pub(crate) key_counts: BTreeMap<String, usize>,
+}
+
+impl TrieStatsSummary {
+    pub(crate) fn new(state_root_hash: Digest, stats: TrieStats) -> Self {
+        let value_sizes = stats
+            .value_sizes
+            .iter()
+            .map(|(variant, sizes)| {
+                let summary = ValueSizeSummary {
+                    count: sizes.values().sum(),
+                    size: summarize_map(sizes),
+                };
+                (variant.to_string(), summary)
+            })
+            .collect();
+        let key_counts = stats
+            .key_counts
+            .into_iter()
+            .map(|(variant, count)| (variant.to_string(), count))
+            .collect();
+
+        Self {
+            state_root_hash,
+            leaf_count: stats.leaf_count,
+            node_count: stats.node_count,
+            extension_count: stats.extension_count,
+            leaf_depth: summarize_map(&stats.leaf_depths),
+            leaf_depth_histogram: stats.leaf_depths,
+            branching_factor: summarize_map(&stats.branching_factors),
+            branching_factor_histogram: stats.branching_factors,
+            value_sizes,
+            key_counts,
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use std::{collections::HashMap, fs};
+
+use tempfile::tempdir;
+
+use casper_hashing::Digest;
+use casper_types::{
+    account::AccountHash, bytesrepr::ToBytes, CLValue, ContractWasm, Key, StoredValue, U512,
+};
+
+use crate::{
+    common::trie_walk::Error as TrieWalkError,
+    subcommands::trie_compact::tests::{create_test_global_state, DEFAULT_MAX_DB_SIZE},
+};
+
+use super::{read_trie, stats::TrieStatsSummary, Error};
+
+fn test_stored_values() -> HashMap<Key, StoredValue> {
+    let mut stored_values = HashMap::new();
+    stored_values.insert(
+        Key::Account(AccountHash::new([1u8; 32])),
+        StoredValue::CLValue(CLValue::from_t(1u64).unwrap()),
+    );
+    stored_values.insert(
+        Key::Hash([2u8; 32]),
+        StoredValue::ContractWasm(ContractWasm::new(vec![0u8; 100])),
+    );
+    stored_values.insert(
+        Key::Hash([3u8; 32]),
+        StoredValue::ContractWasm(ContractWasm::new(vec![0u8; 10])),
+    );
+    stored_values.insert(
+        Key::Balance([4u8; 32]),
+        StoredValue::CLValue(CLValue::from_t(U512::from(10)).unwrap()),
+    );
+    stored_values
+}
+
+#[test]
+fn trie_stats_should_count_leaves_by_variant() {
+    let (trie_dir, state_root_hash) = create_test_global_state(test_stored_values());
+
+    let stats = read_trie::get_trie_stats(
+        trie_dir.path(),
+        *DEFAULT_MAX_DB_SIZE,
+        state_root_hash,
+        false,
+    )
+    .unwrap();
+    let summary = TrieStatsSummary::new(state_root_hash, stats);
+
+    assert_eq!(summary.state_root_hash, state_root_hash);
+    assert_eq!(summary.leaf_count, 4);
+    assert_eq!(summary.leaf_depth_histogram.values().sum::<usize>(), 4);
+    assert!(summary.leaf_depth.median > 0);
+    // The leaves are under the root node, so there is at least one node
+    // with as many children as there are distinct first key bytes.
+    assert!(summary.node_count > 0);
+    assert_eq!(
+        summary.branching_factor_histogram.values().sum::<usize>(),
+        summary.node_count
+    );
+    assert!(summary.branching_factor.max >= 2);
+
+    let key_counts: Vec<(&str, usize)> = summary
+        .key_counts
+        .iter()
+        .map(|(variant, count)| (variant.as_str(), *count))
+        .collect();
+    assert_eq!(
+        key_counts,
+        vec![("Account", 1), ("Balance", 1), ("Hash", 2)]
+    );
+
+    let stored_values = test_stored_values();
+    let wasm_sizes = &summary.value_sizes["ContractWasm"];
+    assert_eq!(wasm_sizes.count, 2);
+    assert_eq!(
+        wasm_sizes.size.max,
+        stored_values[&Key::Hash([2u8; 32])].serialized_length()
+    );
+    let cl_value_sizes = &summary.value_sizes["CLValue"];
+    assert_eq!(cl_value_sizes.count, 2);
+    assert_eq!(summary.value_sizes.len(), 2);
+}
+
+#[test]
+fn trie_stats_should_fail_on_missing_state_root() {
+    let (trie_dir, _state_root_hash) = create_test_global_state(test_stored_values());
+    let missing_root = Digest::hash(b"missing");
+
+    match read_trie::get_trie_stats(trie_dir.path(), *DEFAULT_MAX_DB_SIZE, missing_root, false) {
+        Err(Error::Walk(TrieWalkError::MissingTrie(trie_key))) => {
+            assert_eq!(trie_key, missing_root)
+        }
+        Err(err) => panic!("Unexpected error: {err}"),
+        Ok(_) => panic!("Unexpected successful trie stats"),
+    }
+}
+
+#[test]
+fn trie_stats_should_write_json_summary() {
+    let (trie_dir, state_root_hash) = create_test_global_state(test_stored_values());
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("stats.json");
+
+    read_trie::trie_stats(
+        trie_dir.path(),
+        *DEFAULT_MAX_DB_SIZE,
+        state_root_hash,
+        Some(&out_path),
+        false,
+    )
+    .unwrap();
+    let summary: TrieStatsSummary = serde_json::from_slice(&fs::read(&out_path).unwrap()).unwrap();
+    assert_eq!(summary.leaf_count, 4);
+
+    // The output file isn't overwritten without the flag.
+    assert!(matches!(
+        read_trie::trie_stats(
+            trie_dir.path(),
+            *DEFAULT_MAX_DB_SIZE,
+            state_root_hash,
+            Some(&out_path),
+            false,
+        ),
+        Err(Error::Output(_))
+    ));
+    read_trie::trie_stats(
+        trie_dir.path(),
+        *DEFAULT_MAX_DB_SIZE,
+        state_root_hash,
+        Some(&out_path),
+        true,
+    )
+    .unwrap();
+}